@@ -2,7 +2,7 @@ use std::slice::{self, Chunks};
 use hidapi::HidDevice;
 use crate::{packet::{IAPCommand, IAPacket}, error::*};
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub enum IAPState {
     Idle,
     Programming,
@@ -45,6 +45,18 @@ impl<'a> IAP<'a> {
         Ok(len)
     }
 
+    /// 固件总字节数
+    pub fn firmware_len(&self) -> usize {
+        self.firmware_data.as_ref().map(|v| v.len()).unwrap_or_default()
+    }
+
+    /// 在两个数据包之间中止烧录，不发送结束命令，设备会停留在IAP模式
+    pub fn abort(&mut self) {
+        self.tmp_data = None;
+        self.flash_addr = 0;
+        self.state = IAPState::Idle;
+    }
+
     pub fn program(&mut self) -> Result<u16> {
         if self.state != IAPState::Programming {
             return Err(Error::Other("IAP is not in programming state"));
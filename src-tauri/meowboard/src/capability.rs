@@ -0,0 +1,70 @@
+use crate::config;
+use ciborium::Value;
+use meowpad::{cbor::map_get_mut, error::Error, FirmwareVersion, Result};
+use serde::{Deserialize, Serialize};
+
+/// 按键释放死区 (`rd`) 与单键RT开关 (`e`) 从该版本开始支持
+const KEY_RT_EXTRA_SINCE: FirmwareVersion = FirmwareVersion(0, 1, 2);
+
+/// 已连接固件支持的配置项，默认认为支持全部功能
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    pub release_dead_zone: bool,
+    pub rt_toggle: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self {
+            release_dead_zone: true,
+            rt_toggle: true,
+        }
+    }
+}
+
+impl Capabilities {
+    pub fn from_version(version: &str) -> Self {
+        let Ok(version) = version.parse::<FirmwareVersion>() else {
+            return Self::default();
+        };
+        Self {
+            release_dead_zone: version >= KEY_RT_EXTRA_SINCE,
+            rt_toggle: version >= KEY_RT_EXTRA_SINCE,
+        }
+    }
+
+    /// 拒绝在不支持的固件上使用非默认值的配置项
+    pub fn check_key(&self, cfg: &config::Device) -> Result<()> {
+        let default = config::KeyConfig::from(crate::cbor::KeyRTConfig::default());
+        for key in cfg.keys.iter() {
+            if !self.release_dead_zone && key.release_dead_zone != default.release_dead_zone {
+                return Err(Error::UnsupportedFeature("release_dead_zone"));
+            }
+            if !self.rt_toggle && key.rt_enabled != default.rt_enabled {
+                return Err(Error::UnsupportedFeature("rt_enabled"));
+            }
+        }
+        Ok(())
+    }
+
+    /// 移除固件不支持的CBOR字段
+    pub fn strip_key(&self, value: &mut Value) {
+        let Some(keys) = map_get_mut(value, "ks").and_then(|v| v.as_array_mut()) else {
+            return;
+        };
+        for key in keys.iter_mut() {
+            self.strip_key_rt(key);
+        }
+    }
+
+    /// 同 `strip_key`，用于单个按键的行程设置
+    pub fn strip_key_rt(&self, value: &mut Value) {
+        if let Some(map) = value.as_map_mut() {
+            map.retain(|(k, _)| match k.as_text() {
+                Some("rd") => self.release_dead_zone,
+                Some("e") => self.rt_toggle,
+                _ => true,
+            });
+        }
+    }
+}
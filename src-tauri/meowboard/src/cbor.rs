@@ -5,7 +5,7 @@ use palette::{rgb::channels::Argb, WithAlpha};
 use palette::Srgb;
 use serde::{Deserialize, Serialize};
 use serde_with::*;
-use std::io::Cursor;
+pub use meowpad::cbor::CborConvertor;
 
 #[inline]
 pub unsafe fn serialize_raw<T: Sized>(src: &T) -> &[u8] {
@@ -13,7 +13,7 @@ pub unsafe fn serialize_raw<T: Sized>(src: &T) -> &[u8] {
 }
 
 #[repr(C)]
-#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
 #[allow(non_snake_case)]
 pub struct KeyRTConfig {
     #[serde(rename = "p")]
@@ -22,15 +22,19 @@ pub struct KeyRTConfig {
     pub ReleasePercentage: u8,
     #[serde(rename = "d")]
     pub DeadZone: u8,
-    #[serde(rename = "rd")]
+    #[serde(rename = "rd", default)]
     pub ReleaseDeadZone: u8,
-    #[serde(rename = "e")]
+    #[serde(rename = "e", default = "default_true")]
     pub RtEnabled: bool,
 }
 
+fn default_true() -> bool {
+    true
+}
+
 #[repr(C)]
 #[serde_as]
-#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
 #[allow(non_snake_case)]
 pub struct Device {
     #[serde(rename = "ks")]
@@ -55,23 +59,8 @@ pub struct Device {
     pub led_color: u32,
 }
 
-pub trait CborConvertor
-where
-    Self: Sized + Serialize,
-    for<'de> Self: Deserialize<'de>,
-{
-    fn from_cbor<T: AsRef<[u8]>>(data: T) -> Result<Self, ciborium::de::Error<std::io::Error>> {
-        Ok(ciborium::de::from_reader(Cursor::new(data))?)
-    }
-
-    fn to_cbor(self) -> Vec<u8> {
-        let mut data = vec![];
-        ciborium::ser::into_writer(&self, &mut data).unwrap();
-        data
-    }
-}
-
 impl CborConvertor for Device {}
+impl CborConvertor for KeyRTConfig {}
 
 impl From<config::KeyConfig> for KeyRTConfig {
     fn from(cfg: config::KeyConfig) -> Self {
@@ -1,9 +1,10 @@
 use crate::{cbor, keymap};
-use meowpad::{error::Error, keycode::KeyValue, KbReport, KeyCode};
+use meowpad::{diff::{changes_where, ConfigDiff}, error::Error, keycode::KeyValue, schema::Rgb, validate::{Diagnostic, Validator}, KbReport, KeyCode};
 use num::FromPrimitive;
 use num_derive::{FromPrimitive, ToPrimitive};
 use palette::rgb::channels::Argb;
 use palette::Srgb;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_repr::*;
 use serde_with::*;
@@ -11,30 +12,43 @@ use serde_with::*;
 
 
 
-#[derive(Serialize, Deserialize, Clone, Debug, Default, Copy)]
+/// 行程相关的数值以 0.5% 为单位
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Default, Copy)]
 pub struct KeyConfig {
+    #[schemars(range(min = 1, max = 200))]
     pub press_percentage: u8,
+    #[schemars(range(min = 1, max = 200))]
     pub release_percentage: u8,
+    #[schemars(range(max = 200))]
     pub dead_zone: u8,   // 0-30
+    #[schemars(range(max = 200))]
     pub release_dead_zone: u8,   // 0-30
     pub rt_enabled: bool
 }
 
 #[serde_as]
-#[derive(Serialize, Deserialize, Clone, Debug, Copy)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Copy)]
 pub struct Device {
     #[serde_as(as = "[_; 64]")]
+    #[schemars(with = "Vec<KeyConfig>", length(equal = 64))]
     pub keys: [KeyConfig; 64],
     #[serde_as(as = "[_; 64]")]
+    #[schemars(with = "Vec<KeyValue>", length(equal = 64))]
     pub normal_layer: [KeyValue; 64],
     #[serde_as(as = "[_; 64]")]
+    #[schemars(with = "Vec<KeyValue>", length(equal = 64))]
     pub fn_layer: [KeyValue; 64],
+    /// 以 1/8 ms 为单位
+    #[schemars(range(max = 400))]
     pub jitters_elimination_time: u16,
     pub high_reportrate: bool,
     pub key_proof: bool,
     pub auto_calibration: bool,
+    #[schemars(range(max = 3))]
     pub hall_filter: u8,
+    #[schemars(range(max = 50))]
     pub max_brightness: u8,
+    #[schemars(with = "Rgb")]
     pub led_color: Srgb<u8>,
 }
 
@@ -44,6 +58,27 @@ impl Default for Device {
     }
 }
 
+impl Device {
+    /// 按JSON Schema中的范围检查
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        Validator::from_schema(self).finish()
+    }
+
+    pub fn diff(&self, new: &Self) -> ConfigDiff {
+        let mut diff = ConfigDiff::default();
+        for (i, (a, b)) in self.keys.iter().zip(&new.keys).enumerate() {
+            diff.key(i, a, b, |_| true);
+        }
+        diff.layer(0, &self.normal_layer, &new.normal_layer);
+        diff.layer(1, &self.fn_layer, &new.fn_layer);
+        const LIGHT: [&str; 2] = ["max_brightness", "led_color"];
+        const SKIP: [&str; 3] = ["keys", "normal_layer", "fn_layer"];
+        diff.light = changes_where(self, new, |f| LIGHT.contains(&f));
+        diff.device = changes_where(self, new, |f| !LIGHT.contains(&f) && !SKIP.contains(&f));
+        diff
+    }
+}
+
 impl TryFrom<cbor::Device> for Device {
     type Error = Error;
     fn try_from(cfg: cbor::Device) -> Result<Self, Self::Error> {
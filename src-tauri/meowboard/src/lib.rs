@@ -1,5 +1,7 @@
 pub mod cbor;
 pub mod config;
+pub mod capability;
+pub mod migrate;
 mod meowboard;
 mod packet_id;
 mod keymap;
@@ -19,7 +21,374 @@ mod tests {
     fn cbor_kb() {
         let c = cbor::Device::default().to_cbor();
         dbg!(c.hex_dump());
-        assert!(c.len() <= 1);
+        assert_eq!(cbor::Device::from_cbor(&c).unwrap(), cbor::Device::default());
     }
 
+    #[test]
+    fn capabilities_strip_old_firmware() {
+        use crate::capability::Capabilities;
+        let caps = Capabilities::from_version("0.1.1");
+        assert!(!caps.release_dead_zone && !caps.rt_toggle);
+        assert_eq!(Capabilities::from_version("0.1.2"), Capabilities::default());
+
+        let data = cbor::Device::default().to_cbor_with(|v| caps.strip_key(v));
+        let value: ciborium::Value = ciborium::de::from_reader(Cursor::new(&data)).unwrap();
+        let keys = value.as_map().unwrap().iter().find(|(k, _)| k.as_text() == Some("ks")).unwrap();
+        for key in keys.1.as_array().unwrap() {
+            assert!(key.as_map().unwrap().iter().all(|(k, _)| k.as_text() != Some("rd") && k.as_text() != Some("e")));
+        }
+
+        // 旧固件返回的配置缺少的字段使用默认值
+        let parsed = cbor::Device::from_cbor(&data).unwrap();
+        let mut cfg = config::Device::try_from(parsed).unwrap();
+        assert!(caps.check_key(&cfg).is_ok());
+        cfg.keys[3].release_dead_zone = 5;
+        assert!(caps.check_key(&cfg).is_err());
+    }
+
+    #[test]
+    fn cbor_lenient_decode() {
+        use ciborium::Value;
+        let mut value = Value::serialized(&cbor::Device::default()).unwrap();
+        let map = value.as_map_mut().unwrap();
+        map.retain(|(k, _)| k.as_text() != Some("hf"));
+        map.push((Value::Text("zz".into()), Value::Integer(7.into())));
+        let ks = map.iter_mut().find(|(k, _)| k.as_text() == Some("ks")).unwrap();
+        ks.1.as_array_mut().unwrap()[0].as_map_mut().unwrap().push((Value::Text("x".into()), Value::Bool(true)));
+        ks.1.as_array_mut().unwrap().push(Value::Integer(1.into()));
+        let mut data = vec![];
+        ciborium::ser::into_writer(&value, &mut data).unwrap();
+
+        assert!(cbor::Device::from_cbor(&data).is_err());
+        let (cfg, raw, mut report) = meowpad::cbor::from_cbor_lenient::<cbor::Device>(&data).unwrap();
+        report.ignored.sort();
+        assert_eq!(report.defaulted, vec!["hf".to_owned()]);
+        assert_eq!(report.ignored, vec!["ks[0].x".to_owned(), "ks[64..]".to_owned(), "zz".to_owned()]);
+        assert_eq!(cfg.HallFilter, cbor::Device::default().HallFilter);
+
+        // 写回时保留未知字段
+        let out = cfg.to_cbor_with(|v| meowpad::cbor::restore_unknown(v, &raw));
+        let (_, _, mut report) = meowpad::cbor::from_cbor_lenient::<cbor::Device>(&out).unwrap();
+        report.ignored.sort();
+        assert!(report.defaulted.is_empty());
+        assert_eq!(report.ignored, vec!["ks[0].x".to_owned(), "ks[64..]".to_owned(), "zz".to_owned()]);
+    }
+
+    /// 前端手写的类型与JSON Schema的字段一致
+    #[test]
+    fn ts_interfaces() {
+        use ::meowpad::schema::{schema_fields, ts_interface_fields, Rgb};
+        let config = include_str!("../../../src/apis/meowboard/config.ts");
+        assert_eq!(ts_interface_fields(config, "IKeyboard"), schema_fields::<config::Device>());
+        assert_eq!(ts_interface_fields(config, "IKeyConfigBoard"), schema_fields::<config::KeyConfig>());
+        assert_eq!(ts_interface_fields(include_str!("../../../src/interface.ts"), "IRgb"), schema_fields::<Rgb>());
+    }
+
+    /// 各版本固件导出的配置
+    #[test]
+    fn migrate_golden_cbor() {
+        use crate::migrate::*;
+        for (data, version) in [
+            (&include_bytes!("../fixtures/pure64-0.1.1.cbor")[..], 1),
+            (&include_bytes!("../fixtures/pure64-0.1.2.cbor")[..], 2),
+        ] {
+            let mut value: ciborium::Value = ciborium::de::from_reader(data).unwrap();
+            assert_eq!(migrate_cbor(&mut value, None), version);
+            assert_eq!(detect_cbor_version(&value), SCHEMA_VERSION);
+            let (cfg, report) = meowpad::cbor::from_value_lenient::<cbor::Device>(value).unwrap();
+            assert!(report.is_empty(), "{:?}", report);
+            assert_eq!(cfg.KeyConfigs[0].PressPercentage, 20);
+            assert_eq!(cfg.KeyConfigs[0].ReleasePercentage, 12);
+            assert_eq!(cfg.KeyConfigs[3].DeadZone, 10);
+            assert_eq!(cfg.JittersEliminationTime, 80);
+            assert_eq!(cfg.MaxBrightness, 30);
+            if version == 1 {
+                assert!(cfg.KeyConfigs.iter().all(|k| k.ReleaseDeadZone == 0 && k.RtEnabled));
+            } else {
+                assert_eq!(cfg.KeyConfigs[0].ReleaseDeadZone, 5);
+                assert!(!cfg.KeyConfigs[1].RtEnabled);
+            }
+        }
+
+        // 设备报告的版本优先于推断
+        let mut value: ciborium::Value =
+            ciborium::de::from_reader(&include_bytes!("../fixtures/pure64-0.1.2.cbor")[..]).unwrap();
+        assert_eq!(migrate_cbor(&mut value, Some(1)), 1);
+        let (cfg, _) = meowpad::cbor::from_value_lenient::<cbor::Device>(value).unwrap();
+        assert_eq!(cfg.KeyConfigs[0].ReleaseDeadZone, 5);
+    }
+
+    #[test]
+    fn migrate_golden_config() {
+        use crate::migrate::*;
+        let mut table: toml::Table = toml::from_str(include_str!("../fixtures/pure64-config-v1.toml")).unwrap();
+        let key = table.remove("key").unwrap();
+        assert!(key.clone().try_into::<config::Device>().is_err());
+
+        let cfg = config_from(key.clone(), 0).unwrap();
+        assert_eq!(cfg.keys[0].press_percentage, 20);
+        assert_eq!(cfg.keys[0].release_percentage, 12);
+        assert_eq!(cfg.max_brightness, 30);
+        assert!(cfg.keys.iter().all(|k| k.release_dead_zone == 0 && k.rt_enabled));
+
+        let cfg2 = config_from(key, 1).unwrap();
+        assert_eq!(cbor::Device::from(cfg).to_cbor(), cbor::Device::from(cfg2).to_cbor());
+    }
+
+    #[test]
+    fn validate_ranges() {
+        use meowpad::validate::Severity;
+        assert!(config::Device::default().validate().is_empty());
+
+        let mut cfg = config::Device::default();
+        cfg.keys[3].press_percentage = 0;
+        cfg.max_brightness = 255;
+        cfg.hall_filter = 3;
+        let diagnostics = cfg.validate();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].path, "keys[3].press_percentage");
+        assert_eq!((diagnostics[0].min, diagnostics[0].max), (1, 200));
+        assert_eq!(diagnostics[1].path, "max_brightness");
+        assert_eq!(diagnostics[1].value, 255);
+        assert!(diagnostics.iter().all(|d| d.severity == Severity::Error));
+    }
+
+    #[test]
+    fn config_schema() {
+        let schema = schemars::schema_for!(config::Device);
+        let schema = schema.as_value();
+        let press = schema.pointer("/$defs/KeyConfig/properties/press_percentage").unwrap();
+        assert_eq!(press["minimum"], 1);
+        assert_eq!(press["maximum"], 200);
+        assert_eq!(schema.pointer("/properties/keys/minItems").unwrap(), 64);
+        assert_eq!(schema.pointer("/$defs/Rgb/properties/red/maximum").unwrap(), 255);
+        assert!(schema.pointer("/$defs/KeyCode/enum").unwrap().as_array().unwrap().len() > 100);
+    }
+
+    #[test]
+    fn config_diff() {
+        use meowpad::{keycode::KeyValue, KeyCode};
+        let old = config::Device::default();
+        assert!(old.diff(&old).is_empty());
+
+        let mut new = old;
+        new.keys[3].dead_zone += 10;
+        new.fn_layer[5] = KeyValue::Keyboard(KeyCode::A);
+        new.led_color.red = new.led_color.red.wrapping_add(1);
+        new.hall_filter = 2;
+        let diff = old.diff(&new);
+        assert_eq!(diff.keys.len(), 1);
+        assert_eq!(diff.keys[0].index, 3);
+        assert_eq!(diff.keys[0].changes[0].path, "dead_zone");
+        assert_eq!((diff.keymap[0].layer, diff.keymap[0].position), (1, 5));
+        assert_eq!(diff.light[0].path, "led_color.red");
+        assert_eq!(diff.device.len(), 1);
+        assert_eq!(diff.device[0].path, "hall_filter");
+        assert_eq!(diff.to_string().lines().count(), 4);
+    }
+
+    /// 按顺序返回预设响应的模拟设备
+    #[derive(Default)]
+    struct MockDevice {
+        responses: std::cell::RefCell<std::collections::VecDeque<[u8; 64]>>,
+        written: std::cell::RefCell<Vec<Vec<u8>>>,
+    }
+
+    impl MockDevice {
+        fn respond(&self, id: PacketID, data: &[u8]) {
+            let mut frame = [0u8; 64];
+            frame[0] = id as u8;
+            frame[1..3].copy_from_slice(&(data.len() as u16).to_be_bytes());
+            frame[3..3 + data.len()].copy_from_slice(data);
+            self.responses.borrow_mut().push_back(frame);
+        }
+
+        /// 超过一帧的响应，后续帧不带包头
+        fn respond_long(&self, id: PacketID, data: &[u8]) {
+            let first = data.len().min(61);
+            let mut frame = [0u8; 64];
+            frame[0] = id as u8;
+            frame[1..3].copy_from_slice(&(data.len() as u16).to_be_bytes());
+            frame[3..3 + first].copy_from_slice(&data[..first]);
+            self.responses.borrow_mut().push_back(frame);
+            for chunk in data[first..].chunks(64) {
+                let mut frame = [0u8; 64];
+                frame[..chunk.len()].copy_from_slice(chunk);
+                self.responses.borrow_mut().push_back(frame);
+            }
+        }
+    }
+
+    impl meowpad::Device for MockDevice {
+        fn get_serial(&self) -> meowpad::Result<String> {
+            Ok("mock".to_owned())
+        }
+
+        fn write(&self, data: &[u8]) -> meowpad::Result<usize> {
+            self.written.borrow_mut().push(data.to_vec());
+            Ok(data.len())
+        }
+
+        fn read(&self, buf: &mut [u8]) -> meowpad::Result<usize> {
+            let frame = self.responses.borrow_mut().pop_front().ok_or(meowpad::error::Error::Disconnect)?;
+            buf.copy_from_slice(&frame);
+            Ok(frame.len())
+        }
+
+        fn read_timeout(&self, buf: &mut [u8], _timeout: i32) -> meowpad::Result<usize> {
+            match self.responses.borrow_mut().pop_front() {
+                Some(frame) => {
+                    buf.copy_from_slice(&frame);
+                    Ok(frame.len())
+                }
+                None => Ok(0),
+            }
+        }
+
+        fn clear_buffer(&self) -> meowpad::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn apply_verifies_before_saving() {
+        let previous = cbor::Device::default();
+        let mut key = previous;
+        key.HallFilter = 2;
+
+        // 读回的配置与写入的不一致，恢复原配置且不保存
+        let mut board = Meowboard::new(MockDevice::default());
+        board.device.respond_long(PacketID::Ok, &previous.to_cbor());
+        board.device.respond(PacketID::Ok, &[]);
+        board.device.respond_long(PacketID::Ok, &previous.to_cbor());
+        board.device.respond(PacketID::Ok, &[]);
+        assert!(matches!(
+            board.apply(key),
+            Err(meowpad::error::Error::VerifyFailed("key_config"))
+        ));
+        assert_eq!(board.key_config, Some(previous));
+        assert_eq!(board.saved_key_config, Some(previous));
+        assert!(board.device.responses.borrow().is_empty());
+        assert!(!board.unsaved().any());
+        board.key_config = Some(key);
+        assert!(board.unsaved().key);
+
+        let mut board = Meowboard::new(MockDevice::default());
+        board.device.respond_long(PacketID::Ok, &previous.to_cbor());
+        board.device.respond(PacketID::Ok, &[]);
+        board.device.respond_long(PacketID::Ok, &key.to_cbor());
+        board.device.respond(PacketID::Ok, &[]);
+        board.apply(key).unwrap();
+        assert_eq!(board.saved_key_config, Some(key));
+        assert!(!board.unsaved().any());
+
+        // 保存失败时同样恢复原配置
+        let mut board = Meowboard::new(MockDevice::default());
+        board.device.respond_long(PacketID::Ok, &previous.to_cbor());
+        board.device.respond(PacketID::Ok, &[]);
+        board.device.respond_long(PacketID::Ok, &key.to_cbor());
+        board.device.respond(PacketID::Bad, &[]);
+        board.device.respond(PacketID::Ok, &[]);
+        assert!(board.apply(key).is_err());
+        assert_eq!(board.key_config, Some(previous));
+        assert!(board.device.responses.borrow().is_empty());
+        assert!(!board.unsaved().any());
+    }
+
+    #[test]
+    fn partial_update() {
+        use meowpad::{keycode::KeyValue, models::Descriptor, KeyCode};
+        let sent = |board: &Meowboard<MockDevice>| -> Vec<u8> {
+            board.device.written.borrow_mut().drain(..).map(|f| f[1]).collect()
+        };
+
+        let mut board = Meowboard::new(MockDevice::default());
+        board.descriptor = Some(Descriptor {
+            packets: vec![
+                PacketID::SetKeyConfig as u8,
+                PacketID::SetKeyRTConfig as u8,
+                PacketID::SetKeyMapEntry as u8,
+                PacketID::SetLedColor as u8,
+            ],
+            ..Default::default()
+        });
+        board.key_config = Some(cbor::Device::default());
+
+        let rt = cbor::KeyRTConfig { DeadZone: 40, ..Default::default() };
+        board.device.respond(PacketID::Ok, &[]);
+        board.set_key_rt_config(3, rt).unwrap();
+        assert_eq!(board.key_config.unwrap().KeyConfigs[3], rt);
+        let frame = board.device.written.borrow()[0].clone();
+        assert_eq!((frame[1], frame[4]), (PacketID::SetKeyRTConfig as u8, 3));
+        assert_eq!(sent(&board), vec![PacketID::SetKeyRTConfig as u8]);
+
+        board.device.respond(PacketID::Ok, &[]);
+        board.set_keymap_entry(1, 5, KeyValue::Keyboard(KeyCode::A)).unwrap();
+        let frame = board.device.written.borrow()[0].clone();
+        assert_eq!(frame[4..8], [1, 5, 1, KeyCode::A as u8]);
+        assert_eq!(sent(&board), vec![PacketID::SetKeyMapEntry as u8]);
+
+        board.device.respond(PacketID::Ok, &[]);
+        board.set_led_color(0xff00ff00).unwrap();
+        assert_eq!(sent(&board), vec![PacketID::SetLedColor as u8]);
+
+        // 修改了其他设置时整体写入
+        let mut key = board.key_config.unwrap();
+        key.KeyConfigs[0].DeadZone = 10;
+        key.MaxBrightness = 10;
+        board.device.respond(PacketID::Ok, &[]);
+        board.update_key_config(key).unwrap();
+        assert_eq!(sent(&board)[0], PacketID::SetKeyConfig as u8);
+
+        // 局部写入被拒绝时改为整体写入
+        key.KeyConfigs[1].DeadZone = 10;
+        board.device.respond(PacketID::Bad, &[]);
+        board.device.respond(PacketID::Ok, &[]);
+        board.update_key_config(key).unwrap();
+        assert_eq!(sent(&board)[0], PacketID::SetKeyRTConfig as u8);
+        assert!(board.device.responses.borrow().is_empty());
+
+        // 旧固件没有设备描述
+        board.descriptor = None;
+        board.device.respond(PacketID::Ok, &[]);
+        board.set_led_color(0xffffffff).unwrap();
+        assert_eq!(sent(&board)[0], PacketID::SetKeyConfig as u8);
+    }
+
+    #[test]
+    fn descriptor_negotiation() {
+        use meowpad::models::Descriptor;
+        let descriptor = Descriptor {
+            protocol_version: 1,
+            key_count: 64,
+            schema_version: 1,
+            max_packet_size: 64,
+            packets: vec![PacketID::GetFirmwareVersion as u8, PacketID::GetStatus as u8],
+            lighting_modes: vec![],
+        };
+        let mut data = vec![];
+        ciborium::ser::into_writer(&descriptor, &mut data).unwrap();
+
+        let mut board = Meowboard::new(MockDevice::default());
+        board.device.respond(PacketID::Ok, &data);
+        assert_eq!(board.get_descriptor().unwrap(), Some(descriptor));
+
+        // 不在描述中的数据包不会发送
+        let written = board.device.written.borrow().len();
+        assert!(matches!(
+            board.get_hall_config_part(0),
+            Err(meowpad::error::Error::UnsupportedPacket(108))
+        ));
+        assert_eq!(board.device.written.borrow().len(), written);
+
+        board.device.respond(PacketID::Ok, &[1, 1, 1]);
+        assert!(board.get_status().unwrap().enabled);
+
+        // 旧固件不认识该数据包
+        let mut board = Meowboard::new(MockDevice::default());
+        board.device.respond(PacketID::Bad, &[]);
+        assert_eq!(board.get_descriptor().unwrap(), None);
+        board.device.respond(PacketID::Ok, &[0; 48]);
+        assert!(board.get_hall_config_part(0).is_ok());
+    }
 }
@@ -1,18 +1,28 @@
 use crate::{
-    cbor, packet_id::PacketID
+    capability::Capabilities, cbor, keymap::KeyMap, migrate, packet_id::PacketID
 };
-use meowpad::{Packet, error::Error, Result, models::*, Device};
+use meowpad::{Packet, error::Error, Result, models::*, keycode::KeyValue, Device};
 use byteorder::{BigEndian, ReadBytesExt};
 use log::*;
 use num::FromPrimitive;
 use pretty_hex::*;
 use std::{io::Cursor, thread, time::Duration};
 use crate::cbor::CborConvertor;
+use meowpad::cbor::{from_value_lenient, restore_unknown, DecodeReport};
+
+/// 局部写入的数据包超过该数量时改为整体写入
+const PARTIAL_LIMIT: usize = 8;
 
 pub struct Meowboard<D: Device> {
     pub key_config: Option<cbor::Device>,
+    /// 设备返回的原始配置，写回时保留其中不认识的字段
+    pub key_config_raw: Option<ciborium::Value>,
+    /// 闪存中的配置，取自连接后第一次读取或最后一次保存
+    pub saved_key_config: Option<cbor::Device>,
     pub device_name: Option<String>,
     pub firmware_version: Option<String>,
+    pub capabilities: Capabilities,
+    pub descriptor: Option<Descriptor>,
     pub device: D,
 }
 
@@ -22,8 +32,12 @@ impl<D: Device> Meowboard<D> {
         Meowboard {
             device,
             key_config: None,
+            key_config_raw: None,
+            saved_key_config: None,
             device_name: None,
             firmware_version: None,
+            capabilities: Capabilities::default(),
+            descriptor: None,
         }
     }
 
@@ -54,7 +68,9 @@ impl<D: Device> Meowboard<D> {
         self.write(Packet::new(PacketID::GetFirmwareVersion, []))?;
         let packet = self.read()?; // 读取
         if packet.id == PacketID::Ok as u8 {
-            self.firmware_version = Some(String::from_utf8(packet.data)?);
+            let version = String::from_utf8(packet.data)?;
+            self.capabilities = Capabilities::from_version(&version);
+            self.firmware_version = Some(version);
             Ok(())
         } else {
             dbg!(packet.id);
@@ -63,6 +79,26 @@ impl<D: Device> Meowboard<D> {
         }
     }
 
+    /// 查询设备描述，旧固件不支持该数据包时返回 `None`
+    pub fn get_descriptor(&mut self) -> Result<Option<Descriptor>> {
+        self.write(Packet::new(PacketID::GetDescriptor, []))?;
+        let packet = self.read_timeout(1000)?;
+        if packet.id == PacketID::Ok as u8 {
+            let descriptor = Descriptor::from_cbor(packet.data)?;
+            self.descriptor = Some(descriptor.clone());
+            Ok(Some(descriptor))
+        } else {
+            debug!("设备不支持描述查询: {}", packet.id);
+            self.descriptor = None;
+            Ok(None)
+        }
+    }
+
+    /// 设备描述中报告的配置结构版本
+    fn schema_version(&self) -> Option<u32> {
+        self.descriptor.as_ref().map(|d| d.schema_version as u32)
+    }
+
     /// (按键配置状态，灯光配置状态，按键校准状态，按键是否启用)
     pub fn get_status(&mut self) -> Result<DeviceStatus> {
         self.write(Packet::new(PacketID::GetStatus, []))?;
@@ -202,18 +238,36 @@ impl<D: Device> Meowboard<D> {
     }
 
 
-    pub fn load_key_config(&mut self) -> Result<()> {
+    pub fn load_key_config(&mut self) -> Result<DecodeReport> {
         self.write(Packet::new(PacketID::GetKeyConfig, []))?;
         let packet = self.read()?;
-        self.key_config = Some(cbor::Device::from_cbor(packet.data)?);
-        Ok(())
+        let raw: ciborium::Value = ciborium::de::from_reader(packet.data.as_slice())?;
+        let mut value = raw.clone();
+        let version = migrate::migrate_cbor(&mut value, self.schema_version());
+        if version != migrate::SCHEMA_VERSION {
+            info!("按键配置结构版本 {} 已升级到 {}", version, migrate::SCHEMA_VERSION);
+        }
+        let (config, report) = from_value_lenient::<cbor::Device>(value)?;
+        if !report.is_empty() {
+            warn!("按键配置与当前版本不一致：{:?}", report);
+        }
+        self.saved_key_config.get_or_insert(config);
+        self.key_config = Some(config);
+        self.key_config_raw = Some(raw);
+        Ok(report)
     }
 
 
     pub fn set_key_config(&self) -> Result<()> {
         let config = self.key_config.ok_or(Error::EmptyConfig)?;
         // debug!("写入键盘配置：{:?}", config);
-        self.write(Packet::new(PacketID::SetKeyConfig, config.to_cbor()))?;
+        let data = config.to_cbor_with(|v| {
+            self.capabilities.strip_key(v);
+            if let Some(raw) = &self.key_config_raw {
+                restore_unknown(v, raw);
+            }
+        });
+        self.write(Packet::new(PacketID::SetKeyConfig, data))?;
         let packet = self.read()?; // 读取
         if packet.id == PacketID::Ok as u8 {
             Ok(())
@@ -224,10 +278,106 @@ impl<D: Device> Meowboard<D> {
         }
     }
 
+    /// 只写入与当前配置不同的按键行程、键位和灯光颜色，
+    /// 固件不支持局部写入、修改了其他设置或变化过多时整体写入
+    pub fn update_key_config(&mut self, key: cbor::Device) -> Result<()> {
+        let packets = self
+            .key_config
+            .and_then(|current| self.partial_packets(&current, &key))
+            .filter(|p| p.len() <= PARTIAL_LIMIT);
+        self.key_config = Some(key);
+        match packets {
+            Some(packets) => self.write_partial(packets),
+            None => self.set_key_config(),
+        }
+    }
+
+    /// 修改单个按键的行程设置
+    pub fn set_key_rt_config(&mut self, index: usize, config: cbor::KeyRTConfig) -> Result<()> {
+        let mut key = self.key_config.ok_or(Error::EmptyConfig)?;
+        *key.KeyConfigs.get_mut(index).ok_or(Error::ConfigDataCheckFailed("keys", index))? = config;
+        self.update_key_config(key)
+    }
+
+    /// 修改单个键位，`layer` 为0时是按键层，为1时是Fn层
+    pub fn set_keymap_entry(&mut self, layer: usize, position: usize, value: KeyValue) -> Result<()> {
+        let mut key = self.key_config.ok_or(Error::EmptyConfig)?;
+        let mut layers: [[KeyValue; 64]; 2] = KeyMap::from(key.KeyMap).into();
+        *layers
+            .get_mut(layer)
+            .and_then(|l| l.get_mut(position))
+            .ok_or(Error::ConfigDataCheckFailed("key_map", position))? = value;
+        key.KeyMap = KeyMap::from(layers).into();
+        self.update_key_config(key)
+    }
+
+    pub fn set_led_color(&mut self, color: u32) -> Result<()> {
+        let mut key = self.key_config.ok_or(Error::EmptyConfig)?;
+        key.led_color = color;
+        self.update_key_config(key)
+    }
+
+    /// 两份配置之间的局部写入数据包，需要整体写入时返回 `None`
+    fn partial_packets(&self, old: &cbor::Device, new: &cbor::Device) -> Option<Vec<Packet>> {
+        let others = |d: &cbor::Device| {
+            (d.JittersEliminationTime, d.HighReportRate, d.KeyProof, d.AutoCalibration, d.HallFilter, d.MaxBrightness)
+        };
+        if others(old) != others(new) {
+            return None;
+        }
+        let supports = |id| self.supports_partial(id);
+        let mut packets = vec![];
+        for (i, (a, b)) in old.KeyConfigs.iter().zip(&new.KeyConfigs).enumerate() {
+            if a != b {
+                supports(PacketID::SetKeyRTConfig).then_some(())?;
+                let mut data = vec![i as u8];
+                data.extend(b.to_cbor_with(|v| self.capabilities.strip_key_rt(v)));
+                packets.push(Packet::new(PacketID::SetKeyRTConfig, data));
+            }
+        }
+        // 每层128字节，前64字节是键值类型，后64字节是键值
+        for layer in 0..2 {
+            for position in 0..64 {
+                let (kind, code) = (layer * 128 + position, layer * 128 + 64 + position);
+                if old.KeyMap[kind] != new.KeyMap[kind] || old.KeyMap[code] != new.KeyMap[code] {
+                    supports(PacketID::SetKeyMapEntry).then_some(())?;
+                    packets.push(Packet::new(
+                        PacketID::SetKeyMapEntry,
+                        [layer as u8, position as u8, new.KeyMap[kind], new.KeyMap[code]],
+                    ));
+                }
+            }
+        }
+        if old.led_color != new.led_color {
+            supports(PacketID::SetLedColor).then_some(())?;
+            packets.push(Packet::new(PacketID::SetLedColor, new.led_color.to_be_bytes()));
+        }
+        Some(packets)
+    }
+
+    fn supports_partial(&self, packet_id: PacketID) -> bool {
+        self.descriptor.as_ref().is_some_and(|d| d.supports(packet_id))
+    }
+
+    /// 逐个发送局部写入的数据包，失败时改为整体写入
+    fn write_partial(&self, packets: Vec<Packet>) -> Result<()> {
+        debug!("局部写入 {} 项", packets.len());
+        for packet in packets {
+            self.write(packet)?;
+            let packet = self.read()?;
+            if packet.id != PacketID::Ok as u8 {
+                warn!("局部写入失败，改为整体写入：{}", packet.id);
+                return self.set_key_config();
+            }
+        }
+        Ok(())
+    }
+
     pub fn save_key_config(&mut self) -> Result<()> {
         self.write(Packet::new(PacketID::SaveKeyConfig, []))?;
         let packet = self.read()?;
         if packet.id == PacketID::Ok as u8 {
+            self.saved_key_config = self.key_config;
             Ok(())
         } else {
             dbg!(packet.id);
@@ -236,10 +386,46 @@ impl<D: Device> Meowboard<D> {
         }
     }
 
+    /// 写入配置并读回比较，一致后才保存到闪存；
+    /// 写入或保存失败时把写入前的配置写回内存，闪存中的配置不受影响
+    pub fn apply(&mut self, key: cbor::Device) -> Result<()> {
+        self.load_key_config()?;
+        let previous = self.key_config;
+        if let Err(e) = self.write_and_verify(key).and_then(|_| self.save_key_config()) {
+            warn!("写入配置失败，恢复写入前的配置：{}", e);
+            self.key_config = previous;
+            if let Err(e) = self.set_key_config() {
+                error!("恢复配置失败：{}", e);
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn write_and_verify(&mut self, key: cbor::Device) -> Result<()> {
+        self.key_config = Some(key);
+        self.set_key_config()?;
+        self.load_key_config()?;
+        if self.key_config != Some(key) {
+            return Err(Error::VerifyFailed("key_config"));
+        }
+        Ok(())
+    }
+
+    /// 与闪存中的配置不同即视为未保存
+    pub fn unsaved(&self) -> Unsaved {
+        Unsaved {
+            key: self.key_config.is_some() && self.key_config != self.saved_key_config,
+            light: false,
+        }
+    }
+
     pub fn clear_key_config(&mut self) -> Result<()> {
         self.write(Packet::new(PacketID::ClearKeyConfig, []))?;
         let packet = self.read()?;
         if packet.id == PacketID::Ok as u8 {
+            self.saved_key_config = None;
+            self.key_config = None;
             Ok(())
         } else {
             dbg!(packet.id);
@@ -309,6 +495,7 @@ impl<D: Device> Meowboard<D> {
     }
 
     fn write_no_delay(&self, packet: Packet) -> Result<()> {
+        self.check_supported(packet.id)?;
         debug!("发送：{:?}", packet);
         debug!("总数据大小：{}", packet.data.len());
         for v in packet.build_packets() {
@@ -318,7 +505,21 @@ impl<D: Device> Meowboard<D> {
         Ok(())
     }
 
+    /// 设备返回过描述时，拒绝发送设备不支持的数据包
+    fn check_supported(&self, packet_id: u8) -> Result<()> {
+        match &self.descriptor {
+            Some(d) if packet_id >= PacketID::GetFirmwareVersion as u8
+                && packet_id != PacketID::GetDescriptor as u8
+                && !d.supports(packet_id) =>
+            {
+                Err(Error::UnsupportedPacket(packet_id))
+            }
+            _ => Ok(()),
+        }
+    }
+
     fn write(&self, packet: Packet) -> Result<()> {
+        self.check_supported(packet.id)?;
         debug!("发送：{:?}", packet);
         debug!("总数据大小：{}", packet.data.len());
         for v in packet.build_packets() {
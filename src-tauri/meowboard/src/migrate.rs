@@ -0,0 +1,86 @@
+//! 配置结构版本与迁移
+//!
+//! 设备上的CBOR与本地保存的配置都可能来自旧版本，读取时先逐级升级到当前结构。
+//!
+//! | 版本 | 固件 | 变化 |
+//! | ---- | ---- | ---- |
+//! | 1 | <= 0.1.1 | 按键只有 `p`/`r`/`d` |
+//! | 2 | 0.1.2 | 按键新增 `rd`（释放死区）与 `e`（RT开关） |
+
+use crate::config;
+use ciborium::Value;
+use meowpad::cbor::{insert_missing, map_get, map_get_mut, migrate};
+use serde::{de::Error as _, Deserialize, Deserializer};
+
+pub const SCHEMA_VERSION: u32 = 2;
+
+const CBOR_MIGRATIONS: [fn(&mut Value); 1] = [cbor_v1_to_v2];
+const CONFIG_MIGRATIONS: [fn(&mut Value); 1] = [config_v1_to_v2];
+
+fn first_key<'a>(value: &'a Value, keys: &str) -> Option<&'a Value> {
+    map_get(value, keys)?.as_array()?.first()
+}
+
+fn for_each_key(value: &mut Value, keys: &str, f: impl Fn(&mut Value)) {
+    if let Some(keys) = map_get_mut(value, keys).and_then(Value::as_array_mut) {
+        keys.iter_mut().for_each(f);
+    }
+}
+
+/// 根据内容推断设备CBOR的结构版本
+pub fn detect_cbor_version(value: &Value) -> u32 {
+    match first_key(value, "ks") {
+        Some(key) if map_get(key, "rd").is_none() => 1,
+        _ => SCHEMA_VERSION,
+    }
+}
+
+/// 根据内容推断本地配置的结构版本
+pub fn detect_config_version(value: &Value) -> u32 {
+    match first_key(value, "keys") {
+        Some(key) if map_get(key, "release_dead_zone").is_none() => 1,
+        _ => SCHEMA_VERSION,
+    }
+}
+
+/// 将设备CBOR升级到当前结构，`version` 为设备报告的版本（未知时推断），返回升级前的版本
+pub fn migrate_cbor(value: &mut Value, version: Option<u32>) -> u32 {
+    let from = version
+        .filter(|v| *v > 0)
+        .unwrap_or_else(|| detect_cbor_version(value));
+    migrate(value, from, &CBOR_MIGRATIONS);
+    from
+}
+
+/// 将本地配置升级到当前结构，`version` 为 0 时根据内容推断
+pub fn migrate_config(value: &mut Value, version: u32) -> u32 {
+    let from = match version {
+        0 => detect_config_version(value),
+        v => v,
+    };
+    migrate(value, from, &CONFIG_MIGRATIONS);
+    from
+}
+
+/// 读取任意版本的本地配置
+pub fn config_from<'de, D: Deserializer<'de>>(deserializer: D, version: u32) -> Result<config::Device, D::Error> {
+    let mut value = Value::deserialize(deserializer)?;
+    migrate_config(&mut value, version);
+    value
+        .deserialized()
+        .map_err(|ciborium::value::Error::Custom(e)| D::Error::custom(e))
+}
+
+fn cbor_v1_to_v2(value: &mut Value) {
+    for_each_key(value, "ks", |key| {
+        insert_missing(key, "rd", Value::Integer(0.into()));
+        insert_missing(key, "e", Value::Bool(true));
+    });
+}
+
+fn config_v1_to_v2(value: &mut Value) {
+    for_each_key(value, "keys", |key| {
+        insert_missing(key, "release_dead_zone", Value::Integer(0.into()));
+        insert_missing(key, "rt_enabled", Value::Bool(true));
+    });
+}
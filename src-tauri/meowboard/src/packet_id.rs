@@ -24,6 +24,7 @@ pub enum PacketID {
     ToggleKeyboard     = 14,
     Reset              = 15,
     GetStatus          = 16,
+    GetDescriptor      = 17,
 
     // debug extra
     DebugValue = 50,
@@ -37,6 +38,11 @@ pub enum PacketID {
     ClearKeyConfig   = 106,
     GetHallConfig    = 108,
     ClearHallConfig  = 109,
+
+    // 局部写入，设备描述中列出时才使用
+    SetKeyRTConfig   = 110,
+    SetKeyMapEntry   = 111,
+    SetLedColor      = 112,
 }
 
 impl std::fmt::Display for PacketID {
@@ -0,0 +1,176 @@
+use ciborium::Value;
+use serde::{de::DeserializeOwned, Serialize};
+use std::io::Cursor;
+
+pub type DecodeError = ciborium::de::Error<std::io::Error>;
+
+/// 配置结构与CBOR之间的转换，各型号的配置结构只需声明实现
+pub trait CborConvertor: Sized + Serialize + DeserializeOwned {
+    fn from_cbor<T: AsRef<[u8]>>(data: T) -> Result<Self, DecodeError> {
+        ciborium::de::from_reader(Cursor::new(data))
+    }
+
+    fn to_cbor(self) -> Vec<u8> {
+        let mut data = vec![];
+        ciborium::ser::into_writer(&self, &mut data).unwrap();
+        data
+    }
+
+    /// 序列化前允许修改CBOR结构，例如去掉固件不支持的字段或加回设备原有的未知字段
+    fn to_cbor_with(self, f: impl FnOnce(&mut Value)) -> Vec<u8> {
+        let mut value = Value::serialized(&self).unwrap();
+        f(&mut value);
+        let mut data = vec![];
+        ciborium::ser::into_writer(&value, &mut data).unwrap();
+        data
+    }
+}
+
+/// 宽松解析配置时的记录
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct DecodeReport {
+    /// 设备缺少、使用默认值填充的字段
+    pub defaulted: Vec<String>,
+    /// 当前版本不认识的字段，写回设备时原样保留
+    pub ignored: Vec<String>,
+}
+
+impl DecodeReport {
+    pub fn is_empty(&self) -> bool {
+        self.defaulted.is_empty() && self.ignored.is_empty()
+    }
+}
+
+fn key_name(key: &Value) -> String {
+    match key {
+        Value::Text(s) => s.clone(),
+        Value::Integer(i) => i128::from(*i).to_string(),
+        v => format!("{:?}", v),
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+/// 用 `default` 补全 `value` 中缺少的字段，并记录多余的字段
+pub fn fill_defaults(value: &mut Value, default: &Value, path: &str, report: &mut DecodeReport) {
+    match (value, default) {
+        (Value::Map(map), Value::Map(default_map)) => {
+            for (k, _) in map.iter() {
+                if !default_map.iter().any(|(dk, _)| dk == k) {
+                    report.ignored.push(join(path, &key_name(k)));
+                }
+            }
+            for (dk, dv) in default_map.iter() {
+                let key_path = join(path, &key_name(dk));
+                match map.iter_mut().find(|(k, _)| k == dk) {
+                    Some((_, v)) => fill_defaults(v, dv, &key_path, report),
+                    None => {
+                        report.defaulted.push(key_path);
+                        map.push((dk.clone(), dv.clone()));
+                    }
+                }
+            }
+        }
+        (Value::Array(arr), Value::Array(default_arr)) => {
+            for (i, (v, dv)) in arr.iter_mut().zip(default_arr.iter()).enumerate() {
+                fill_defaults(v, dv, &format!("{}[{}]", path, i), report);
+            }
+            if arr.len() > default_arr.len() {
+                report.ignored.push(format!("{}[{}..]", path, default_arr.len()));
+                arr.truncate(default_arr.len());
+            }
+            let start = arr.len();
+            for i in start..default_arr.len() {
+                report.defaulted.push(format!("{}[{}]", path, i));
+            }
+            arr.extend(default_arr[start..].iter().cloned());
+        }
+        _ => (),
+    }
+}
+
+/// 宽松解析：缺少的字段使用默认值，不认识的字段被忽略，同时返回原始数据以便写回
+pub fn from_cbor_lenient<T>(data: impl AsRef<[u8]>) -> Result<(T, Value, DecodeReport), DecodeError>
+where
+    T: Default + Serialize + DeserializeOwned,
+{
+    let raw: Value = ciborium::de::from_reader(Cursor::new(data))?;
+    let (cfg, report) = from_value_lenient(raw.clone())?;
+    Ok((cfg, raw, report))
+}
+
+/// 同 `from_cbor_lenient`，用于已经迁移过的数据
+pub fn from_value_lenient<T>(mut value: Value) -> Result<(T, DecodeReport), DecodeError>
+where
+    T: Default + Serialize + DeserializeOwned,
+{
+    let default = Value::serialized(&T::default()).unwrap();
+    let mut report = DecodeReport::default();
+    fill_defaults(&mut value, &default, "", &mut report);
+    let cfg = value
+        .deserialized()
+        .map_err(|e| ciborium::de::Error::Semantic(None, e.to_string()))?;
+    Ok((cfg, report))
+}
+
+/// 把 `original` 中存在而 `value` 中没有的字段加回 `value`
+pub fn restore_unknown(value: &mut Value, original: &Value) {
+    match (value, original) {
+        (Value::Map(map), Value::Map(original_map)) => {
+            for (ok, ov) in original_map.iter() {
+                match map.iter_mut().find(|(k, _)| k == ok) {
+                    Some((_, v)) => restore_unknown(v, ov),
+                    None => map.push((ok.clone(), ov.clone())),
+                }
+            }
+        }
+        (Value::Array(arr), Value::Array(original_arr)) => {
+            for (v, ov) in arr.iter_mut().zip(original_arr.iter()) {
+                restore_unknown(v, ov);
+            }
+            // 解析时多出的元素被截断，配置中的数组都是定长的，原样加回
+            if original_arr.len() > arr.len() {
+                arr.extend(original_arr[arr.len()..].iter().cloned());
+            }
+        }
+        _ => (),
+    }
+}
+
+pub fn map_get<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    value
+        .as_map()?
+        .iter()
+        .find(|(k, _)| k.as_text() == Some(key))
+        .map(|(_, v)| v)
+}
+
+pub fn map_get_mut<'a>(value: &'a mut Value, key: &str) -> Option<&'a mut Value> {
+    value
+        .as_map_mut()?
+        .iter_mut()
+        .find(|(k, _)| k.as_text() == Some(key))
+        .map(|(_, v)| v)
+}
+
+/// 字段不存在时插入
+pub fn insert_missing(value: &mut Value, key: &str, v: Value) {
+    if let Some(map) = value.as_map_mut() {
+        if !map.iter().any(|(k, _)| k.as_text() == Some(key)) {
+            map.push((Value::Text(key.to_owned()), v));
+        }
+    }
+}
+
+/// 将 `from` 版本的数据依次升级到最新版本，`steps[i]` 负责从 `i + 1` 升级到 `i + 2`
+pub fn migrate(value: &mut Value, from: u32, steps: &[fn(&mut Value)]) {
+    for step in steps.iter().skip(from.max(1) as usize - 1) {
+        step(value);
+    }
+}
@@ -0,0 +1,158 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// 单个字段的变化
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Change {
+    /// 字段路径，例如 `led_color.red`
+    pub path: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+/// 单个按键行程设置的变化
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct KeyDiff {
+    pub index: usize,
+    /// 路径相对于该按键
+    pub changes: Vec<Change>,
+}
+
+/// 单个键位的变化
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct KeymapDiff {
+    pub layer: usize,
+    pub position: usize,
+    pub old: Value,
+    pub new: Value,
+}
+
+/// 同一型号两份配置之间的差异
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ConfigDiff {
+    pub keys: Vec<KeyDiff>,
+    pub keymap: Vec<KeymapDiff>,
+    pub light: Vec<Change>,
+    /// 其余的全局设置
+    pub device: Vec<Change>,
+}
+
+/// 逐个叶子字段比较，数组长度不同时整体视为一项变化
+pub fn changes<T: Serialize>(old: &T, new: &T) -> Vec<Change> {
+    changes_where(old, new, |_| true)
+}
+
+/// 只比较满足条件的顶层字段
+pub fn changes_where<T: Serialize>(old: &T, new: &T, field: impl Fn(&str) -> bool) -> Vec<Change> {
+    let mut out = Vec::new();
+    let (old, new) = (to_value(old), to_value(new));
+    match (&old, &new) {
+        (Value::Object(a), Value::Object(b)) => {
+            for (k, v) in a {
+                if field(k) {
+                    walk(&mut out, k.clone(), v, b.get(k).unwrap_or(&Value::Null));
+                }
+            }
+            for (k, v) in b {
+                if field(k) && !a.contains_key(k) {
+                    walk(&mut out, k.clone(), &Value::Null, v);
+                }
+            }
+        }
+        _ => walk(&mut out, String::new(), &old, &new),
+    }
+    out
+}
+
+fn to_value<T: Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).unwrap_or_default()
+}
+
+fn walk(out: &mut Vec<Change>, path: String, old: &Value, new: &Value) {
+    let join = |k: &str| if path.is_empty() { k.to_owned() } else { format!("{}.{}", path, k) };
+    match (old, new) {
+        (Value::Object(a), Value::Object(b)) => {
+            for (k, v) in a {
+                walk(out, join(k), v, b.get(k).unwrap_or(&Value::Null));
+            }
+            for (k, v) in b {
+                if !a.contains_key(k) {
+                    walk(out, join(k), &Value::Null, v);
+                }
+            }
+        }
+        (Value::Array(a), Value::Array(b)) if a.len() == b.len() => {
+            for (i, (x, y)) in a.iter().zip(b).enumerate() {
+                walk(out, format!("{}[{}]", path, i), x, y);
+            }
+        }
+        _ if old != new => out.push(Change {
+            path,
+            old: old.clone(),
+            new: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+impl ConfigDiff {
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty() && self.keymap.is_empty() && self.light.is_empty() && self.device.is_empty()
+    }
+
+    /// 记录一个按键的变化，`field` 用于排除键位等不属于行程设置的字段
+    pub fn key<T: Serialize>(&mut self, index: usize, old: &T, new: &T, field: impl Fn(&str) -> bool) {
+        let changes = changes_where(old, new, field);
+        if !changes.is_empty() {
+            self.keys.push(KeyDiff { index, changes });
+        }
+    }
+
+    pub fn keymap<T: Serialize>(&mut self, layer: usize, position: usize, old: &T, new: &T) {
+        let (old, new) = (to_value(old), to_value(new));
+        if old != new {
+            self.keymap.push(KeymapDiff {
+                layer,
+                position,
+                old,
+                new,
+            });
+        }
+    }
+
+    /// 按位置比较一整层键位
+    pub fn layer<T: Serialize>(&mut self, layer: usize, old: &[T], new: &[T]) {
+        for (position, (a, b)) in old.iter().zip(new).enumerate() {
+            self.keymap(layer, position, a, b);
+        }
+    }
+
+    pub fn extend(&mut self, other: ConfigDiff) {
+        self.keys.extend(other.keys);
+        self.keymap.extend(other.keymap);
+        self.light.extend(other.light);
+        self.device.extend(other.device);
+    }
+}
+
+impl fmt::Display for ConfigDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for k in &self.keys {
+            for c in &k.changes {
+                writeln!(f, "按键 {} {}: {} -> {}", k.index, c.path, c.old, c.new)?;
+            }
+        }
+        for k in &self.keymap {
+            writeln!(f, "键位 层{} 位置{}: {} -> {}", k.layer, k.position, k.old, k.new)?;
+        }
+        for c in &self.light {
+            writeln!(f, "灯光 {}: {} -> {}", c.path, c.old, c.new)?;
+        }
+        for c in &self.device {
+            writeln!(f, "设备 {}: {} -> {}", c.path, c.old, c.new)?;
+        }
+        Ok(())
+    }
+}
@@ -20,6 +20,15 @@ pub enum Error {
     UnexceptedResponse(crate::packet::Packet),
     #[error(transparent)]
     Utf8Error(#[from] std::string::FromUtf8Error),
+    #[error("unsupported_packet, packet_id: {0}")]
+    UnsupportedPacket(u8),
+    #[error("unsupported_feature: {0}")]
+    UnsupportedFeature(&'static str),
+    #[error("verify_failed: {0}")]
+    VerifyFailed(&'static str),
+    /// 只有部分配置保存到了闪存，并且没能恢复
+    #[error("partial_save, saved: {0}")]
+    PartialSave(&'static str),
     #[error("{0}")]
     Other(&'static str),
 }
\ No newline at end of file
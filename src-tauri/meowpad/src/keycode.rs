@@ -3,9 +3,10 @@ use serde::{Serialize, Deserialize};
 use serde_repr::*;
 use strum_macros::EnumIter;
 use crate::KbReport;
+use schemars::JsonSchema;
 
 #[derive(
-    Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq, Default
+    Serialize, Deserialize, JsonSchema, Copy, Clone, Debug, Eq, PartialEq, Default
 )]
 #[serde(tag = "t", content = "c")]
 pub enum KeyValue {
@@ -307,6 +308,17 @@ impl fmt::Display for KeyCode {
     }
 }
 
+impl JsonSchema for KeyCode {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "KeyCode".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        use strum::IntoEnumIterator;
+        crate::schema::repr_enum(KeyCode::iter().map(|k| k as u8))
+    }
+}
+
 use num::FromPrimitive;
 impl From<u8> for KeyCode {
     fn from(value: u8) -> Self {
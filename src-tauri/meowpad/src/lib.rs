@@ -4,9 +4,15 @@ pub mod packet;
 pub mod kbreport;
 pub mod models;
 pub mod device;
+pub mod version;
+pub mod cbor;
+pub mod validate;
+pub mod schema;
+pub mod diff;
 
 pub use packet::Packet;
 pub use error::Result;
 pub use keycode::KeyCode;
 pub use kbreport::KbReport;
-pub use device::Device;
\ No newline at end of file
+pub use device::Device;
+pub use version::FirmwareVersion;
\ No newline at end of file
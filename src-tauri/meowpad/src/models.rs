@@ -25,6 +25,33 @@ pub struct KeyHallConfig {
     pub hall_middle: u16,
 }
 
+/// `GetDescriptor` 返回的设备描述 (CBOR)
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Descriptor {
+    #[serde(rename = "pv")]
+    pub protocol_version: u8,
+    #[serde(rename = "kc")]
+    pub key_count: u8,
+    #[serde(rename = "sv")]
+    pub schema_version: u8,
+    #[serde(rename = "mps")]
+    pub max_packet_size: u16,
+    #[serde(rename = "pk")]
+    pub packets: Vec<u8>,
+    #[serde(rename = "lm", default)]
+    pub lighting_modes: Vec<u8>,
+}
+
+impl Descriptor {
+    pub fn from_cbor<T: AsRef<[u8]>>(data: T) -> crate::Result<Self> {
+        Ok(ciborium::de::from_reader(std::io::Cursor::new(data))?)
+    }
+
+    pub fn supports(&self, packet_id: impl Into<u8>) -> bool {
+        self.packets.contains(&packet_id.into())
+    }
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Debug, Copy, Clone, Default)]
 pub struct DeviceStatus {
     pub key: bool,
@@ -32,3 +59,16 @@ pub struct DeviceStatus {
     pub hall: bool,
     pub enabled: bool,
 }
+
+/// 已写入设备内存但尚未保存到闪存的配置
+#[derive(serde::Serialize, serde::Deserialize, Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Unsaved {
+    pub key: bool,
+    pub light: bool,
+}
+
+impl Unsaved {
+    pub fn any(&self) -> bool {
+        self.key || self.light
+    }
+}
@@ -0,0 +1,49 @@
+use schemars::{json_schema, JsonSchema, Schema};
+
+/// 以整数序列化的枚举 (`serde_repr`)
+pub fn repr_enum(values: impl IntoIterator<Item = u8>) -> Schema {
+    let values: Vec<u8> = values.into_iter().collect();
+    json_schema!({
+        "type": "integer",
+        "enum": values,
+    })
+}
+
+/// `palette::Srgb<u8>` 的序列化结构
+#[derive(JsonSchema)]
+#[allow(dead_code)]
+pub struct Rgb {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+/// 类型的JSON Schema中的字段名，按名称排列
+pub fn schema_fields<T: JsonSchema>() -> Vec<String> {
+    let schema = schemars::schema_for!(T);
+    let mut fields: Vec<String> = schema
+        .get("properties")
+        .and_then(|p| p.as_object())
+        .map(|p| p.keys().cloned().collect())
+        .unwrap_or_default();
+    fields.sort();
+    fields
+}
+
+/// 前端TypeScript源码中 `interface` 的字段名，按名称排列，用于检查手写的类型是否与JSON Schema一致
+pub fn ts_interface_fields(source: &str, name: &str) -> Vec<String> {
+    let header = format!("export interface {} {{", name);
+    let mut fields: Vec<String> = source
+        .lines()
+        .skip_while(|l| l.trim() != header)
+        .skip(1)
+        .take_while(|l| !l.starts_with('}'))
+        .filter_map(|l| {
+            let l = l.split("//").next().unwrap_or_default().trim();
+            let (field, _) = l.split_once(':')?;
+            Some(field.trim().trim_end_matches('?').to_owned())
+        })
+        .collect();
+    fields.sort();
+    fields
+}
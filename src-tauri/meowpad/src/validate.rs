@@ -0,0 +1,113 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// 超出时只给出警告的推荐范围，作为JSON Schema的扩展字段
+pub const RECOMMENDED_MINIMUM: &str = "x-recommended-minimum";
+pub const RECOMMENDED_MAXIMUM: &str = "x-recommended-maximum";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// 超出推荐范围，仍然可以写入
+    Warning,
+    /// 超出设备允许的范围，拒绝写入
+    Error,
+}
+
+/// 单个字段的检查结果
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// 字段路径，例如 `keys[0].dead_zone`
+    pub path: String,
+    pub value: i64,
+    pub min: i64,
+    pub max: i64,
+    pub severity: Severity,
+}
+
+impl Diagnostic {
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Validator {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator {
+    /// 按类型的JSON Schema检查 `minimum`/`maximum` 与推荐范围
+    pub fn from_schema<T: Serialize + JsonSchema>(value: &T) -> Self {
+        let mut v = Self::default();
+        let schema = schemars::schema_for!(T);
+        let value = serde_json::to_value(value).unwrap();
+        v.walk(schema.as_value(), schema.as_value(), &value, String::new());
+        v
+    }
+
+    fn walk(&mut self, root: &Value, schema: &Value, value: &Value, path: String) {
+        let schema = match schema.get("$ref").and_then(Value::as_str) {
+            Some(r) => r
+                .strip_prefix('#')
+                .and_then(|p| root.pointer(p))
+                .unwrap_or(schema),
+            None => schema,
+        };
+        let bound = |key: &str, default: i64| schema.get(key).and_then(Value::as_i64).unwrap_or(default);
+        match value {
+            Value::Object(map) => {
+                let Some(properties) = schema.get("properties") else { return };
+                for (k, v) in map {
+                    if let Some(s) = properties.get(k) {
+                        let path = if path.is_empty() { k.clone() } else { format!("{}.{}", path, k) };
+                        self.walk(root, s, v, path);
+                    }
+                }
+            }
+            Value::Array(items) => {
+                let Some(s) = schema.get("items") else { return };
+                for (i, v) in items.iter().enumerate() {
+                    self.walk(root, s, v, format!("{}[{}]", path, i));
+                }
+            }
+            Value::Number(n) => {
+                let Some(n) = n.as_i64() else { return };
+                let (min, max) = (bound("minimum", i64::MIN), bound("maximum", i64::MAX));
+                self.range(path.clone(), n, min, max);
+                self.recommend(path, n, bound(RECOMMENDED_MINIMUM, min), bound(RECOMMENDED_MAXIMUM, max));
+            }
+            _ => {}
+        }
+    }
+
+    /// 同一字段只保留第一条诊断
+    fn check(&mut self, path: impl Into<String>, value: impl Into<i64>, min: i64, max: i64, severity: Severity) {
+        let path = path.into();
+        let value = value.into();
+        if !(min..=max).contains(&value) && !self.diagnostics.iter().any(|d| d.path == path) {
+            self.diagnostics.push(Diagnostic {
+                path,
+                value,
+                min,
+                max,
+                severity,
+            });
+        }
+    }
+
+    /// 设备允许的范围
+    pub fn range(&mut self, path: impl Into<String>, value: impl Into<i64>, min: i64, max: i64) {
+        self.check(path, value, min, max, Severity::Error);
+    }
+
+    /// 推荐范围，超出时只给出警告
+    pub fn recommend(&mut self, path: impl Into<String>, value: impl Into<i64>, min: i64, max: i64) {
+        self.check(path, value, min, max, Severity::Warning);
+    }
+
+    pub fn finish(self) -> Vec<Diagnostic> {
+        self.diagnostics
+    }
+}
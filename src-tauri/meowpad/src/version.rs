@@ -0,0 +1,30 @@
+use std::{fmt, str::FromStr};
+
+/// 固件版本号，形如 `0.1.2`，忽略版本号后的非数字后缀
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct FirmwareVersion(pub u16, pub u16, pub u16);
+
+impl FromStr for FirmwareVersion {
+    type Err = crate::error::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().trim_start_matches(['v', 'V']);
+        let end = s
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(s.len());
+        let mut parts = s[..end].split('.').filter(|p| !p.is_empty()).map(|p| p.parse::<u16>());
+        let major = parts
+            .next()
+            .ok_or(crate::error::Error::Other("invalid_firmware_version"))?
+            .map_err(|_| crate::error::Error::Other("invalid_firmware_version"))?;
+        let minor = parts.next().and_then(|p| p.ok()).unwrap_or_default();
+        let patch = parts.next().and_then(|p| p.ok()).unwrap_or_default();
+        Ok(Self(major, minor, patch))
+    }
+}
+
+impl fmt::Display for FirmwareVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
+}
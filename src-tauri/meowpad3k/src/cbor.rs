@@ -4,10 +4,10 @@ use palette::rgb::channels::Argb;
 use palette::Srgb;
 use serde::{Deserialize, Serialize};
 use serde_with::*;
-use std::io::Cursor;
+pub use meowpad::cbor::CborConvertor;
 
 #[repr(C)]
-#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
 #[allow(non_snake_case)]
 pub struct KeyRTConfig {
     #[serde(rename = "pp")]
@@ -21,7 +21,7 @@ pub struct KeyRTConfig {
 }
 
 #[repr(C)]
-#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
 #[allow(non_snake_case)]
 pub struct Keyboard {
     #[serde(rename = "ks")]
@@ -39,7 +39,7 @@ pub struct Keyboard {
 /// speed 0-10
 /// steep length 50 - 200
 #[repr(C)]
-#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
 #[allow(non_snake_case)]
 pub struct Light {
     #[serde(rename = "c")]
@@ -52,22 +52,6 @@ pub struct Light {
     pub sleep_time: u16,
 }
 
-pub trait CborConvertor
-where
-    Self: Sized + Serialize,
-    for<'de> Self: Deserialize<'de>,
-{
-    fn from_cbor<T: AsRef<[u8]>>(data: T) -> Result<Self, ciborium::de::Error<std::io::Error>> {
-        Ok(ciborium::de::from_reader(Cursor::new(data))?)
-    }
-
-    fn to_cbor(self) -> Vec<u8> {
-        let mut data = vec![];
-        ciborium::ser::into_writer(&self, &mut data).unwrap();
-        data
-    }
-}
-
 impl CborConvertor for Keyboard {}
 impl CborConvertor for Light {}
 
@@ -1,9 +1,10 @@
 use crate::cbor;
-use meowpad::{KeyCode, error::Error, KbReport};
+use meowpad::{KeyCode, diff::{changes, changes_where, ConfigDiff}, error::Error, KbReport, schema::{repr_enum, Rgb}, validate::{Diagnostic, Validator}};
 use num::FromPrimitive;
 use num_derive::{FromPrimitive, ToPrimitive};
 use palette::rgb::channels::Argb;
 use palette::Srgb;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_repr::*;
 use serde_with::*;
@@ -20,33 +21,50 @@ pub enum LightingMode {
     Solid,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, Default, Copy)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Default, Copy)]
 pub struct KeyConfig {
+    #[schemars(range(min = 1, max = 100))]
     pub press_percentage: u8,
+    #[schemars(range(min = 1, max = 100))]
     pub release_percentage: u8,
+    #[schemars(range(max = 100), extend("x-recommended-maximum" = 30))]
     pub dead_zone: u8,   // 0-30
     pub key_data: [KeyCode; 6],
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, Copy)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Copy)]
 pub struct Key {
     pub keys: [KeyConfig; 3],
     pub side_btn: [KeyCode; 6],
+    /// 以 ms 为单位
+    #[schemars(range(max = 50))]
     pub jitters_elimination_time: u16,
     pub continuous_report: bool,
     pub kalman_filter: bool
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, Copy)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Copy)]
 pub struct Light {
     /// LED灯颜色
+    #[schemars(with = "[Rgb; 3]")]
     pub led_colors: [Srgb<u8>; 3],
     /// 灯效模式
     pub lighting_mode: LightingMode,
+    #[schemars(range(max = 50))]
     pub max_brightness: u8,
     pub sleep_time: u16,
 }
 
+impl JsonSchema for LightingMode {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "LightingMode".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        repr_enum((0..=u8::MAX).filter(|v| LightingMode::from_u8(*v).is_some()))
+    }
+}
+
 impl Default for Key {
     fn default() -> Self {
         cbor::Keyboard::default().try_into().unwrap()
@@ -59,6 +77,38 @@ impl Default for Light {
     }
 }
 
+impl Key {
+    /// 按JSON Schema中的范围检查
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        Validator::from_schema(self).finish()
+    }
+
+    /// 键位只有一层，位置即按键序号，侧键排在最后
+    pub fn diff(&self, new: &Self) -> ConfigDiff {
+        let mut diff = ConfigDiff::default();
+        for (i, (a, b)) in self.keys.iter().zip(&new.keys).enumerate() {
+            diff.key(i, a, b, |f| f != "key_data");
+            diff.keymap(0, i, &a.key_data, &b.key_data);
+        }
+        diff.keymap(0, self.keys.len(), &self.side_btn, &new.side_btn);
+        diff.device = changes_where(self, new, |f| f != "keys" && f != "side_btn");
+        diff
+    }
+}
+
+impl Light {
+    pub fn diff(&self, new: &Self) -> ConfigDiff {
+        ConfigDiff {
+            light: changes(self, new),
+            ..Default::default()
+        }
+    }
+
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        Validator::from_schema(self).finish()
+    }
+}
+
 impl TryFrom<cbor::Keyboard> for Key {
     type Error = Error;
     fn try_from(cfg: cbor::Keyboard) -> Result<Self, Self::Error> {
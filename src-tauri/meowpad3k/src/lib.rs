@@ -1,5 +1,6 @@
 pub mod cbor;
 pub mod config;
+pub mod migrate;
 mod meowpad;
 mod packet_id;
 
@@ -9,6 +10,7 @@ pub use crate::packet_id::PacketID;
 #[cfg(test)]
 mod tests {
     use crate::*;
+    use ::meowpad::{packet::PacketBuilder, KbReport, KeyCode, Packet};
     use pretty_hex::*;
     use std::io::{Cursor, Read};
     use crate::cbor::CborConvertor;
@@ -89,8 +91,9 @@ mod tests {
                     Err(_) => {
                         // cur已经遍历结束
                         buf.get_mut().iter_mut().for_each(|b| *b = 0);
-                        buf.set_position(0);
                         read_packet(&mut packet_builder, buf.get_mut());
+                        // 后续的数据包以0xFE开头
+                        buf.set_position(1);
                     }
                 }
             } else {
@@ -107,14 +110,36 @@ mod tests {
     fn cbor_kb() {
         let c = cbor::Keyboard::default().to_cbor();
         dbg!(c.hex_dump());
-        assert!(c.len() <= 1);
+        assert_eq!(cbor::Keyboard::from_cbor(&c).unwrap(), cbor::Keyboard::default());
     }
 
     #[test]
     fn cbor_light() {
         let c = cbor::Light::default().to_cbor();
         dbg!(c.hex_dump());
-        assert!(c.len() <= 1);
+        assert_eq!(cbor::Light::from_cbor(&c).unwrap(), cbor::Light::default());
+    }
+
+    #[test]
+    fn migrate_v1() {
+        use crate::migrate::*;
+        let mut value = ciborium::Value::serialized(&cbor::Keyboard::default()).unwrap();
+        let expected = value.clone();
+        // 目前只有版本1，设备没有报告版本时按当前版本读取
+        assert_eq!(migrate_key_cbor(&mut value, None), SCHEMA_VERSION);
+        assert_eq!(migrate_key_cbor(&mut value, Some(1)), 1);
+        assert_eq!(value, expected);
+    }
+
+    /// 前端手写的类型与JSON Schema的字段一致
+    #[test]
+    fn ts_interfaces() {
+        use ::meowpad::schema::{schema_fields, ts_interface_fields};
+        let config = include_str!("../../../src/apis/meowpad3k/config.ts");
+        let index = include_str!("../../../src/apis/index.ts");
+        assert_eq!(ts_interface_fields(config, "IKeyboard"), schema_fields::<config::Key>());
+        assert_eq!(ts_interface_fields(config, "ILighting"), schema_fields::<config::Light>());
+        assert_eq!(ts_interface_fields(index, "IKeyConfig"), schema_fields::<config::KeyConfig>());
     }
 
     #[test]
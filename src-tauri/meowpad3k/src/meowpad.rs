@@ -1,5 +1,5 @@
 use crate::{
-    cbor, packet_id::PacketID
+    cbor, migrate, packet_id::PacketID
 };
 use meowpad::{Packet, error::Error, Result, models::*, Device};
 use byteorder::{BigEndian, ReadBytesExt};
@@ -8,12 +8,20 @@ use num::FromPrimitive;
 use pretty_hex::*;
 use std::{io::Cursor, thread, time::Duration};
 use crate::cbor::CborConvertor;
+use meowpad::cbor::{from_value_lenient, restore_unknown, DecodeReport};
 
 pub struct Meowpad<D: Device> {
     pub key_config: Option<cbor::Keyboard>,
     pub light_config: Option<cbor::Light>,
+    /// 设备返回的原始配置，写回时保留其中不认识的字段
+    pub key_config_raw: Option<ciborium::Value>,
+    pub light_config_raw: Option<ciborium::Value>,
+    /// 闪存中的配置，取自连接后第一次读取或最后一次保存
+    pub saved_key_config: Option<cbor::Keyboard>,
+    pub saved_light_config: Option<cbor::Light>,
     pub device_name: Option<String>,
     pub firmware_version: Option<String>,
+    pub descriptor: Option<Descriptor>,
     pub device: D,
 }
 
@@ -24,8 +32,13 @@ impl<D: Device> Meowpad<D> {
             device,
             key_config: None,
             light_config: None,
+            key_config_raw: None,
+            light_config_raw: None,
+            saved_key_config: None,
+            saved_light_config: None,
             device_name: None,
             firmware_version: None,
+            descriptor: None,
         }
     }
 
@@ -65,6 +78,26 @@ impl<D: Device> Meowpad<D> {
         }
     }
 
+    /// 查询设备描述，旧固件不支持该数据包时返回 `None`
+    pub fn get_descriptor(&mut self) -> Result<Option<Descriptor>> {
+        self.write(Packet::new(PacketID::GetDescriptor, []))?;
+        let packet = self.read_timeout(1000)?;
+        if packet.id == PacketID::Ok as u8 {
+            let descriptor = Descriptor::from_cbor(packet.data)?;
+            self.descriptor = Some(descriptor.clone());
+            Ok(Some(descriptor))
+        } else {
+            debug!("设备不支持描述查询: {}", packet.id);
+            self.descriptor = None;
+            Ok(None)
+        }
+    }
+
+    /// 设备描述中报告的配置结构版本
+    fn schema_version(&self) -> Option<u32> {
+        self.descriptor.as_ref().map(|d| d.schema_version as u32)
+    }
+
     /// (按键配置状态，灯光配置状态，按键校准状态，按键是否启用)
     pub fn get_status(&mut self) -> Result<DeviceStatus> {
         self.write(Packet::new(PacketID::GetStatus, []))?;
@@ -124,24 +157,47 @@ impl<D: Device> Meowpad<D> {
     }
 
 
-    pub fn load_key_config(&mut self) -> Result<()> {
+    pub fn load_key_config(&mut self) -> Result<DecodeReport> {
         self.write(Packet::new(PacketID::GetKeyConfig, []))?;
         let packet = self.read()?;
-        self.key_config = Some(cbor::Keyboard::from_cbor(packet.data)?);
-        Ok(())
+        let raw: ciborium::Value = ciborium::de::from_reader(packet.data.as_slice())?;
+        let mut value = raw.clone();
+        migrate::migrate_key_cbor(&mut value, self.schema_version());
+        let (config, report) = from_value_lenient::<cbor::Keyboard>(value)?;
+        if !report.is_empty() {
+            warn!("按键配置与当前版本不一致：{:?}", report);
+        }
+        self.saved_key_config.get_or_insert(config);
+        self.key_config = Some(config);
+        self.key_config_raw = Some(raw);
+        Ok(report)
     }
     
-    pub fn load_light_config(&mut self) -> Result<()> {
+    pub fn load_light_config(&mut self) -> Result<DecodeReport> {
         self.write(Packet::new(PacketID::GetLightConfig, []))?;
         let packet = self.read()?;
-        self.light_config = Some(cbor::Light::from_cbor(packet.data)?);
-        Ok(())
+        let raw: ciborium::Value = ciborium::de::from_reader(packet.data.as_slice())?;
+        let mut value = raw.clone();
+        migrate::migrate_light_cbor(&mut value, self.schema_version());
+        let (config, report) = from_value_lenient::<cbor::Light>(value)?;
+        if !report.is_empty() {
+            warn!("灯光配置与当前版本不一致：{:?}", report);
+        }
+        self.saved_light_config.get_or_insert(config);
+        self.light_config = Some(config);
+        self.light_config_raw = Some(raw);
+        Ok(report)
     }
 
     pub fn set_key_config(&self) -> Result<()> {
         let config = self.key_config.ok_or(Error::EmptyConfig)?;
         info!("写入键盘配置：{:?}", config);
-        self.write_large(Packet::new(PacketID::SetKeyConfig, config.to_cbor()))?;
+        let data = config.to_cbor_with(|v| {
+            if let Some(raw) = &self.key_config_raw {
+                restore_unknown(v, raw);
+            }
+        });
+        self.write_large(Packet::new(PacketID::SetKeyConfig, data))?;
         let packet = self.read()?; // 读取
         if packet.id == PacketID::Ok as u8 {
             Ok(())
@@ -154,8 +210,18 @@ impl<D: Device> Meowpad<D> {
 
     pub fn set_light_config(&self) -> Result<()> {
         let config = self.light_config.ok_or(Error::EmptyConfig)?;
+        if let Some(d) = self.descriptor.as_ref().filter(|d| !d.lighting_modes.is_empty()) {
+            if !d.lighting_modes.contains(&config.led_mode) {
+                return Err(Error::UnsupportedFeature("lighting_mode"));
+            }
+        }
         info!("写入键盘配置：{:?}", config);
-        self.write_large(Packet::new(PacketID::SetLightConfig, config.to_cbor()))?;
+        let data = config.to_cbor_with(|v| {
+            if let Some(raw) = &self.light_config_raw {
+                restore_unknown(v, raw);
+            }
+        });
+        self.write_large(Packet::new(PacketID::SetLightConfig, data))?;
         let packet = self.read()?; // 读取
         if packet.id == PacketID::Ok as u8 {
             Ok(())
@@ -170,6 +236,7 @@ impl<D: Device> Meowpad<D> {
         self.write(Packet::new(PacketID::SaveKeyConfig, []))?;
         let packet = self.read()?;
         if packet.id == PacketID::Ok as u8 {
+            self.saved_key_config = self.key_config;
             Ok(())
         } else {
             dbg!(packet.id);
@@ -182,6 +249,7 @@ impl<D: Device> Meowpad<D> {
         self.write(Packet::new(PacketID::SaveLightConfig, []))?;
         let packet = self.read()?;
         if packet.id == PacketID::Ok as u8 {
+            self.saved_light_config = self.light_config;
             Ok(())
         } else {
             dbg!(packet.id);
@@ -190,10 +258,75 @@ impl<D: Device> Meowpad<D> {
         }
     }
 
+    /// 写入按键与灯光配置并读回比较，全部一致后才保存到闪存；
+    /// 失败时把写入前的配置写回内存，闪存中的配置不受影响。
+    /// 灯光配置保存失败时把之前的按键配置重新保存，仍然失败则返回 `PartialSave`
+    pub fn apply(&mut self, key: cbor::Keyboard, light: cbor::Light) -> Result<()> {
+        self.load_key_config()?;
+        self.load_light_config()?;
+        let previous = (self.key_config, self.light_config);
+        let saved_key = self.saved_key_config;
+        if let Err(e) = self.write_and_verify(key, light) {
+            warn!("写入配置失败，恢复写入前的配置：{}", e);
+            if let Err(e) = self.restore(previous) {
+                error!("恢复配置失败：{}", e);
+            }
+            return Err(e);
+        }
+        if let Err(e) = self.save_key_config() {
+            warn!("保存按键配置失败，恢复写入前的配置：{}", e);
+            if let Err(e) = self.restore(previous) {
+                error!("恢复配置失败：{}", e);
+            }
+            return Err(e);
+        }
+        if let Err(e) = self.save_light_config() {
+            warn!("保存灯光配置失败，恢复闪存中原来的按键配置：{}", e);
+            if let Err(e) = self.restore((saved_key, previous.1)).and_then(|_| self.save_key_config()) {
+                error!("恢复按键配置失败，闪存中只有按键配置是新的：{}", e);
+                return Err(Error::PartialSave("key_config"));
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn restore(&mut self, (key, light): (Option<cbor::Keyboard>, Option<cbor::Light>)) -> Result<()> {
+        (self.key_config, self.light_config) = (key, light);
+        self.set_key_config()?;
+        self.set_light_config()
+    }
+
+    fn write_and_verify(&mut self, key: cbor::Keyboard, light: cbor::Light) -> Result<()> {
+        self.key_config = Some(key);
+        self.set_key_config()?;
+        self.light_config = Some(light);
+        self.set_light_config()?;
+        self.load_key_config()?;
+        self.load_light_config()?;
+        if self.key_config != Some(key) {
+            return Err(Error::VerifyFailed("key_config"));
+        }
+        if self.light_config != Some(light) {
+            return Err(Error::VerifyFailed("light_config"));
+        }
+        Ok(())
+    }
+
+    /// 与闪存中的配置不同即视为未保存
+    pub fn unsaved(&self) -> Unsaved {
+        Unsaved {
+            key: self.key_config.is_some() && self.key_config != self.saved_key_config,
+            light: self.light_config.is_some() && self.light_config != self.saved_light_config,
+        }
+    }
+
     pub fn clear_key_config(&mut self) -> Result<()> {
         self.write(Packet::new(PacketID::ClearKeyConfig, []))?;
         let packet = self.read()?;
         if packet.id == PacketID::Ok as u8 {
+            self.saved_key_config = None;
+            self.key_config = None;
             Ok(())
         } else {
             dbg!(packet.id);
@@ -206,6 +339,8 @@ impl<D: Device> Meowpad<D> {
         self.write(Packet::new(PacketID::ClearLightConfig, []))?;
         let packet = self.read()?;
         if packet.id == PacketID::Ok as u8 {
+            self.saved_light_config = None;
+            self.light_config = None;
             Ok(())
         } else {
             dbg!(packet.id);
@@ -275,6 +410,7 @@ impl<D: Device> Meowpad<D> {
     }
 
     fn write_large(&self, packet: Packet) -> Result<()> {
+        self.check_supported(packet.id)?;
         debug!("发送：{:?}", packet);
         debug!("总数据大小：{}", packet.data.len());
         for v in packet.build_packets_large() {
@@ -285,7 +421,21 @@ impl<D: Device> Meowpad<D> {
         Ok(())
     }
 
+    /// 设备返回过描述时，拒绝发送设备不支持的数据包
+    fn check_supported(&self, packet_id: u8) -> Result<()> {
+        match &self.descriptor {
+            Some(d) if packet_id >= PacketID::GetFirmwareVersion as u8
+                && packet_id != PacketID::GetDescriptor as u8
+                && !d.supports(packet_id) =>
+            {
+                Err(Error::UnsupportedPacket(packet_id))
+            }
+            _ => Ok(()),
+        }
+    }
+
     fn write(&self, packet: Packet) -> Result<()> {
+        self.check_supported(packet.id)?;
         debug!("发送：{:?}", packet);
         debug!("总数据大小：{}", packet.data.len());
         for v in packet.build_packets() {
@@ -0,0 +1,49 @@
+//! 配置结构版本与迁移
+//!
+//! 设备上的CBOR与本地保存的配置都可能来自旧版本，读取时先逐级升级到当前结构。
+//!
+//! | 版本 | 固件 | 变化 |
+//! | ---- | ---- | ---- |
+//! | 1 | 全部 | 初始结构 |
+
+use crate::config;
+use ciborium::Value;
+use meowpad::cbor::migrate;
+use serde::{de::Error as _, Deserialize, Deserializer};
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+const KEY_MIGRATIONS: [fn(&mut Value); 0] = [];
+const LIGHT_MIGRATIONS: [fn(&mut Value); 0] = [];
+
+/// 将设备按键CBOR升级到当前结构，返回升级前的版本
+pub fn migrate_key_cbor(value: &mut Value, version: Option<u32>) -> u32 {
+    let from = version.filter(|v| *v > 0).unwrap_or(SCHEMA_VERSION);
+    migrate(value, from, &KEY_MIGRATIONS);
+    from
+}
+
+/// 将设备灯光CBOR升级到当前结构，返回升级前的版本
+pub fn migrate_light_cbor(value: &mut Value, version: Option<u32>) -> u32 {
+    let from = version.filter(|v| *v > 0).unwrap_or(SCHEMA_VERSION);
+    migrate(value, from, &LIGHT_MIGRATIONS);
+    from
+}
+
+/// 读取任意版本的本地按键配置
+pub fn key_config_from<'de, D: Deserializer<'de>>(deserializer: D, version: u32) -> Result<config::Key, D::Error> {
+    let mut value = Value::deserialize(deserializer)?;
+    migrate(&mut value, version.max(1), &KEY_MIGRATIONS);
+    value
+        .deserialized()
+        .map_err(|ciborium::value::Error::Custom(e)| D::Error::custom(e))
+}
+
+/// 读取任意版本的本地灯光配置
+pub fn light_config_from<'de, D: Deserializer<'de>>(deserializer: D, version: u32) -> Result<config::Light, D::Error> {
+    let mut value = Value::deserialize(deserializer)?;
+    migrate(&mut value, version.max(1), &LIGHT_MIGRATIONS);
+    value
+        .deserialized()
+        .map_err(|ciborium::value::Error::Custom(e)| D::Error::custom(e))
+}
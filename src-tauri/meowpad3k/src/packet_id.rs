@@ -24,6 +24,7 @@ pub enum PacketID {
     ToggleKeyboard     = 14,
     Reset              = 15,
     GetStatus          = 16,
+    GetDescriptor      = 17,
 
     // 配置部分
     GetKeyConfig     = 100,
@@ -0,0 +1,48 @@
+use crate::config;
+use ciborium::Value;
+use meowpad::{error::Error, FirmwareVersion, Result};
+use serde::{Deserialize, Serialize};
+
+/// 高速模式开关 (`ehs`) 从该版本开始支持
+const ENABLE_HS_SINCE: FirmwareVersion = FirmwareVersion(0, 1, 1);
+
+/// 已连接固件支持的配置项，默认认为支持全部功能
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    pub enable_hs: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self { enable_hs: true }
+    }
+}
+
+impl Capabilities {
+    pub fn from_version(version: &str) -> Self {
+        let Ok(version) = version.parse::<FirmwareVersion>() else {
+            return Self::default();
+        };
+        Self {
+            enable_hs: version >= ENABLE_HS_SINCE,
+        }
+    }
+
+    /// 拒绝在不支持的固件上使用非默认值的配置项
+    pub fn check_key(&self, cfg: &config::Key) -> Result<()> {
+        if !self.enable_hs && cfg.enable_hs != config::Key::default().enable_hs {
+            return Err(Error::UnsupportedFeature("enable_hs"));
+        }
+        Ok(())
+    }
+
+    /// 移除固件不支持的CBOR字段
+    pub fn strip_key(&self, value: &mut Value) {
+        if let Some(map) = value.as_map_mut() {
+            map.retain(|(k, _)| match k.as_text() {
+                Some("ehs") => self.enable_hs,
+                _ => true,
+            });
+        }
+    }
+}
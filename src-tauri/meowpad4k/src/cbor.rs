@@ -4,7 +4,7 @@ use palette::{rgb::channels::Argb, WithAlpha};
 use palette::Srgb;
 use serde::{Deserialize, Serialize};
 use serde_with::*;
-use std::io::Cursor;
+pub use meowpad::cbor::CborConvertor;
 
 #[inline]
 pub unsafe fn serialize_raw<T: Sized>(src: &T) -> &[u8] {
@@ -12,7 +12,7 @@ pub unsafe fn serialize_raw<T: Sized>(src: &T) -> &[u8] {
 }
 
 #[repr(C)]
-#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
 #[allow(non_snake_case)]
 pub struct KeyRTConfig {
     #[serde(rename = "pp")]
@@ -26,7 +26,7 @@ pub struct KeyRTConfig {
 }
 
 #[repr(C)]
-#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
 #[allow(non_snake_case)]
 pub struct Keyboard {
     #[serde(rename = "ks")]
@@ -37,14 +37,18 @@ pub struct Keyboard {
     pub ContinuousReport: bool,
     #[serde(rename = "kf")]
     pub KalmanFilter: bool,
-    #[serde(rename = "ehs")]
+    #[serde(rename = "ehs", default = "default_true")]
     pub EnableHS: bool,
 }
 
+fn default_true() -> bool {
+    true
+}
+
 /// speed 0-10
 /// steep length 50 - 200
 #[repr(C)]
-#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
 #[allow(non_snake_case)]
 pub struct Light {
     #[serde(rename = "c")]
@@ -109,24 +113,9 @@ pub struct Light {
     pub high_speed_color: u32,
 }
 
-pub trait CborConvertor
-where
-    Self: Sized + Serialize,
-    for<'de> Self: Deserialize<'de>,
-{
-    fn from_cbor<T: AsRef<[u8]>>(data: T) -> Result<Self, ciborium::de::Error<std::io::Error>> {
-        Ok(ciborium::de::from_reader(Cursor::new(data))?)
-    }
-
-    fn to_cbor(self) -> Vec<u8> {
-        let mut data = vec![];
-        ciborium::ser::into_writer(&self, &mut data).unwrap();
-        data
-    }
-}
-
 impl CborConvertor for Keyboard {}
 impl CborConvertor for Light {}
+impl CborConvertor for KeyRTConfig {}
 
 impl From<config::KeyConfig> for KeyRTConfig {
     fn from(cfg: config::KeyConfig) -> Self {
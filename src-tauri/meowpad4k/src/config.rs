@@ -1,9 +1,10 @@
 use crate::cbor;
-use meowpad::{KeyCode, error::Error, KbReport};
+use meowpad::{KeyCode, diff::{changes, changes_where, ConfigDiff}, error::Error, KbReport, schema::{repr_enum, Rgb}, validate::{Diagnostic, Validator}};
 use num::FromPrimitive;
 use num_derive::{FromPrimitive, ToPrimitive};
 use palette::rgb::channels::Argb;
 use palette::Srgb;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_repr::*;
 use serde_with::*;
@@ -30,52 +31,68 @@ pub enum LightingMode {
     SpeedLightMode
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, Default, Copy)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Default, Copy)]
 pub struct KeyConfig {
+    #[schemars(range(min = 1, max = 100))]
     pub press_percentage: u8,
+    #[schemars(range(min = 1, max = 100))]
     pub release_percentage: u8,
+    #[schemars(range(max = 100), extend("x-recommended-maximum" = 30))]
     pub dead_zone: u8,   // 0-30
     pub key_data: [KeyCode; 6],
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, Copy)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Copy)]
 pub struct Key {
     pub keys: [KeyConfig; 4],
+    /// 以 1/8 ms 为单位
+    #[schemars(range(max = 400))]
     pub jitters_elimination_time: u16,
     pub continuous_report: bool,
     pub kalman_filter: bool,
     pub enable_hs: bool
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, Copy)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Copy)]
 pub struct Light {
     /// LED灯颜色
+    #[schemars(with = "[Rgb; 4]")]
     pub led_colors: [Srgb<u8>; 4],
     /// 灯效模式
     pub lighting_mode: LightingMode,
     pub lighting_mode_sleep: LightingMode,
+    #[schemars(range(max = 50))]
     pub max_brightness: u8,
     pub sleep_time: u16,
 
     // rainbow_flow_mode
+    #[schemars(range(min = 10, max = 500))]
     pub rainbow_flow_speed: u16,
+    #[schemars(range(min = 1, max = 10))]
     pub color_change_rate: u8,
     pub is_flow_delay: bool,
 
     // rainbow_mode
+    #[schemars(range(min = 1, max = 30))]
     pub rainbow_speed: u16,
 
     // breathing_mode
+    #[schemars(range(max = 20))]
     pub breathing_speed: u16,
+    #[schemars(range(max = 1000))]
     pub max_keep_time: u16,
+    #[schemars(range(max = 1000))]
     pub min_keep_time: u16,
     pub breaths_before_color_switch: u8,
 
     // rain_drop_mode
+    #[schemars(range(min = 1, max = 40))]
     pub rain_drop_speed: u16,
+    #[schemars(range(min = 10, max = 1000))]
     pub random_rain_chance: u16,
 
     // tap_to_glow_mode
+    #[schemars(range(min = 1, max = 40))]
     pub tap_to_glow_speed: u16,
     pub max_lum_freeze_time: u8,
     pub change_color_when_pressed: bool,
@@ -85,11 +102,24 @@ pub struct Light {
     // speed_light_mode
     pub speed_light_mode_speed: u16,
     pub attenuation_speed: u16,
+    #[schemars(range(min = 1, max = 40))]
     pub increase_difficulty: u8,
+    #[schemars(with = "Rgb")]
     pub low_speed_color: Srgb<u8>,
+    #[schemars(with = "Rgb")]
     pub high_speed_color: Srgb<u8>,
 }
 
+impl JsonSchema for LightingMode {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "LightingMode".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        repr_enum((0..=u8::MAX).filter(|v| LightingMode::from_u8(*v).is_some()))
+    }
+}
+
 impl Default for Key {
     fn default() -> Self {
         cbor::Keyboard::default().try_into().unwrap()
@@ -102,6 +132,39 @@ impl Default for Light {
     }
 }
 
+impl Key {
+    /// 按JSON Schema中的范围检查
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        Validator::from_schema(self).finish()
+    }
+
+    /// 键位只有一层，位置即按键序号
+    pub fn diff(&self, new: &Self) -> ConfigDiff {
+        let mut diff = ConfigDiff::default();
+        for (i, (a, b)) in self.keys.iter().zip(&new.keys).enumerate() {
+            diff.key(i, a, b, |f| f != "key_data");
+            diff.keymap(0, i, &a.key_data, &b.key_data);
+        }
+        diff.device = changes_where(self, new, |f| f != "keys");
+        diff
+    }
+}
+
+impl Light {
+    pub fn diff(&self, new: &Self) -> ConfigDiff {
+        ConfigDiff {
+            light: changes(self, new),
+            ..Default::default()
+        }
+    }
+
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut v = Validator::from_schema(self);
+        v.range("min_keep_time", self.min_keep_time, 0, self.max_keep_time.into());
+        v.finish()
+    }
+}
+
 
 impl TryFrom<cbor::Keyboard> for Key {
     type Error = Error;
@@ -0,0 +1,154 @@
+//! 初代 Meowpad (V1) 配置导入
+//!
+//! V1 配置器 (0.3.1) 导出的JSON与CBOR使用缩写字段，按下表转换为V2的按键与灯光配置，
+//! 其余字段在V2中没有对应设置，导入时列在 `ignored` 中。
+//!
+//! | V1 | V2 |
+//! | -- | -- |
+//! | `k.k1` .. `k.k4` | `keys[0..4].key_data` |
+//! | `k.jet` | `jitters_elimination_time` |
+//! | `l.1c` .. `l.4c` | `led_colors` |
+//! | `l.slhc` / `l.sllc` | `high_speed_color` / `low_speed_color` |
+//! | `l.km` / `l.lksm` | `lighting_mode` / `lighting_mode_sleep` |
+//! | `l.lmx` | `max_brightness` |
+//! | `l.dsit` | `sleep_time` |
+//! | `l.blts` / `l.blmxkt` / `l.blmnkt` | `breathing_speed` / `max_keep_time` / `min_keep_time` |
+//! | `l.rbsc1` | `rainbow_speed` |
+//! | `l.ptgts` | `tap_to_glow_speed` |
+//! | `l.slts` / `l.slsl` | `speed_light_mode_speed` / `increase_difficulty` |
+//!
+//! 灯效编号按V2固件解释，无法识别的灯效保留V2的默认值。
+
+use std::{collections::BTreeMap, io::Cursor};
+
+use crate::config::{Key, Light, LightingMode};
+use meowpad::KbReport;
+use num::FromPrimitive;
+use palette::{rgb::channels::Argb, Srgb};
+use serde::Deserialize;
+use serde_json::Value;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct V1Config {
+    #[serde(rename = "k")]
+    pub key: V1Key,
+    #[serde(rename = "l")]
+    pub light: V1Light,
+}
+
+/// 缺少的字段使用V2的默认值
+#[derive(Deserialize, Debug, Clone)]
+pub struct V1Key {
+    pub k1: Option<[u8; 4]>,
+    pub k2: Option<[u8; 4]>,
+    pub k3: Option<[u8; 4]>,
+    pub k4: Option<[u8; 4]>,
+    pub jet: Option<u16>,
+    /// V2中没有对应设置的字段
+    #[serde(flatten)]
+    pub other: BTreeMap<String, Value>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct V1Light {
+    #[serde(rename = "1c")]
+    pub c1: Option<u32>,
+    #[serde(rename = "2c")]
+    pub c2: Option<u32>,
+    #[serde(rename = "3c")]
+    pub c3: Option<u32>,
+    #[serde(rename = "4c")]
+    pub c4: Option<u32>,
+    pub slhc: Option<u32>,
+    pub sllc: Option<u32>,
+    pub km: Option<u8>,
+    pub lksm: Option<u8>,
+    pub lmx: Option<u16>,
+    pub dsit: Option<u16>,
+    pub blts: Option<u16>,
+    pub blmxkt: Option<u16>,
+    pub blmnkt: Option<u16>,
+    pub rbsc1: Option<u16>,
+    pub ptgts: Option<u16>,
+    pub slts: Option<u16>,
+    pub slsl: Option<u16>,
+    /// V2中没有对应设置的字段
+    #[serde(flatten)]
+    pub other: BTreeMap<String, Value>,
+}
+
+/// 转换后的V2配置
+#[derive(Debug, Clone)]
+pub struct Imported {
+    pub key: Key,
+    pub light: Light,
+    /// 没有导入的V1字段，例如 `k.k5`
+    pub ignored: Vec<String>,
+}
+
+impl V1Config {
+    pub fn from_json(text: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(text)
+    }
+
+    pub fn from_cbor<T: AsRef<[u8]>>(data: T) -> Result<Self, ciborium::de::Error<std::io::Error>> {
+        ciborium::de::from_reader(Cursor::new(data))
+    }
+
+    pub fn convert(&self) -> Imported {
+        let (k, l) = (&self.key, &self.light);
+        let mut ignored: Vec<String> = k.other.keys().map(|f| format!("k.{}", f))
+            .chain(l.other.keys().map(|f| format!("l.{}", f)))
+            .collect();
+
+        let mut key = Key::default();
+        for (cfg, codes) in key.keys.iter_mut().zip([k.k1, k.k2, k.k3, k.k4]) {
+            if let Some(codes) = codes {
+                let mut data = [0u8; 6];
+                data[..4].copy_from_slice(&codes);
+                cfg.key_data = KbReport::from(data).into();
+            }
+        }
+        set(&mut key.jitters_elimination_time, k.jet);
+
+        let mut light = Light::default();
+        for (color, v1) in light.led_colors.iter_mut().zip([l.c1, l.c2, l.c3, l.c4]) {
+            set(color, v1.map(Srgb::from_u32::<Argb>));
+        }
+        set(&mut light.high_speed_color, l.slhc.map(Srgb::from_u32::<Argb>));
+        set(&mut light.low_speed_color, l.sllc.map(Srgb::from_u32::<Argb>));
+        for (mode, v1, field) in [
+            (&mut light.lighting_mode, l.km, "l.km"),
+            (&mut light.lighting_mode_sleep, l.lksm, "l.lksm"),
+        ] {
+            match v1.map(lighting_mode) {
+                Some(Some(m)) => *mode = m,
+                Some(None) => ignored.push(field.to_owned()),
+                None => {}
+            }
+        }
+        // V2的上限较低，超出时取上限
+        set(&mut light.max_brightness, l.lmx.map(|v| v.min(50) as u8));
+        set(&mut light.increase_difficulty, l.slsl.map(|v| v.clamp(1, 40) as u8));
+        set(&mut light.sleep_time, l.dsit);
+        set(&mut light.breathing_speed, l.blts);
+        set(&mut light.max_keep_time, l.blmxkt);
+        set(&mut light.min_keep_time, l.blmnkt);
+        set(&mut light.rainbow_speed, l.rbsc1);
+        set(&mut light.tap_to_glow_speed, l.ptgts);
+        set(&mut light.speed_light_mode_speed, l.slts);
+
+        Imported { key, light, ignored }
+    }
+}
+
+fn set<T>(field: &mut T, value: Option<T>) {
+    if let Some(v) = value {
+        *field = v;
+    }
+}
+
+/// 校准与错误提示不是可选的灯效
+fn lighting_mode(v: u8) -> Option<LightingMode> {
+    LightingMode::from_u8(v).filter(|m| !matches!(m, LightingMode::Calibration | LightingMode::Error))
+}
@@ -1,5 +1,8 @@
 pub mod cbor;
 pub mod config;
+pub mod capability;
+pub mod migrate;
+pub mod legacy;
 mod meowpad;
 mod packet_id;
 
@@ -9,6 +12,7 @@ pub use crate::packet_id::PacketID;
 #[cfg(test)]
 mod tests {
     use crate::*;
+    use ::meowpad::{packet::PacketBuilder, KbReport, KeyCode, Packet};
     use pretty_hex::*;
     use std::io::{Cursor, Read};
     use crate::cbor::CborConvertor;
@@ -89,8 +93,9 @@ mod tests {
                     Err(_) => {
                         // cur已经遍历结束
                         buf.get_mut().iter_mut().for_each(|b| *b = 0);
-                        buf.set_position(0);
                         read_packet(&mut packet_builder, buf.get_mut());
+                        // 后续的数据包以0xFE开头
+                        buf.set_position(1);
                     }
                 }
             } else {
@@ -107,14 +112,14 @@ mod tests {
     fn cbor_kb() {
         let c = cbor::Keyboard::default().to_cbor();
         dbg!(c.hex_dump());
-        assert!(c.len() <= 1);
+        assert_eq!(cbor::Keyboard::from_cbor(&c).unwrap(), cbor::Keyboard::default());
     }
 
     #[test]
     fn cbor_light() {
         let c = cbor::Light::default().to_cbor();
         dbg!(c.hex_dump());
-        assert!(c.len() <= 1);
+        assert_eq!(cbor::Light::from_cbor(&c).unwrap(), cbor::Light::default());
     }
 
     #[test]
@@ -154,4 +159,155 @@ mod tests {
             vec![]
         );
     }
+
+    /// 前端手写的类型与JSON Schema的字段一致
+    #[test]
+    fn ts_interfaces() {
+        use ::meowpad::schema::{schema_fields, ts_interface_fields};
+        let config = include_str!("../../../src/apis/meowpad4k/config.ts");
+        let index = include_str!("../../../src/apis/index.ts");
+        assert_eq!(ts_interface_fields(config, "IKeyboard"), schema_fields::<config::Key>());
+        assert_eq!(ts_interface_fields(config, "ILighting"), schema_fields::<config::Light>());
+        assert_eq!(ts_interface_fields(index, "IKeyConfig"), schema_fields::<config::KeyConfig>());
+    }
+
+    /// 各版本固件导出的配置
+    #[test]
+    fn migrate_golden() {
+        use crate::migrate::*;
+        for (data, version, enable_hs) in [
+            (&include_bytes!("../fixtures/meowpad4k-key-0.1.0.cbor")[..], 1, true),
+            (&include_bytes!("../fixtures/meowpad4k-key-0.1.1.cbor")[..], 2, false),
+        ] {
+            let mut value: ciborium::Value = ciborium::de::from_reader(data).unwrap();
+            assert_eq!(migrate_key_cbor(&mut value, None), version);
+            let (cfg, report) = ::meowpad::cbor::from_value_lenient::<cbor::Keyboard>(value).unwrap();
+            assert!(report.is_empty(), "{:?}", report);
+            assert_eq!(cfg.KeyConfigs[0].PressPercentage, 40);
+            assert_eq!(cfg.KeyConfigs[2].DeadZone, 12);
+            assert_eq!(cfg.JittersEliminationTime, 60);
+            assert_eq!(cfg.EnableHS, enable_hs);
+        }
+
+        let mut table: toml::Table = toml::from_str(include_str!("../fixtures/meowpad4k-config-v1.toml")).unwrap();
+        let key = table.remove("key").unwrap();
+        assert!(key.clone().try_into::<config::Key>().is_err());
+        let key = key_config_from(key, 0).unwrap();
+        assert_eq!(key.keys[0].press_percentage, 40);
+        assert_eq!(key.jitters_elimination_time, 60);
+        assert!(key.enable_hs);
+        assert!(light_config_from(table.remove("light").unwrap(), 1).is_ok());
+    }
+
+    /// 仓库根目录的V1配置示例
+    #[test]
+    fn legacy_v1_import() {
+        use crate::{config::LightingMode, legacy::V1Config};
+        use ::meowpad::KeyCode;
+        let json = V1Config::from_json(include_str!("../../../example.json")).unwrap().convert();
+        let cbor = V1Config::from_cbor(include_bytes!("../../../example.cbor")).unwrap().convert();
+        for imported in [&json, &cbor] {
+            assert_eq!(imported.key.keys[0].key_data[0], KeyCode::Z);
+            assert_eq!(imported.key.keys[3].key_data[0], KeyCode::F2);
+            assert_eq!(imported.key.keys[3].key_data[1], KeyCode::None);
+            assert_eq!(imported.key.jitters_elimination_time, 100);
+            assert_eq!(imported.light.lighting_mode, LightingMode::BreatheGlowMode);
+            assert_eq!(imported.light.lighting_mode_sleep, LightingMode::Off);
+            assert_eq!(imported.light.high_speed_color, palette::Srgb::new(255, 0, 0));
+            assert_eq!(imported.light.max_brightness, 50);
+            assert_eq!(imported.light.sleep_time, 60);
+            assert!(imported.key.validate().is_empty());
+            assert!(imported.light.validate().is_empty());
+            assert!(imported.ignored.contains(&"k.k5".to_owned()));
+            assert!(imported.ignored.contains(&"l.bm".to_owned()));
+        }
+        assert_eq!(json.ignored, cbor.ignored);
+    }
+
+    #[derive(Default)]
+    struct MockDevice {
+        responses: std::cell::RefCell<std::collections::VecDeque<[u8; 64]>>,
+    }
+
+    impl MockDevice {
+        /// 超过一帧的响应，后续帧不带包头
+        fn respond(&self, id: PacketID, data: &[u8]) {
+            let first = data.len().min(61);
+            let mut frame = [0u8; 64];
+            frame[0] = id as u8;
+            frame[1..3].copy_from_slice(&(data.len() as u16).to_be_bytes());
+            frame[3..3 + first].copy_from_slice(&data[..first]);
+            self.responses.borrow_mut().push_back(frame);
+            for chunk in data[first..].chunks(64) {
+                let mut frame = [0u8; 64];
+                frame[..chunk.len()].copy_from_slice(chunk);
+                self.responses.borrow_mut().push_back(frame);
+            }
+        }
+    }
+
+    impl ::meowpad::Device for MockDevice {
+        fn get_serial(&self) -> ::meowpad::Result<String> {
+            Ok("mock".to_owned())
+        }
+
+        fn write(&self, data: &[u8]) -> ::meowpad::Result<usize> {
+            Ok(data.len())
+        }
+
+        fn read(&self, buf: &mut [u8]) -> ::meowpad::Result<usize> {
+            let frame = self.responses.borrow_mut().pop_front().ok_or(::meowpad::error::Error::Disconnect)?;
+            buf.copy_from_slice(&frame);
+            Ok(frame.len())
+        }
+
+        fn read_timeout(&self, buf: &mut [u8], _timeout: i32) -> ::meowpad::Result<usize> {
+            self.read(buf)
+        }
+
+        fn clear_buffer(&self) -> ::meowpad::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn apply_partial_save() {
+        use ::meowpad::error::Error;
+        let (key0, light0) = (cbor::Keyboard::default(), cbor::Light::default());
+        let mut key = key0;
+        key.JittersEliminationTime = 10;
+        let mut light = light0;
+        light.max_brightness = 10;
+        let pad = |restore_saved: PacketID| {
+            let pad = Meowpad::new(MockDevice::default());
+            let d = &pad.device;
+            // 读取、写入并读回
+            d.respond(PacketID::Ok, &key0.to_cbor());
+            d.respond(PacketID::Ok, &light0.to_cbor());
+            d.respond(PacketID::Ok, &[]);
+            d.respond(PacketID::Ok, &[]);
+            d.respond(PacketID::Ok, &key.to_cbor());
+            d.respond(PacketID::Ok, &light.to_cbor());
+            // 按键配置保存成功，灯光配置保存失败
+            d.respond(PacketID::Ok, &[]);
+            d.respond(PacketID::Bad, &[]);
+            // 写回原配置并重新保存按键配置
+            d.respond(PacketID::Ok, &[]);
+            d.respond(PacketID::Ok, &[]);
+            d.respond(restore_saved, &[]);
+            pad
+        };
+
+        let mut p = pad(PacketID::Ok);
+        assert!(matches!(p.apply(key, light), Err(Error::UnexceptedResponse(_))));
+        assert_eq!((p.key_config, p.light_config), (Some(key0), Some(light0)));
+        assert_eq!((p.saved_key_config, p.saved_light_config), (Some(key0), Some(light0)));
+        assert!(p.device.responses.borrow().is_empty());
+
+        // 恢复失败时明确报告只保存了按键配置
+        let mut p = pad(PacketID::Bad);
+        assert!(matches!(p.apply(key, light), Err(Error::PartialSave("key_config"))));
+        assert_eq!(p.saved_key_config, Some(key));
+        assert!(p.unsaved().key);
+    }
 }
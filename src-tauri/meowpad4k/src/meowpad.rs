@@ -1,5 +1,5 @@
 use crate::{
-    cbor, packet_id::PacketID
+    capability::Capabilities, cbor, migrate, packet_id::PacketID
 };
 use meowpad::{Packet, error::Error, Result, models::*, Device};
 use byteorder::{BigEndian, ReadBytesExt};
@@ -8,24 +8,44 @@ use num::FromPrimitive;
 use pretty_hex::*;
 use std::{io::Cursor, thread, time::Duration};
 use crate::cbor::CborConvertor;
+use meowpad::cbor::{from_value_lenient, restore_unknown, DecodeReport};
 
 pub struct Meowpad<D: Device> {
     pub key_config: Option<cbor::Keyboard>,
     pub light_config: Option<cbor::Light>,
+    /// 设备返回的原始配置，写回时保留其中不认识的字段
+    pub key_config_raw: Option<ciborium::Value>,
+    pub light_config_raw: Option<ciborium::Value>,
+    /// 闪存中的配置，取自连接后第一次读取或最后一次保存
+    pub saved_key_config: Option<cbor::Keyboard>,
+    pub saved_light_config: Option<cbor::Light>,
     pub device_name: Option<String>,
     pub firmware_version: Option<String>,
+    pub capabilities: Capabilities,
+    pub descriptor: Option<Descriptor>,
     pub device: D,
 }
 
 
+/// 除按键外的设置相同时才能逐个按键写入
+fn same_globals(a: &cbor::Keyboard, b: &cbor::Keyboard) -> bool {
+    cbor::Keyboard { KeyConfigs: b.KeyConfigs, ..*a } == *b
+}
+
 impl<D: Device> Meowpad<D> {
     pub fn new(device: D) -> Meowpad<D> {
         Meowpad {
             device,
             key_config: None,
             light_config: None,
+            key_config_raw: None,
+            light_config_raw: None,
+            saved_key_config: None,
+            saved_light_config: None,
             device_name: None,
             firmware_version: None,
+            capabilities: Capabilities::default(),
+            descriptor: None,
         }
     }
 
@@ -56,7 +76,9 @@ impl<D: Device> Meowpad<D> {
         self.write(Packet::new(PacketID::GetFirmwareVersion, []))?;
         let packet = self.read()?; // 读取
         if packet.id == PacketID::Ok as u8 {
-            self.firmware_version = Some(String::from_utf8(packet.data)?);
+            let version = String::from_utf8(packet.data)?;
+            self.capabilities = Capabilities::from_version(&version);
+            self.firmware_version = Some(version);
             Ok(())
         } else {
             dbg!(packet.id);
@@ -65,6 +87,26 @@ impl<D: Device> Meowpad<D> {
         }
     }
 
+    /// 查询设备描述，旧固件不支持该数据包时返回 `None`
+    pub fn get_descriptor(&mut self) -> Result<Option<Descriptor>> {
+        self.write(Packet::new(PacketID::GetDescriptor, []))?;
+        let packet = self.read_timeout(1000)?;
+        if packet.id == PacketID::Ok as u8 {
+            let descriptor = Descriptor::from_cbor(packet.data)?;
+            self.descriptor = Some(descriptor.clone());
+            Ok(Some(descriptor))
+        } else {
+            debug!("设备不支持描述查询: {}", packet.id);
+            self.descriptor = None;
+            Ok(None)
+        }
+    }
+
+    /// 设备描述中报告的配置结构版本
+    fn schema_version(&self) -> Option<u32> {
+        self.descriptor.as_ref().map(|d| d.schema_version as u32)
+    }
+
     /// (按键配置状态，灯光配置状态，按键校准状态，按键是否启用)
     pub fn get_status(&mut self) -> Result<DeviceStatus> {
         self.write(Packet::new(PacketID::GetStatus, []))?;
@@ -123,24 +165,51 @@ impl<D: Device> Meowpad<D> {
     }
 
 
-    pub fn load_key_config(&mut self) -> Result<()> {
+    pub fn load_key_config(&mut self) -> Result<DecodeReport> {
         self.write(Packet::new(PacketID::GetKeyConfig, []))?;
         let packet = self.read()?;
-        self.key_config = Some(cbor::Keyboard::from_cbor(packet.data)?);
-        Ok(())
+        let raw: ciborium::Value = ciborium::de::from_reader(packet.data.as_slice())?;
+        let mut value = raw.clone();
+        let version = migrate::migrate_key_cbor(&mut value, self.schema_version());
+        if version != migrate::SCHEMA_VERSION {
+            info!("按键配置结构版本 {} 已升级到 {}", version, migrate::SCHEMA_VERSION);
+        }
+        let (config, report) = from_value_lenient::<cbor::Keyboard>(value)?;
+        if !report.is_empty() {
+            warn!("按键配置与当前版本不一致：{:?}", report);
+        }
+        self.saved_key_config.get_or_insert(config);
+        self.key_config = Some(config);
+        self.key_config_raw = Some(raw);
+        Ok(report)
     }
     
-    pub fn load_light_config(&mut self) -> Result<()> {
+    pub fn load_light_config(&mut self) -> Result<DecodeReport> {
         self.write(Packet::new(PacketID::GetLightConfig, []))?;
         let packet = self.read()?;
-        self.light_config = Some(cbor::Light::from_cbor(packet.data)?);
-        Ok(())
+        let raw: ciborium::Value = ciborium::de::from_reader(packet.data.as_slice())?;
+        let mut value = raw.clone();
+        migrate::migrate_light_cbor(&mut value, self.schema_version());
+        let (config, report) = from_value_lenient::<cbor::Light>(value)?;
+        if !report.is_empty() {
+            warn!("灯光配置与当前版本不一致：{:?}", report);
+        }
+        self.saved_light_config.get_or_insert(config);
+        self.light_config = Some(config);
+        self.light_config_raw = Some(raw);
+        Ok(report)
     }
 
     pub fn set_key_config(&self) -> Result<()> {
         let config = self.key_config.ok_or(Error::EmptyConfig)?;
         debug!("写入键盘配置：{:?}", config);
-        self.write(Packet::new(PacketID::SetKeyConfig, config.to_cbor()))?;
+        let data = config.to_cbor_with(|v| {
+            self.capabilities.strip_key(v);
+            if let Some(raw) = &self.key_config_raw {
+                restore_unknown(v, raw);
+            }
+        });
+        self.write(Packet::new(PacketID::SetKeyConfig, data))?;
         let packet = self.read()?; // 读取
         if packet.id == PacketID::Ok as u8 {
             Ok(())
@@ -153,8 +222,18 @@ impl<D: Device> Meowpad<D> {
 
     pub fn set_light_config(&self) -> Result<()> {
         let config = self.light_config.ok_or(Error::EmptyConfig)?;
+        if let Some(d) = self.descriptor.as_ref().filter(|d| !d.lighting_modes.is_empty()) {
+            if !d.lighting_modes.contains(&config.led_mode) || !d.lighting_modes.contains(&config.led_mode_sleep) {
+                return Err(Error::UnsupportedFeature("lighting_mode"));
+            }
+        }
         debug!("写入灯光配置：{:?}", config);
-        self.write(Packet::new(PacketID::SetLightConfig, config.to_cbor()))?;
+        let data = config.to_cbor_with(|v| {
+            if let Some(raw) = &self.light_config_raw {
+                restore_unknown(v, raw);
+            }
+        });
+        self.write(Packet::new(PacketID::SetLightConfig, data))?;
         let packet = self.read()?; // 读取
         if packet.id == PacketID::Ok as u8 {
             Ok(())
@@ -165,10 +244,103 @@ impl<D: Device> Meowpad<D> {
         }
     }
 
+    /// 只写入与当前配置不同的按键，修改了全局设置时整体写入
+    pub fn update_key_config(&mut self, key: cbor::Keyboard) -> Result<()> {
+        let supported = self.supports_partial(PacketID::SetKeyRTConfig);
+        let packets = self
+            .key_config
+            .filter(|current| supported && same_globals(current, &key))
+            .map(|current| {
+                current
+                    .KeyConfigs
+                    .iter()
+                    .zip(&key.KeyConfigs)
+                    .enumerate()
+                    .filter(|(_, (a, b))| a != b)
+                    .map(|(i, (_, b))| {
+                        let mut data = vec![i as u8];
+                        data.extend(b.to_cbor());
+                        Packet::new(PacketID::SetKeyRTConfig, data)
+                    })
+                    .collect::<Vec<_>>()
+            });
+        self.key_config = Some(key);
+        match packets {
+            Some(packets) => self.write_partial(packets, Self::set_key_config),
+            None => self.set_key_config(),
+        }
+    }
+
+    /// 只修改了按键颜色时逐个写入颜色，否则整体写入
+    pub fn update_light_config(&mut self, light: cbor::Light) -> Result<()> {
+        let supported = self.supports_partial(PacketID::SetLedColor);
+        let packets = self
+            .light_config
+            .filter(|current| supported && cbor::Light { led_colors: light.led_colors, ..*current } == light)
+            .map(|current| {
+                current
+                    .led_colors
+                    .iter()
+                    .zip(&light.led_colors)
+                    .enumerate()
+                    .filter(|(_, (a, b))| a != b)
+                    .map(|(i, (_, b))| {
+                        let mut data = vec![i as u8];
+                        data.extend(b.to_be_bytes());
+                        Packet::new(PacketID::SetLedColor, data)
+                    })
+                    .collect::<Vec<_>>()
+            });
+        self.light_config = Some(light);
+        match packets {
+            Some(packets) => self.write_partial(packets, Self::set_light_config),
+            None => self.set_light_config(),
+        }
+    }
+
+    /// 修改单个按键的行程设置与键值
+    pub fn set_key_rt_config(&mut self, index: usize, config: cbor::KeyRTConfig) -> Result<()> {
+        let mut key = self.key_config.ok_or(Error::EmptyConfig)?;
+        *key.KeyConfigs.get_mut(index).ok_or(Error::ConfigDataCheckFailed("keys", index))? = config;
+        self.update_key_config(key)
+    }
+
+    /// 只修改单个按键的键值
+    pub fn set_key_data(&mut self, index: usize, data: [u8; 6]) -> Result<()> {
+        let mut key = self.key_config.ok_or(Error::EmptyConfig)?;
+        key.KeyConfigs.get_mut(index).ok_or(Error::ConfigDataCheckFailed("keys", index))?.KeyData = data;
+        self.update_key_config(key)
+    }
+
+    pub fn set_led_color(&mut self, index: usize, color: u32) -> Result<()> {
+        let mut light = self.light_config.ok_or(Error::EmptyConfig)?;
+        *light.led_colors.get_mut(index).ok_or(Error::ConfigDataCheckFailed("led_colors", index))? = color;
+        self.update_light_config(light)
+    }
+
+    fn supports_partial(&self, packet_id: PacketID) -> bool {
+        self.descriptor.as_ref().is_some_and(|d| d.supports(packet_id))
+    }
+
+    /// 逐个发送局部写入的数据包，失败时改用 `fallback` 整体写入
+    fn write_partial(&self, packets: Vec<Packet>, fallback: fn(&Self) -> Result<()>) -> Result<()> {
+        debug!("局部写入 {} 项", packets.len());
+        for packet in packets {
+            self.write(packet)?;
+            let packet = self.read()?;
+            if packet.id != PacketID::Ok as u8 {
+                warn!("局部写入失败，改为整体写入：{}", packet.id);
+                return fallback(self);
+            }
+        }
+        Ok(())
+    }
+
     pub fn save_key_config(&mut self) -> Result<()> {
         self.write(Packet::new(PacketID::SaveKeyConfig, []))?;
         let packet = self.read()?;
         if packet.id == PacketID::Ok as u8 {
+            self.saved_key_config = self.key_config;
             Ok(())
         } else {
             dbg!(packet.id);
@@ -181,6 +353,7 @@ impl<D: Device> Meowpad<D> {
         self.write(Packet::new(PacketID::SaveLightConfig, []))?;
         let packet = self.read()?;
         if packet.id == PacketID::Ok as u8 {
+            self.saved_light_config = self.light_config;
             Ok(())
         } else {
             dbg!(packet.id);
@@ -189,10 +362,75 @@ impl<D: Device> Meowpad<D> {
         }
     }
 
+    /// 写入按键与灯光配置并读回比较，全部一致后才保存到闪存；
+    /// 失败时把写入前的配置写回内存，闪存中的配置不受影响。
+    /// 灯光配置保存失败时把之前的按键配置重新保存，仍然失败则返回 `PartialSave`
+    pub fn apply(&mut self, key: cbor::Keyboard, light: cbor::Light) -> Result<()> {
+        self.load_key_config()?;
+        self.load_light_config()?;
+        let previous = (self.key_config, self.light_config);
+        let saved_key = self.saved_key_config;
+        if let Err(e) = self.write_and_verify(key, light) {
+            warn!("写入配置失败，恢复写入前的配置：{}", e);
+            if let Err(e) = self.restore(previous) {
+                error!("恢复配置失败：{}", e);
+            }
+            return Err(e);
+        }
+        if let Err(e) = self.save_key_config() {
+            warn!("保存按键配置失败，恢复写入前的配置：{}", e);
+            if let Err(e) = self.restore(previous) {
+                error!("恢复配置失败：{}", e);
+            }
+            return Err(e);
+        }
+        if let Err(e) = self.save_light_config() {
+            warn!("保存灯光配置失败，恢复闪存中原来的按键配置：{}", e);
+            if let Err(e) = self.restore((saved_key, previous.1)).and_then(|_| self.save_key_config()) {
+                error!("恢复按键配置失败，闪存中只有按键配置是新的：{}", e);
+                return Err(Error::PartialSave("key_config"));
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn restore(&mut self, (key, light): (Option<cbor::Keyboard>, Option<cbor::Light>)) -> Result<()> {
+        (self.key_config, self.light_config) = (key, light);
+        self.set_key_config()?;
+        self.set_light_config()
+    }
+
+    fn write_and_verify(&mut self, key: cbor::Keyboard, light: cbor::Light) -> Result<()> {
+        self.key_config = Some(key);
+        self.set_key_config()?;
+        self.light_config = Some(light);
+        self.set_light_config()?;
+        self.load_key_config()?;
+        self.load_light_config()?;
+        if self.key_config != Some(key) {
+            return Err(Error::VerifyFailed("key_config"));
+        }
+        if self.light_config != Some(light) {
+            return Err(Error::VerifyFailed("light_config"));
+        }
+        Ok(())
+    }
+
+    /// 与闪存中的配置不同即视为未保存
+    pub fn unsaved(&self) -> Unsaved {
+        Unsaved {
+            key: self.key_config.is_some() && self.key_config != self.saved_key_config,
+            light: self.light_config.is_some() && self.light_config != self.saved_light_config,
+        }
+    }
+
     pub fn clear_key_config(&mut self) -> Result<()> {
         self.write(Packet::new(PacketID::ClearKeyConfig, []))?;
         let packet = self.read()?;
         if packet.id == PacketID::Ok as u8 {
+            self.saved_key_config = None;
+            self.key_config = None;
             Ok(())
         } else {
             dbg!(packet.id);
@@ -205,6 +443,8 @@ impl<D: Device> Meowpad<D> {
         self.write(Packet::new(PacketID::ClearLightConfig, []))?;
         let packet = self.read()?;
         if packet.id == PacketID::Ok as u8 {
+            self.saved_light_config = None;
+            self.light_config = None;
             Ok(())
         } else {
             dbg!(packet.id);
@@ -273,7 +513,21 @@ impl<D: Device> Meowpad<D> {
         }
     }
 
+    /// 设备返回过描述时，拒绝发送设备不支持的数据包
+    fn check_supported(&self, packet_id: u8) -> Result<()> {
+        match &self.descriptor {
+            Some(d) if packet_id >= PacketID::GetFirmwareVersion as u8
+                && packet_id != PacketID::GetDescriptor as u8
+                && !d.supports(packet_id) =>
+            {
+                Err(Error::UnsupportedPacket(packet_id))
+            }
+            _ => Ok(()),
+        }
+    }
+
     fn write(&self, packet: Packet) -> Result<()> {
+        self.check_supported(packet.id)?;
         debug!("发送：{:?}", packet);
         debug!("总数据大小：{}", packet.data.len());
         for v in packet.build_packets() {
@@ -0,0 +1,88 @@
+//! 配置结构版本与迁移
+//!
+//! 设备上的CBOR与本地保存的配置都可能来自旧版本，读取时先逐级升级到当前结构。
+//!
+//! | 版本 | 固件 | 变化 |
+//! | ---- | ---- | ---- |
+//! | 1 | <= 0.1.0 | 初始结构 |
+//! | 2 | 0.1.1 | 按键配置新增 `ehs`（高速模式） |
+
+use crate::config;
+use ciborium::Value;
+use meowpad::cbor::{insert_missing, map_get, migrate};
+use serde::{de::Error as _, Deserialize, Deserializer};
+
+pub const SCHEMA_VERSION: u32 = 2;
+
+const KEY_CBOR_MIGRATIONS: [fn(&mut Value); 1] = [key_cbor_v1_to_v2];
+const KEY_CONFIG_MIGRATIONS: [fn(&mut Value); 1] = [key_config_v1_to_v2];
+/// 灯光结构在版本2中没有变化
+const LIGHT_MIGRATIONS: [fn(&mut Value); 0] = [];
+
+/// 根据内容推断按键CBOR的结构版本
+pub fn detect_key_cbor_version(value: &Value) -> u32 {
+    match map_get(value, "ehs") {
+        None => 1,
+        Some(_) => SCHEMA_VERSION,
+    }
+}
+
+/// 根据内容推断本地按键配置的结构版本
+pub fn detect_key_config_version(value: &Value) -> u32 {
+    match map_get(value, "enable_hs") {
+        None => 1,
+        Some(_) => SCHEMA_VERSION,
+    }
+}
+
+/// 将设备按键CBOR升级到当前结构，`version` 为设备报告的版本（未知时推断），返回升级前的版本
+pub fn migrate_key_cbor(value: &mut Value, version: Option<u32>) -> u32 {
+    let from = version
+        .filter(|v| *v > 0)
+        .unwrap_or_else(|| detect_key_cbor_version(value));
+    migrate(value, from, &KEY_CBOR_MIGRATIONS);
+    from
+}
+
+/// 灯光结构目前没有变化，无法从内容推断版本
+pub fn migrate_light_cbor(value: &mut Value, version: Option<u32>) -> u32 {
+    let from = version.filter(|v| *v > 0).unwrap_or(SCHEMA_VERSION);
+    migrate(value, from, &LIGHT_MIGRATIONS);
+    from
+}
+
+/// 将本地按键配置升级到当前结构，`version` 为 0 时根据内容推断
+pub fn migrate_key_config(value: &mut Value, version: u32) -> u32 {
+    let from = match version {
+        0 => detect_key_config_version(value),
+        v => v,
+    };
+    migrate(value, from, &KEY_CONFIG_MIGRATIONS);
+    from
+}
+
+/// 读取任意版本的本地按键配置
+pub fn key_config_from<'de, D: Deserializer<'de>>(deserializer: D, version: u32) -> Result<config::Key, D::Error> {
+    let mut value = Value::deserialize(deserializer)?;
+    migrate_key_config(&mut value, version);
+    value
+        .deserialized()
+        .map_err(|ciborium::value::Error::Custom(e)| D::Error::custom(e))
+}
+
+/// 读取任意版本的本地灯光配置
+pub fn light_config_from<'de, D: Deserializer<'de>>(deserializer: D, version: u32) -> Result<config::Light, D::Error> {
+    let mut value = Value::deserialize(deserializer)?;
+    migrate(&mut value, version.max(1), &LIGHT_MIGRATIONS);
+    value
+        .deserialized()
+        .map_err(|ciborium::value::Error::Custom(e)| D::Error::custom(e))
+}
+
+fn key_cbor_v1_to_v2(value: &mut Value) {
+    insert_missing(value, "ehs", Value::Bool(true));
+}
+
+fn key_config_v1_to_v2(value: &mut Value) {
+    insert_missing(value, "enable_hs", Value::Bool(true));
+}
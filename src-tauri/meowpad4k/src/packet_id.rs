@@ -24,6 +24,7 @@ pub enum PacketID {
     ToggleKeyboard     = 14,
     Reset              = 15,
     GetStatus          = 16,
+    GetDescriptor      = 17,
 
     // 配置部分
     GetKeyConfig     = 100,
@@ -36,6 +37,10 @@ pub enum PacketID {
     ClearLightConfig = 107,
     GetHallConfig    = 108,
     ClearHallConfig  = 109,
+
+    // 局部写入，设备描述中列出时才使用
+    SetKeyRTConfig   = 110,
+    SetLedColor      = 111,
 }
 
 impl std::fmt::Display for PacketID {
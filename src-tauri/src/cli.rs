@@ -0,0 +1,72 @@
+use std::fs;
+
+use crate::{
+    cmd3k::{diff_raw_configs_3k, get_config_schema_3k},
+    cmd4k::{diff_raw_configs_4k, get_config_schema_4k},
+    cmdkbd::{diff_raw_configs_kb, get_config_schema_kb},
+    cmdpreset::get_preset_schema,
+};
+
+const USAGE: &str = "用法: meowpad_configurator diff <kb|4k|3k> <旧配置> <新配置>
+      meowpad_configurator schema <kb|4k|3k|preset>";
+
+/// 命令行模式，没有可识别的子命令时返回 `None`，按图形界面启动
+pub fn run(args: &[String]) -> Option<i32> {
+    match args.first().map(String::as_str) {
+        Some("diff") => Some(diff(&args[1..])),
+        Some("schema") => Some(schema(&args[1..])),
+        _ => None,
+    }
+}
+
+/// 输出两份配置文件的差异，有差异时返回1，出错时返回2
+fn diff(args: &[String]) -> i32 {
+    let [model, old, new] = args else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    let diff_raw = match model.as_str() {
+        "kb" => diff_raw_configs_kb,
+        "4k" => diff_raw_configs_4k,
+        "3k" => diff_raw_configs_3k,
+        _ => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+    let read = |path: &String| fs::read_to_string(path).map_err(|e| eprintln!("无法读取 {}：{}", path, e));
+    let (Ok(old_text), Ok(new_text)) = (read(old), read(new)) else {
+        return 2;
+    };
+    match diff_raw(&old_text, &new_text) {
+        Ok(diff) if diff.is_empty() => 0,
+        Ok(diff) => {
+            print!("{}", diff);
+            1
+        }
+        Err(e) => {
+            eprintln!("配置解析失败：{}", e);
+            2
+        }
+    }
+}
+
+/// 输出配置或预设的JSON Schema，供外部工具校验与生成类型
+fn schema(args: &[String]) -> i32 {
+    let [kind] = args else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    let schema = match kind.as_str() {
+        "kb" => get_config_schema_kb(),
+        "4k" => get_config_schema_4k(),
+        "3k" => get_config_schema_3k(),
+        "preset" => get_preset_schema(),
+        _ => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+    println!("{}", serde_json::to_string_pretty(&schema).unwrap());
+    0
+}
@@ -0,0 +1,44 @@
+use tauri::State;
+
+use crate::{
+    error::{Error, Result},
+    firmware::{FirmwareCatalog, FirmwareRelease},
+};
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct FirmwareReleaseInfo {
+    #[serde(flatten)]
+    pub release: FirmwareRelease,
+    /// 是否已有校验通过的本地缓存
+    pub cached: bool,
+}
+
+#[tauri::command]
+pub async fn get_firmware_releases(
+    catalog: State<'_, FirmwareCatalog>,
+    product: String,
+) -> Result<Vec<FirmwareReleaseInfo>> {
+    let manifest = catalog.manifest().await?;
+    Ok(manifest
+        .releases(&product)
+        .into_iter()
+        .map(|release| FirmwareReleaseInfo {
+            cached: catalog.is_cached(&release),
+            release,
+        })
+        .collect())
+}
+
+/// 下载（或从缓存读取）固件，返回的数据可以直接交给 `iap_start`
+#[tauri::command]
+pub async fn download_firmware(
+    catalog: State<'_, FirmwareCatalog>,
+    product: String,
+    version: String,
+) -> Result<Vec<u8>> {
+    let manifest = catalog.manifest().await?;
+    let release = manifest
+        .find(&product, &version)
+        .ok_or(Error::FirmwareNotFound(product, version))?;
+    catalog.image(release).await
+}
@@ -0,0 +1,33 @@
+use tauri::State;
+
+use crate::{
+    error::Result,
+    history::{History, Snapshot},
+};
+
+/// 设备的配置快照，从新到旧排列
+#[tauri::command]
+pub fn list_snapshots(history: State<'_, History>, serial: String) -> Result<Vec<Snapshot>> {
+    history.list(&serial)
+}
+
+/// 带标签的快照不会被自动清理，标签为空时取消
+#[tauri::command]
+pub fn label_snapshot(history: State<'_, History>, serial: String, id: u64, label: Option<String>) -> Result<()> {
+    history.set_label(&serial, id, label)
+}
+
+#[tauri::command]
+pub fn delete_snapshot(history: State<'_, History>, serial: String, id: u64) -> Result<()> {
+    history.remove(&serial, id)
+}
+
+#[tauri::command]
+pub fn get_history_limit(history: State<'_, History>) -> usize {
+    history.limit()
+}
+
+#[tauri::command]
+pub fn set_history_limit(history: State<'_, History>, limit: usize) -> Result<()> {
+    history.set_limit(limit)
+}
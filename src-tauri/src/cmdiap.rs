@@ -1,10 +1,134 @@
-use std::{sync::Mutex, thread, time::Duration};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
 
 use crate::{device::DeviceInfoExtened, error::{self, Error, Result}, MEOWPAD_DEVICE_NAME, PURE64_DEVICE_NAME};
 use hid_iap::iap::{IAPState, IAP};
 use hidapi::HidApi;
 use log::*;
-use tauri::{Manager, State};
+use tauri::{AppHandle, Manager, State};
+use uuid::Uuid;
+
+/// 两次进度事件之间的最小间隔
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IapPhase {
+    Programming,
+    Verifying,
+    Done,
+    Cancelled,
+    Failed,
+}
+
+/// `iap_process` 事件内容
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct IapProgress {
+    pub job_id: String,
+    pub phase: IapPhase,
+    pub bytes_done: usize,
+    pub total_bytes: usize,
+    /// 当前阶段的速度，字节/秒
+    pub throughput: f64,
+    /// 当前阶段预计剩余秒数
+    pub eta: Option<f64>,
+    pub error: Option<String>,
+}
+
+/// 后台运行的固件烧录任务
+pub struct IapJob {
+    pub id: String,
+    cancel: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl IapJob {
+    pub fn is_running(&self) -> bool {
+        !self.handle.is_finished()
+    }
+}
+
+struct ProgressReporter {
+    app: AppHandle,
+    job_id: String,
+    phase: Option<IapPhase>,
+    phase_start: Instant,
+    last_emit: Option<Instant>,
+    bytes_done: usize,
+    total_bytes: usize,
+}
+
+impl ProgressReporter {
+    fn new(app: AppHandle, job_id: String) -> Self {
+        Self {
+            app,
+            job_id,
+            phase: None,
+            phase_start: Instant::now(),
+            last_emit: None,
+            bytes_done: 0,
+            total_bytes: 0,
+        }
+    }
+
+    fn update(&mut self, phase: IapPhase, bytes_done: usize, total_bytes: usize) {
+        let now = Instant::now();
+        if self.phase != Some(phase) {
+            self.phase = Some(phase);
+            self.phase_start = now;
+            self.last_emit = None;
+        }
+        self.bytes_done = bytes_done;
+        self.total_bytes = total_bytes;
+
+        // 阶段切换或阶段完成时必定发送，其余情况限流
+        let due = match self.last_emit {
+            Some(t) => now.duration_since(t) >= PROGRESS_INTERVAL,
+            None => true,
+        };
+        if due || bytes_done >= total_bytes {
+            self.last_emit = Some(now);
+            self.emit(phase, None);
+        }
+    }
+
+    fn finish(&mut self, phase: IapPhase, error: Option<String>) {
+        self.emit(phase, error);
+    }
+
+    fn emit(&self, phase: IapPhase, error: Option<String>) {
+        let elapsed = self.phase_start.elapsed().as_secs_f64();
+        let throughput = if elapsed > 0.0 {
+            self.bytes_done as f64 / elapsed
+        } else {
+            0.0
+        };
+        let eta = match phase {
+            IapPhase::Programming | IapPhase::Verifying if throughput > 0.0 => {
+                Some(self.total_bytes.saturating_sub(self.bytes_done) as f64 / throughput)
+            }
+            _ => None,
+        };
+        let _ = self.app.emit_all(
+            "iap_process",
+            IapProgress {
+                job_id: self.job_id.clone(),
+                phase,
+                bytes_done: self.bytes_done,
+                total_bytes: self.total_bytes,
+                throughput,
+                eta,
+                error,
+            },
+        );
+    }
+}
 
 
 pub fn find_devices(api: &HidApi) -> Vec<DeviceInfoExtened> {
@@ -55,8 +179,18 @@ pub fn find_devices_pure(api: &HidApi) -> Vec<DeviceInfoExtened> {
 
 
 
+/// 烧录任务运行时不能替换设备或固件
+fn ensure_idle(job: &Option<IapJob>) -> Result<()> {
+    if job.as_ref().is_some_and(|j| j.is_running()) {
+        return Err(Error::IapBusy);
+    }
+    Ok(())
+}
+
 #[tauri::command]
-pub fn connect_iap(iap_device: State<'_, Mutex<Option<IAP>>>) -> Result<()> {
+pub fn connect_iap(iap_device: State<'_, Mutex<Option<IAP>>>, iap_job: State<'_, Mutex<Option<IapJob>>>) -> Result<()> {
+    let job = iap_job.lock().unwrap();
+    ensure_idle(&job)?;
     let api = HidApi::new().unwrap();
 
     match find_devices(&api)
@@ -87,7 +221,9 @@ fn check_firmware(data: &[u8]) -> bool {
     
 
 #[tauri::command]
-pub fn iap_start(iap_device: State<'_, Mutex<Option<IAP>>>, data: Vec<u8>) -> Result<usize> {
+pub fn iap_start(iap_device: State<'_, Mutex<Option<IAP>>>, iap_job: State<'_, Mutex<Option<IapJob>>>, data: Vec<u8>) -> Result<usize> {
+    let job = iap_job.lock().unwrap();
+    ensure_idle(&job)?;
     if !check_firmware(&data) {
         return Ok(0);
     }
@@ -97,22 +233,96 @@ pub fn iap_start(iap_device: State<'_, Mutex<Option<IAP>>>, data: Vec<u8>) -> Re
     Ok(len)
 }
 
+/// 在后台开始烧录，返回任务ID，进度通过 `iap_process` 事件通知
 #[tauri::command]
-pub fn iap_flush(app: tauri::AppHandle, iap_device: State<'_, Mutex<Option<IAP>>>) -> Result<()> {
-    let mut _iap = iap_device.lock().unwrap();
-    let iap = _iap.as_mut().ok_or(Error::DeviceDisconnected)?;
-    while iap.state == IAPState::Programming {
-        let pos = iap.program()?;
-        thread::sleep(Duration::from_millis(1));
-        app.emit_all("iap_process", &[pos, IAPState::Programming as u16])
-            .unwrap();
+pub fn iap_flush(
+    app: tauri::AppHandle,
+    iap_device: State<'_, Mutex<Option<IAP>>>,
+    iap_job: State<'_, Mutex<Option<IapJob>>>,
+) -> Result<String> {
+    let mut job = iap_job.lock().unwrap();
+    ensure_idle(&job)?;
+
+    {
+        let mut _iap = iap_device.lock().unwrap();
+        let iap = _iap.as_mut().ok_or(Error::DeviceDisconnected)?;
+        if iap.state == IAPState::Idle {
+            return Err(hid_iap::error::Error::NoFirmwareData.into());
+        }
     }
 
-    while iap.state == IAPState::Verifying {
-        let pos = iap.verify()?;
-        thread::sleep(Duration::from_millis(1));
-        app.emit_all("iap_process", &[pos, IAPState::Verifying as u16])
-            .unwrap();
+    let id = Uuid::new_v4().to_string();
+    let cancel = Arc::new(AtomicBool::new(false));
+    let handle = thread::spawn({
+        let id = id.clone();
+        let cancel = cancel.clone();
+        move || run_iap_job(app, id, cancel)
+    });
+
+    info!("固件烧录任务开始：{}", id);
+    job.replace(IapJob {
+        id: id.clone(),
+        cancel,
+        handle,
+    });
+    Ok(id)
+}
+
+/// 请求取消烧录任务，会在下一个数据包之前停止
+#[tauri::command]
+pub fn iap_cancel(iap_job: State<'_, Mutex<Option<IapJob>>>, job_id: String) -> bool {
+    let job = iap_job.lock().unwrap();
+    match job.as_ref() {
+        Some(j) if j.id == job_id && j.is_running() => {
+            j.cancel.store(true, Ordering::Relaxed);
+            true
+        }
+        _ => false,
+    }
+}
+
+fn run_iap_job(app: AppHandle, job_id: String, cancel: Arc<AtomicBool>) {
+    let mut reporter = ProgressReporter::new(app.clone(), job_id.clone());
+    let iap_device = app.state::<Mutex<Option<IAP>>>();
+
+    let result = (|| -> Result<IapPhase> {
+        loop {
+            // 每个数据包只短暂持有锁
+            let mut _iap = iap_device.lock().unwrap();
+            let iap = _iap.as_mut().ok_or(Error::DeviceDisconnected)?;
+
+            if cancel.load(Ordering::Relaxed) {
+                iap.abort();
+                return Ok(IapPhase::Cancelled);
+            }
+
+            let pos = match iap.state {
+                IAPState::Programming => iap.program()?,
+                IAPState::Verifying => iap.verify()?,
+                IAPState::Idle => return Ok(IapPhase::Done),
+            };
+            // 最后一个数据包之后状态已切换，按切换后的阶段报告进度
+            let phase = match iap.state {
+                IAPState::Programming => IapPhase::Programming,
+                IAPState::Verifying => IapPhase::Verifying,
+                IAPState::Idle => continue,
+            };
+            let total = iap.firmware_len();
+            drop(_iap);
+
+            reporter.update(phase, pos as usize, total);
+            thread::sleep(Duration::from_millis(1));
+        }
+    })();
+
+    match result {
+        Ok(phase) => {
+            info!("固件烧录任务结束：{} {:?}", job_id, phase);
+            reporter.finish(phase, None);
+        }
+        Err(e) => {
+            warn!("固件烧录任务失败：{} {}", job_id, e);
+            reporter.finish(IapPhase::Failed, Some(e.to_string()));
+        }
     }
-    Ok(())
 }
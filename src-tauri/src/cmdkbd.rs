@@ -1,17 +1,64 @@
 
 use std::sync::Mutex;
 use hidapi::{DeviceInfo, HidApi};
-use meowpad::{models::{DeviceStatus, KeyHallConfig, KeyRTStatus, KeyState}, Device};
+use meowpad::{models::{DeviceStatus, Unsaved, KeyHallConfig, KeyRTStatus, KeyState}, Device};
 use meowboard::Meowboard;
 use tauri::State;
-use crate::{device::{DeviceInfoExtened, HidDevice}, error::{Error, Result}, FIRMWARE_VERSION_KB};
+use crate::{device::{DeviceInfoExtened, HidDevice}, error::{Error, Result}, utils::{check_diagnostics, emit_decode_report, emit_unsaved, emit_unsaved_lost}, FIRMWARE_VERSION_KB, PURE64_DEVICE_NAME};
 use log::*;
+use crate::rawconfig::{Format, ParseError, RawConfigCheck};
+use meowpad::diff::ConfigDiff;
+use crate::history::{History, Snapshot, SnapshotReason};
+use crate::{cmdpreset::load_preset_kb, device_preset::DevicePreset, preset_broadcast::{self, BroadcastFilter, BroadcastResult}};
 
-#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Copy)]
+#[derive(serde::Serialize, serde::Deserialize, schemars::JsonSchema, Clone, Debug, Copy)]
 struct Config {
+    #[serde(default)]
+    version: u32,
     key: meowboard::config::Device,
 }
 
+/// 保存在本地的配置，可能来自旧版本
+#[derive(serde::Deserialize)]
+struct RawConfig {
+    #[serde(default)]
+    version: u32,
+    key: serde_json::Value,
+}
+
+impl Config {
+    /// 支持TOML、JSON与YAML，旧版本的配置会被迁移
+    fn parse(config: &str) -> std::result::Result<Self, ParseError> {
+        let format = Format::detect(config);
+        let raw: RawConfig = format.parse(config)?;
+        Ok(Self {
+            version: meowboard::migrate::SCHEMA_VERSION,
+            key: meowboard::migrate::config_from(raw.key, raw.version)
+                .map_err(|e| format.locate::<Self>(config, e))?,
+        })
+    }
+
+    fn diff(&self, new: &Self) -> ConfigDiff {
+        self.key.diff(&new.key)
+    }
+
+    /// 读取设备内存中的配置，`saved` 时返回闪存中的配置
+    fn from_device(d: &mut Meowboard<HidDevice>, saved: bool) -> Result<Self> {
+        d.load_key_config()?;
+        Ok(Self::cached(d, saved)?.unwrap())
+    }
+
+    /// 已经读取过的配置，不访问设备，尚未读取时为空
+    fn cached(d: &Meowboard<HidDevice>, saved: bool) -> Result<Option<Self>> {
+        let key = if saved { d.saved_key_config } else { d.key_config };
+        let Some(key) = key else { return Ok(None) };
+        Ok(Some(Self {
+            version: meowboard::migrate::SCHEMA_VERSION,
+            key: key.try_into()?,
+        }))
+    }
+}
+
 #[tauri::command]
 pub fn get_firmware_kb_version(_app: tauri::AppHandle) -> &'static str {
     FIRMWARE_VERSION_KB
@@ -23,16 +70,34 @@ pub fn get_device_info_kb(device_handle: State<'_, Mutex<Option<Meowboard<HidDev
     let d = _d.as_mut().ok_or(Error::DeviceDisconnected)?;
     d.get_device_name()?;
     d.get_firmware_version()?;
+    // 查询失败时与不支持描述的旧固件相同，不影响连接
+    let descriptor = d.get_descriptor().unwrap_or_else(|e| {
+        warn!("无法读取设备描述：{}", e);
+        None
+    });
     let name = d.device_name.as_ref().expect("参数错误");
     let version = d.firmware_version.as_ref().expect("参数错误");
     info!("设备名称：{}", name);
     info!("固件版本：{}", version);
+    debug!("设备描述：{:?}", descriptor);
     Ok(serde_json::json!({
         "name": name,
-        "version": version
+        "version": version,
+        "serial": d.device.get_serial()?,
+        "descriptor": descriptor
     }))
 }
 
+#[tauri::command]
+pub fn get_capabilities_kb(device_handle: State<'_, Mutex<Option<Meowboard<HidDevice>>>>) -> Result<meowboard::capability::Capabilities> {
+    let mut _d = device_handle.lock().unwrap();
+    let d = _d.as_mut().ok_or(Error::DeviceDisconnected)?;
+    if d.firmware_version.is_none() {
+        d.get_firmware_version()?;
+    }
+    Ok(d.capabilities)
+}
+
 #[tauri::command]
 pub fn get_device_status_kb(device_handle: State<'_, Mutex<Option<Meowboard<HidDevice>>>>) -> Result<DeviceStatus> {
     let mut _d = device_handle.lock().unwrap();
@@ -54,11 +119,13 @@ pub fn calibration_key_kb(device_handle: State<'_, Mutex<Option<Meowboard<HidDev
 }
 
 #[tauri::command]
-pub fn clear_config_kb(device_handle: State<'_, Mutex<Option<Meowboard<HidDevice>>>>) -> Result<()> {
+pub fn clear_config_kb(app: tauri::AppHandle, device_handle: State<'_, Mutex<Option<Meowboard<HidDevice>>>>, history: State<'_, History>) -> Result<()> {
     let mut _d = device_handle.lock().unwrap();
     let d = _d.as_mut().ok_or(Error::DeviceDisconnected)?;
+    backup(d, &history, SnapshotReason::Clear);
     d.clear_hall_config()?;
     d.clear_key_config()?;
+    emit_unsaved(&app, "kb", d.unsaved());
     Ok(())
 }
 
@@ -113,9 +180,10 @@ pub async fn get_key_calibrate_status_kb(device_handle: State<'_, Mutex<Option<M
 }
 
 #[tauri::command]
-pub fn erase_firmware_kb(device_handle: State<'_, Mutex<Option<Meowboard<HidDevice>>>>) -> Result<()> {
+pub fn erase_firmware_kb(device_handle: State<'_, Mutex<Option<Meowboard<HidDevice>>>>, history: State<'_, History>) -> Result<()> {
     let mut _d = device_handle.lock().unwrap();
     let d = _d.as_mut().ok_or(Error::DeviceDisconnected)?;
+    backup(d, &history, SnapshotReason::Firmware);
     d.erase_firmware()?;
     Ok(())
 }
@@ -126,28 +194,33 @@ pub fn get_default_key_config_kb() -> meowboard::config::Device {
 }
 
 #[tauri::command]
-pub async fn get_key_config_kb(device_handle: State<'_, Mutex<Option<Meowboard<HidDevice>>>>) -> Result<meowboard::config::Device> {
+pub async fn get_key_config_kb(app: tauri::AppHandle, device_handle: State<'_, Mutex<Option<Meowboard<HidDevice>>>>) -> Result<meowboard::config::Device> {
     let mut _d = device_handle.lock().unwrap();
     let d = _d.as_mut().ok_or(Error::DeviceDisconnected)?;
-    d.load_key_config()?;
+    emit_decode_report(&app, "kb", "key", d.load_key_config()?);
     Ok(d.key_config.unwrap().try_into()?)
 }
 
 #[tauri::command]
-pub fn set_key_config_kb(device_handle: State<'_, Mutex<Option<Meowboard<HidDevice>>>>, config: meowboard::config::Device) -> Result<()> {
+pub fn set_key_config_kb(app: tauri::AppHandle, device_handle: State<'_, Mutex<Option<Meowboard<HidDevice>>>>, history: State<'_, History>, config: meowboard::config::Device) -> Result<()> {
     let mut _d = device_handle.lock().unwrap();
     let d = _d.as_mut().ok_or(Error::DeviceDisconnected)?;
-    d.key_config = Some(config.into());
-    d.set_key_config()?;
+    check_diagnostics(config.validate())?;
+    d.capabilities.check_key(&config)?;
+    backup(d, &history, SnapshotReason::Write);
+    d.update_key_config(config.into())?;
+    emit_unsaved(&app, "kb", d.unsaved());
     Ok(())
 }
 
 
 #[tauri::command]
-pub fn save_key_config_kb(device_handle: State<'_, Mutex<Option<Meowboard<HidDevice>>>>) -> Result<()> {
+pub fn save_key_config_kb(app: tauri::AppHandle, device_handle: State<'_, Mutex<Option<Meowboard<HidDevice>>>>, history: State<'_, History>) -> Result<()> {
     let mut _d = device_handle.lock().unwrap();
     let d = _d.as_mut().ok_or(Error::DeviceDisconnected)?;
+    backup(d, &history, SnapshotReason::Save);
     d.save_key_config()?;
+    emit_unsaved(&app, "kb", d.unsaved());
     Ok(())
 }
 
@@ -156,39 +229,209 @@ pub fn save_key_config_kb(device_handle: State<'_, Mutex<Option<Meowboard<HidDev
 pub fn get_raw_config_kb(device_handle: State<'_, Mutex<Option<Meowboard<HidDevice>>>>) -> Result<String> {
     let mut _d = device_handle.lock().unwrap();
     let d = _d.as_mut().ok_or(Error::DeviceDisconnected)?;
+    Ok(toml::to_string(&Config::from_device(d, false)?).unwrap())
+}
+
+/// 原始配置的JSON Schema，包含各项数值的范围
+#[tauri::command]
+pub fn get_config_schema_kb() -> serde_json::Value {
+    serde_json::to_value(schemars::schema_for!(Config)).unwrap()
+}
+
+#[tauri::command]
+pub fn check_raw_config_kb(config: String) -> RawConfigCheck {
+    RawConfigCheck::new(&config, Config::parse(&config).map(|cfg| cfg.key.validate()))
+        .check_schema::<Config>(&config, meowboard::migrate::SCHEMA_VERSION)
+}
+
+#[tauri::command]
+pub fn save_raw_config_kb(app: tauri::AppHandle, device_handle: State<'_, Mutex<Option<Meowboard<HidDevice>>>>, history: State<'_, History>, config: String) -> Result<()> {
+    let mut _d = device_handle.lock().unwrap();
+    let d = _d.as_mut().ok_or(Error::DeviceDisconnected)?;
+    let cfg = Config::parse(&config).map_err(Error::ConfigParse)?;
+    check_diagnostics(cfg.key.validate())?;
+    d.capabilities.check_key(&cfg.key)?;
+    backup(d, &history, SnapshotReason::Write);
+    d.apply(cfg.key.into())?;
+    emit_unsaved(&app, "kb", d.unsaved());
+    Ok(())
+}
+
+/// 写入并读回校验，一致后才保存到闪存
+#[tauri::command]
+pub fn apply_config_kb(app: tauri::AppHandle, device_handle: State<'_, Mutex<Option<Meowboard<HidDevice>>>>, history: State<'_, History>, key: meowboard::config::Device) -> Result<()> {
+    let mut _d = device_handle.lock().unwrap();
+    let d = _d.as_mut().ok_or(Error::DeviceDisconnected)?;
+    check_diagnostics(key.validate())?;
+    d.capabilities.check_key(&key)?;
+    backup(d, &history, SnapshotReason::Write);
+    d.apply(key.into())?;
+    emit_unsaved(&app, "kb", d.unsaved());
+    Ok(())
+}
+
+/// 读取设备当前的配置并应用预设，写入校验后保存
+fn write_preset(d: &mut Meowboard<HidDevice>, history: &History, preset: &DevicePreset) -> Result<()> {
+    let _ = d.device.clear_buffer();
+    d.get_firmware_version()?;
+    d.get_descriptor()?;
     d.load_key_config()?;
-    Ok(toml::to_string(&Config {
-        key: d.key_config.unwrap().try_into()?,
-    })
-    .unwrap())
+    let key = load_preset_kb(d.key_config.unwrap().try_into()?, preset.clone(), None)?;
+    check_diagnostics(key.validate())?;
+    d.capabilities.check_key(&key)?;
+    backup(d, history, SnapshotReason::Write);
+    Ok(d.apply(key.into())?)
+}
+
+/// 把预设写入设备内存，不保存也不记录快照，用于自动切换预设
+pub fn write_preset_live(app: &tauri::AppHandle, d: &mut Meowboard<HidDevice>, preset: &DevicePreset) -> Result<()> {
+    d.load_key_config()?;
+    let key = load_preset_kb(d.key_config.unwrap().try_into()?, preset.clone(), None)?;
+    check_diagnostics(key.validate())?;
+    d.capabilities.check_key(&key)?;
+    d.update_key_config(key.into())?;
+    emit_unsaved(app, "kb", d.unsaved());
+    Ok(())
+}
+
+/// 把预设写入所有匹配 `filter` 的Pure64，已连接的设备使用现有的连接
+pub fn broadcast_preset_kb(
+    app: &tauri::AppHandle,
+    api: &HidApi,
+    connected: &mut Option<Meowboard<HidDevice>>,
+    history: &History,
+    preset: &DevicePreset,
+    filter: &BroadcastFilter,
+) -> Vec<BroadcastResult> {
+    let devices = find_devices(api).into_iter().map(|d| (PURE64_DEVICE_NAME.to_owned(), d.serial_number, d.inner));
+    let results = preset_broadcast::broadcast(filter, devices, |serial, info| {
+        match connected.as_mut().filter(|d| d.device.get_serial().ok().as_deref() == serial) {
+            Some(d) => write_preset(d, history, preset),
+            None => {
+                let device = info.open_device(api).map_err(|_| Error::DeviceNotFound)?;
+                write_preset(&mut Meowboard::new(HidDevice { device }), history, preset)
+            }
+        }
+    });
+    // 其他连接的读写也会发送到已连接的设备
+    if let Some(d) = connected.as_mut() {
+        let _ = d.device.clear_buffer();
+        emit_unsaved(app, "kb", d.unsaved());
+    }
+    results
+}
+
+/// 已写入设备内存但尚未保存的配置
+#[tauri::command]
+pub fn get_unsaved_kb(device_handle: State<'_, Mutex<Option<Meowboard<HidDevice>>>>) -> Result<Unsaved> {
+    let mut _d = device_handle.lock().unwrap();
+    let d = _d.as_mut().ok_or(Error::DeviceDisconnected)?;
+    Ok(d.unsaved())
 }
 
+/// 比较两份按键配置，例如预设与当前配置
 #[tauri::command]
-pub fn check_raw_config_kb(config: String) -> bool {
-    toml::from_str::<Config>(&config).is_ok()
+pub fn diff_key_config_kb(old: meowboard::config::Device, new: meowboard::config::Device) -> ConfigDiff {
+    old.diff(&new)
 }
 
+/// 设备当前配置与原始配置文本的差异
 #[tauri::command]
-pub fn save_raw_config_kb(device_handle: State<'_, Mutex<Option<Meowboard<HidDevice>>>>, config: String) -> Result<()> {
+pub fn diff_raw_config_kb(device_handle: State<'_, Mutex<Option<Meowboard<HidDevice>>>>, config: String) -> Result<ConfigDiff> {
     let mut _d = device_handle.lock().unwrap();
     let d = _d.as_mut().ok_or(Error::DeviceDisconnected)?;
-    let cfg = toml::from_str::<Config>(&config).expect("错误配置");
-    d.key_config = Some(cfg.key.into());
-    d.set_key_config()?;
-    d.save_key_config()?;
+    let cfg = Config::parse(&config).map_err(Error::ConfigParse)?;
+    Ok(Config::from_device(d, false)?.diff(&cfg))
+}
+
+/// 已写入但尚未保存的改动，即闪存与内存中配置的差异
+#[tauri::command]
+pub fn diff_unsaved_config_kb(device_handle: State<'_, Mutex<Option<Meowboard<HidDevice>>>>) -> Result<ConfigDiff> {
+    let mut _d = device_handle.lock().unwrap();
+    let d = _d.as_mut().ok_or(Error::DeviceDisconnected)?;
+    let current = Config::from_device(d, false)?;
+    Ok(Config::from_device(d, true)?.diff(&current))
+}
+
+/// 比较两份原始配置文本，供命令行使用
+pub fn diff_raw_configs_kb(old: &str, new: &str) -> std::result::Result<ConfigDiff, ParseError> {
+    Ok(Config::parse(old)?.diff(&Config::parse(new)?))
+}
+
+/// 保存快照，写入与保存前记录闪存中的配置，已经读取过时不再访问设备，其余记录内存中的配置；
+/// 霍尔校准只会被清除或固件更新改变，其余时候不读取
+fn snapshot(d: &mut Meowboard<HidDevice>, history: &History, reason: SnapshotReason) -> Result<Snapshot> {
+    let config = match reason {
+        SnapshotReason::Write | SnapshotReason::Save => match Config::cached(d, true)? {
+            Some(config) => config,
+            None => Config::from_device(d, true)?,
+        },
+        _ => Config::from_device(d, false)?,
+    };
+    let hall = match reason {
+        SnapshotReason::Clear | SnapshotReason::Firmware | SnapshotReason::Manual => d.get_hall_config()?.to_vec(),
+        _ => vec![],
+    };
+    let serial = d.device.get_serial()?;
+    history.push(&serial, "kb", reason, serde_json::to_value(config)?, hall)
+}
+
+/// 写入前的自动快照，失败时不影响写入
+fn backup(d: &mut Meowboard<HidDevice>, history: &History, reason: SnapshotReason) {
+    if let Err(e) = snapshot(d, history, reason) {
+        warn!("保存配置快照失败：{}", e);
+    }
+}
+
+#[tauri::command]
+pub fn create_snapshot_kb(device_handle: State<'_, Mutex<Option<Meowboard<HidDevice>>>>, history: State<'_, History>, label: Option<String>) -> Result<Snapshot> {
+    let mut _d = device_handle.lock().unwrap();
+    let d = _d.as_mut().ok_or(Error::DeviceDisconnected)?;
+    let mut snapshot = snapshot(d, &history, SnapshotReason::Manual)?;
+    if label.is_some() {
+        history.set_label(&d.device.get_serial()?, snapshot.id, label.clone())?;
+        snapshot.label = label;
+    }
+    Ok(snapshot)
+}
+
+/// 恢复快照并保存到闪存，霍尔校准数据无法写回
+#[tauri::command]
+pub fn restore_snapshot_kb(app: tauri::AppHandle, device_handle: State<'_, Mutex<Option<Meowboard<HidDevice>>>>, history: State<'_, History>, id: u64) -> Result<()> {
+    let mut _d = device_handle.lock().unwrap();
+    let d = _d.as_mut().ok_or(Error::DeviceDisconnected)?;
+    let serial = d.device.get_serial()?;
+    let snapshot = history.get(&serial, id)?;
+    if snapshot.model != "kb" {
+        return Err(Error::SnapshotModelMismatch(snapshot.model));
+    }
+    let cfg = Config::parse(&snapshot.config.to_string()).map_err(Error::ConfigParse)?;
+    check_diagnostics(cfg.key.validate())?;
+    d.capabilities.check_key(&cfg.key)?;
+    backup(d, &history, SnapshotReason::Restore);
+    d.apply(cfg.key.into())?;
+    emit_unsaved(&app, "kb", d.unsaved());
     Ok(())
 }
 
 
 #[tauri::command]
-pub fn connect_kb(device_handle: State<'_, Mutex<Option<Meowboard<HidDevice>>>>) -> bool {
+pub fn connect_kb(app: tauri::AppHandle, device_handle: State<'_, Mutex<Option<Meowboard<HidDevice>>>>) -> bool {
     let mut _d = device_handle.lock().unwrap();
     info!("开始连接!");
     let found_device = find_device();
 
     match found_device {
-        Some(device) => {
+        Some(mut device) => {
             info!("连接到设备");
+            if let Some(unsaved) = _d.as_ref().map(|d| d.unsaved()).filter(|u| u.any()) {
+                warn!("断开的设备有未保存的配置");
+                emit_unsaved_lost(&app, "kb", unsaved);
+            }
+            if let Err(e) = load_baseline(&mut device) {
+                warn!("读取设备配置失败：{}", e);
+            }
+            emit_unsaved(&app, "kb", device.unsaved());
             _d.replace(device);
             true
         }
@@ -200,6 +443,14 @@ pub fn connect_kb(device_handle: State<'_, Mutex<Option<Meowboard<HidDevice>>>>)
 }
 
 
+/// 连接后立即读取配置，作为判断是否有未保存更改的基准
+fn load_baseline(d: &mut Meowboard<HidDevice>) -> Result<()> {
+    d.get_firmware_version()?;
+    d.get_descriptor()?;
+    d.load_key_config()?;
+    Ok(())
+}
+
 fn find_device() -> Option<Meowboard<HidDevice>> {
     // 获取设备列表
     let api = HidApi::new().unwrap();
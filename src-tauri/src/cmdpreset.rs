@@ -1,12 +1,28 @@
-use std::sync::Mutex;
+use std::{collections::BTreeMap, sync::Mutex};
 
 use hidapi::HidApi;
-use tauri::{api::dialog::blocking::FileDialogBuilder, State};
-use meowpad::Device;
+use tauri::{api::dialog::blocking::FileDialogBuilder, Manager, State};
+use meowpad::{validate::Diagnostic, Device};
 use meowpad3k::Meowpad as Meowpad3k;
 use meowpad4k::Meowpad as Meowpad4k;
 use meowboard::Meowboard;
-use crate::{device::HidDevice, device_preset::DevicePreset};
+use crate::{
+    cmd3k::{self, get_key_config_3k, get_light_config_3k, save_key_config_3k, save_light_config_3k},
+    cmd4k::{self, get_key_config_4k, get_light_config_4k, save_key_config_4k, save_light_config_4k},
+    cmdkbd::{self, save_key_config_kb},
+    device::HidDevice,
+    device_preset::{DevicePreset, PresetLight, PresetMeta},
+    error::{Error, Result},
+    history::History,
+    preset_broadcast::{BroadcastFilter, BroadcastResult},
+    preset_code,
+    preset_import::{self, ImportWarning, DEFAULT_TRAVEL},
+    preset_library::{ImportResult, PresetFilter, PresetLibrary},
+    preset_merge::{self, MergeResult, PresetSelection, Side},
+    preset_translate::{self, KeyMapping, Translation},
+    preset_validate::{self, PresetReport},
+    MEOWPAD_DEVICE_NAME, MEOWPAD_SE_DEVICE_NAME, PURE64_DEVICE_NAME,
+};
 
 // #[tauri::command]
 // fn load_preset(
@@ -22,16 +38,43 @@ use crate::{device::HidDevice, device_preset::DevicePreset};
 
 // }
 
+/// 有问题时拒绝使用预设，超出推荐范围只记录日志
+fn ensure_valid(report: PresetReport) -> Result<()> {
+    if !report.is_valid() {
+        return Err(Error::InvalidPreset(report));
+    }
+    for d in report.diagnostics {
+        log::warn!("预设 {} = {} 超出推荐范围 {}-{}", d.path, d.value, d.min, d.max);
+    }
+    Ok(())
+}
+
+/// 读取并检查 `.pcf` 文件
+fn read_preset_file(path: std::path::PathBuf) -> Result<DevicePreset> {
+    let (preset, report) = preset_validate::parse(&std::fs::read_to_string(path)?);
+    ensure_valid(report)?;
+    Ok(preset.expect("没有问题的预设已解析"))
+}
+
+/// 取消选择时返回 `None`，预设有问题时返回 `InvalidPreset` 与检查报告
+#[tauri::command]
+pub async fn load_preset_from_file() -> Result<Option<DevicePreset>> {
+    let Some(file_path) = FileDialogBuilder::new().add_filter("desu.life Config File", &["pcf"]).pick_file() else {
+        return Ok(None);
+    };
+    read_preset_file(file_path).map(Some)
+}
+
+/// 检查预设能否应用到对应型号的设备
 #[tauri::command]
-pub async fn load_preset_from_file() -> Option<DevicePreset> {
-    let file_path = FileDialogBuilder::new().add_filter("desu.life Config File", &["pcf"]).pick_file();
+pub fn check_preset(preset: DevicePreset) -> PresetReport {
+    preset_validate::check(&preset)
+}
 
-    if let Some(file_path) = file_path {
-        let content = std::fs::read_to_string(file_path).ok()?;
-        Some(serde_json::from_str(&content).ok()?)
-    } else {
-        None
-    }
+/// `.pcf` 预设格式的JSON Schema
+#[tauri::command]
+pub fn get_preset_schema() -> serde_json::Value {
+    serde_json::to_value(schemars::schema_for!(DevicePreset)).unwrap()
 }
 
 #[tauri::command]
@@ -45,34 +88,390 @@ pub async fn save_preset_to_file(preset: DevicePreset) {
     }
 }
 
+/// 只应用 `selection` 选择的部分，没有选择时全部应用，预设有问题时不应用其中任何部分
 #[tauri::command]
-pub fn load_preset_kb(mut config: meowboard::config::Device, preset: DevicePreset) -> meowboard::config::Device {
-    if let Some(d) = preset.config.key_configs {
-        if d.len() == 64 {
-            for (i, &k) in d.iter().enumerate() {
-                config.keys[i] = k.into();
-            }
+pub fn load_preset_kb(
+    mut config: meowboard::config::Device,
+    preset: DevicePreset,
+    selection: Option<PresetSelection>,
+) -> Result<meowboard::config::Device> {
+    ensure_valid(preset_validate::check_for(&preset, PURE64_DEVICE_NAME))?;
+    let selection = selection.unwrap_or_default();
+    if let Some(d) = preset.config.key_configs.filter(|_| selection.key_configs) {
+        for (i, &k) in d.iter().enumerate().filter(|(i, _)| selection.key(*i)) {
+            config.keys[i] = k.try_into().map_err(|mut d: Diagnostic| {
+                d.path = format!("config.key_configs[{}].{}", i, d.path);
+                Error::InvalidConfig(vec![d])
+            })?;
         }
     }
 
     if let Some(d) = preset.config.key_layers {
-        for (i, l) in d.iter().enumerate() {
-            if l.keys.len() == 64 {
-                for (j, &k) in l.keys.iter().enumerate() {
-                    if i == 0 {
-                        config.normal_layer[j] = k;
-                    } else if i == 1 {
-                        config.fn_layer[j] = k;
-                    }
-                }
+        for (layer, keys) in [&mut config.normal_layer, &mut config.fn_layer].into_iter().enumerate() {
+            let Some(l) = d.get(layer).filter(|_| selection.layer(layer)) else { continue };
+            for (i, &k) in l.keys.iter().enumerate().filter(|(i, _)| selection.key(*i)) {
+                keys[i] = k;
             }
         }
     }
 
-    config
+    Ok(config)
 }
 
 #[tauri::command]
 pub fn gen_preset_kb(name: &str, config: meowboard::config::Device) -> DevicePreset {
-    DevicePreset::new(name, "Pure64", config)
+    DevicePreset::new(name, PURE64_DEVICE_NAME, config)
+}
+
+/// Meowpad的按键与灯光配置
+#[derive(serde::Serialize)]
+pub struct MeowpadConfig<K, L> {
+    pub key: K,
+    pub light: L,
+}
+
+/// 预设中没有的部分与 `selection` 没有选择的部分保持不变，预设有问题时不应用其中任何部分
+#[tauri::command]
+pub fn load_preset_4k(
+    mut key: meowpad4k::config::Key,
+    mut light: meowpad4k::config::Light,
+    preset: DevicePreset,
+    selection: Option<PresetSelection>,
+) -> Result<MeowpadConfig<meowpad4k::config::Key, meowpad4k::config::Light>> {
+    ensure_valid(preset_validate::check_for(&preset, MEOWPAD_DEVICE_NAME))?;
+    let selection = selection.unwrap_or_default();
+    let config = preset.config;
+    if let Some(d) = config.key_configs.filter(|_| selection.key_configs) {
+        for (_, (k, p)) in key.keys.iter_mut().zip(d).enumerate().filter(|(i, _)| selection.key(*i)) {
+            k.press_percentage = p.press_percentage as u8;
+            k.release_percentage = p.release_percentage as u8;
+            k.dead_zone = p.dead_zone as u8;
+        }
+    }
+    if let Some(d) = config.key_data.filter(|_| selection.key_data) {
+        for (_, (k, data)) in key.keys.iter_mut().zip(d).enumerate().filter(|(i, _)| selection.key(*i)) {
+            k.key_data = data;
+        }
+    }
+    if let Some(s) = config.key_settings.filter(|_| selection.key_settings) {
+        key.jitters_elimination_time = s.jitters_elimination_time;
+        key.continuous_report = s.continuous_report;
+        key.kalman_filter = s.kalman_filter;
+        key.enable_hs = s.enable_hs.unwrap_or(key.enable_hs);
+    }
+    if let Some(PresetLight::Meowpad4k(l)) = config.light.filter(|_| selection.light) {
+        light = l;
+    }
+    Ok(MeowpadConfig { key, light })
+}
+
+#[tauri::command]
+pub fn gen_preset_4k(name: &str, key: meowpad4k::config::Key, light: meowpad4k::config::Light) -> DevicePreset {
+    DevicePreset::new(name, MEOWPAD_DEVICE_NAME, (key, light))
+}
+
+/// 预设中没有的部分与 `selection` 没有选择的部分保持不变，预设有问题时不应用其中任何部分
+#[tauri::command]
+pub fn load_preset_3k(
+    mut key: meowpad3k::config::Key,
+    mut light: meowpad3k::config::Light,
+    preset: DevicePreset,
+    selection: Option<PresetSelection>,
+) -> Result<MeowpadConfig<meowpad3k::config::Key, meowpad3k::config::Light>> {
+    ensure_valid(preset_validate::check_for(&preset, MEOWPAD_SE_DEVICE_NAME))?;
+    let selection = selection.unwrap_or_default();
+    let config = preset.config;
+    if let Some(d) = config.key_configs.filter(|_| selection.key_configs) {
+        for (_, (k, p)) in key.keys.iter_mut().zip(d).enumerate().filter(|(i, _)| selection.key(*i)) {
+            k.press_percentage = p.press_percentage as u8;
+            k.release_percentage = p.release_percentage as u8;
+            k.dead_zone = p.dead_zone as u8;
+        }
+    }
+    if let Some(d) = config.key_data.filter(|_| selection.key_data) {
+        for (_, (k, data)) in key.keys.iter_mut().zip(d).enumerate().filter(|(i, _)| selection.key(*i)) {
+            k.key_data = data;
+        }
+    }
+    if let Some(side_btn) = config.side_btn.filter(|_| selection.key_data) {
+        key.side_btn = side_btn;
+    }
+    if let Some(s) = config.key_settings.filter(|_| selection.key_settings) {
+        key.jitters_elimination_time = s.jitters_elimination_time;
+        key.continuous_report = s.continuous_report;
+        key.kalman_filter = s.kalman_filter;
+    }
+    if let Some(PresetLight::Meowpad3k(l)) = config.light.filter(|_| selection.light) {
+        light = l;
+    }
+    Ok(MeowpadConfig { key, light })
+}
+
+#[tauri::command]
+pub fn gen_preset_3k(name: &str, key: meowpad3k::config::Key, light: meowpad3k::config::Light) -> DevicePreset {
+    DevicePreset::new(name, MEOWPAD_SE_DEVICE_NAME, (key, light))
+}
+
+/// 预设库中的预设，按修改时间从新到旧排列
+#[tauri::command]
+pub fn list_presets(library: State<'_, PresetLibrary>, filter: Option<PresetFilter>) -> Result<Vec<DevicePreset>> {
+    library.list(&filter.unwrap_or_default())
+}
+
+/// 新增或覆盖预设库中的预设
+#[tauri::command]
+pub fn save_preset(library: State<'_, PresetLibrary>, preset: DevicePreset) -> Result<DevicePreset> {
+    library.save(preset)
+}
+
+#[tauri::command]
+pub fn rename_preset(library: State<'_, PresetLibrary>, id: String, name: String) -> Result<DevicePreset> {
+    library.rename(&id, name)
+}
+
+#[tauri::command]
+pub fn update_preset_meta(library: State<'_, PresetLibrary>, id: String, meta: PresetMeta) -> Result<DevicePreset> {
+    let mut preset = library.get(&id)?;
+    preset.meta = PresetMeta {
+        created: preset.meta.created,
+        ..meta
+    };
+    library.save(preset)
+}
+
+#[tauri::command]
+pub fn duplicate_preset(library: State<'_, PresetLibrary>, id: String, name: String) -> Result<DevicePreset> {
+    library.duplicate(&id, name)
+}
+
+#[tauri::command]
+pub fn delete_preset(library: State<'_, PresetLibrary>, id: String) -> Result<()> {
+    library.remove(&id)
+}
+
+/// 导入多个预设，例如旧版本保存在设置中的预设，相同ID的预设会被合并
+#[tauri::command]
+pub fn import_presets(library: State<'_, PresetLibrary>, presets: Vec<DevicePreset>) -> Result<Vec<ImportResult>> {
+    presets.into_iter().map(|p| library.import(p)).collect()
+}
+
+/// 选择 `.pcf` 文件导入预设库，取消选择时返回 `None`
+#[tauri::command]
+pub async fn import_preset_file(library: State<'_, PresetLibrary>) -> Result<Option<ImportResult>> {
+    let Some(file_path) = FileDialogBuilder::new().add_filter("desu.life Config File", &["pcf"]).pick_file() else {
+        return Ok(None);
+    };
+    let preset = read_preset_file(file_path)?;
+    Ok(Some(library.import(preset)?))
+}
+
+/// 从JSON按键配置导入的预设与转换时的警告
+#[derive(serde::Serialize)]
+pub struct ExternalImport {
+    #[serde(flatten)]
+    result: ImportResult,
+    warnings: Vec<ImportWarning>,
+}
+
+/// 选择通用JSON格式的按键配置文件，转换为 `target` 的型号后导入预设库，取消选择时返回 `None`，
+/// `travel` 为按键总行程（毫米），`target` 通常由设备当前的配置生成
+#[tauri::command]
+pub async fn import_external_profile(
+    library: State<'_, PresetLibrary>,
+    target: DevicePreset,
+    travel: Option<f64>,
+) -> Result<Option<Vec<ExternalImport>>> {
+    let Some(file_path) = FileDialogBuilder::new().add_filter("JSON", &["json"]).pick_file() else {
+        return Ok(None);
+    };
+    let name = file_path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let text = std::fs::read_to_string(&file_path)?;
+    let conversions = preset_import::import(&text, &target, travel.filter(|t| *t > 0.0).unwrap_or(DEFAULT_TRAVEL), &name)?;
+    // 全部检查通过后再导入，不留下部分导入的预设
+    for c in &conversions {
+        ensure_valid(preset_validate::check(&c.preset))?;
+    }
+    let imported: Vec<ExternalImport> = conversions
+        .into_iter()
+        .map(|c| Ok(ExternalImport { result: library.import(c.preset)?, warnings: c.warnings }))
+        .collect::<Result<_>>()?;
+    Ok(Some(imported))
+}
+
+/// 把预设库中的预设导出为 `.pcf` 文件，取消选择时返回 `false`
+#[tauri::command]
+pub async fn export_preset_file(library: State<'_, PresetLibrary>, id: String) -> Result<bool> {
+    let preset = library.get(&id)?;
+    let Some(file_path) = FileDialogBuilder::new().set_file_name(&preset.name).add_filter("desu.life Config File", &["pcf"]).save_file() else {
+        return Ok(false);
+    };
+    std::fs::write(file_path, serde_json::to_string(&preset)?)?;
+    Ok(true)
+}
+
+/// 预设库中预设的分享码
+#[tauri::command]
+pub fn encode_preset_code(library: State<'_, PresetLibrary>, id: String) -> Result<String> {
+    preset_code::encode(&library.get(&id)?)
+}
+
+/// 导入分享码到预设库，分享码属于其他型号或预设有问题时拒绝导入
+#[tauri::command]
+pub fn import_preset_code(library: State<'_, PresetLibrary>, code: String, device_name: String) -> Result<ImportResult> {
+    let preset = preset_code::decode(&code, &device_name)?;
+    ensure_valid(preset_validate::check(&preset))?;
+    library.import(preset)
+}
+
+/// 合并预设库中同一型号的两个预设，所有冲突都有选择时保存为新的预设，
+/// 否则返回没有选择的冲突
+#[tauri::command]
+pub fn merge_presets(
+    library: State<'_, PresetLibrary>,
+    base: String,
+    other: String,
+    name: String,
+    resolutions: BTreeMap<String, Side>,
+) -> Result<MergeResult> {
+    let (base, other) = (library.get(&base)?, library.get(&other)?);
+    ensure_valid(preset_validate::check(&base))?;
+    ensure_valid(preset_validate::check_for(&other, &base.device.device_name))?;
+    Ok(match preset_merge::merge(&base, &other, &name, &resolutions) {
+        MergeResult::Merged { preset } => MergeResult::Merged { preset: Box::new(library.save(*preset)?) },
+        conflicts => conflicts,
+    })
+}
+
+/// 把预设库中的预设转换为 `target` 的型号，`target` 通常由设备当前的配置生成，
+/// 结果不会保存到预设库
+#[tauri::command]
+pub fn translate_preset(
+    library: State<'_, PresetLibrary>,
+    id: String,
+    target: DevicePreset,
+    mapping: Option<KeyMapping>,
+) -> Result<Translation> {
+    Ok(preset_translate::translate(&library.get(&id)?, &target, mapping))
+}
+
+/// 把预设写入所有已连接的同型号设备，每台设备写入校验后保存，`filter` 指定序列号时只写入这些设备，
+/// 返回每台设备的结果
+#[tauri::command]
+pub async fn apply_preset_to_devices(
+    app: tauri::AppHandle,
+    api_handle: State<'_, Mutex<HidApi>>,
+    history: State<'_, History>,
+    preset: DevicePreset,
+    filter: BroadcastFilter,
+) -> Result<Vec<BroadcastResult>> {
+    ensure_valid(preset_validate::check_for(&preset, &filter.device_name))?;
+    let api = api_handle.lock().unwrap();
+    // 写入期间锁住已连接的设备，不让其他命令插入读写
+    Ok(match filter.device_name.as_str() {
+        d if d == PURE64_DEVICE_NAME => {
+            let state = app.state::<Mutex<Option<Meowboard<HidDevice>>>>();
+            let mut connected = state.lock().unwrap();
+            cmdkbd::broadcast_preset_kb(&app, &api, &mut connected, &history, &preset, &filter)
+        }
+        d if d == MEOWPAD_DEVICE_NAME => {
+            let state = app.state::<Mutex<Option<Meowpad4k<HidDevice>>>>();
+            let mut connected = state.lock().unwrap();
+            cmd4k::broadcast_preset_4k(&app, &api, &mut connected, &history, &preset, &filter)
+        }
+        d if d == MEOWPAD_SE_DEVICE_NAME => {
+            let state = app.state::<Mutex<Option<Meowpad3k<HidDevice>>>>();
+            let mut connected = state.lock().unwrap();
+            cmd3k::broadcast_preset_3k(&app, &api, &mut connected, &history, &preset, &filter)
+        }
+        _ => return Err(Error::DeviceNotFound),
+    })
+}
+
+/// 由已连接设备内存中的配置生成预设，用于之后恢复
+pub fn current_preset(app: &tauri::AppHandle, device_name: &str) -> Result<DevicePreset> {
+    match device_name {
+        d if d == PURE64_DEVICE_NAME => {
+            let state = app.state::<Mutex<Option<Meowboard<HidDevice>>>>();
+            let mut _d = state.lock().unwrap();
+            let d = _d.as_mut().ok_or(Error::DeviceDisconnected)?;
+            d.load_key_config()?;
+            Ok(gen_preset_kb(device_name, d.key_config.unwrap().try_into()?))
+        }
+        d if d == MEOWPAD_DEVICE_NAME => Ok(gen_preset_4k(device_name, get_key_config_4k(app.clone(), app.state())?, get_light_config_4k(app.clone(), app.state())?)),
+        d if d == MEOWPAD_SE_DEVICE_NAME => Ok(gen_preset_3k(device_name, get_key_config_3k(app.clone(), app.state())?, get_light_config_3k(app.clone(), app.state())?)),
+        _ => Err(Error::DeviceNotFound),
+    }
+}
+
+/// 把预设写入对应型号的已连接设备，只写入内存而不保存，也不记录快照
+pub fn apply_preset_live(app: &tauri::AppHandle, preset: &DevicePreset) -> Result<()> {
+    match preset.device.device_name.as_str() {
+        d if d == PURE64_DEVICE_NAME => {
+            let state = app.state::<Mutex<Option<Meowboard<HidDevice>>>>();
+            let mut _d = state.lock().unwrap();
+            cmdkbd::write_preset_live(app, _d.as_mut().ok_or(Error::DeviceDisconnected)?, preset)
+        }
+        d if d == MEOWPAD_DEVICE_NAME => {
+            let state = app.state::<Mutex<Option<Meowpad4k<HidDevice>>>>();
+            let mut _d = state.lock().unwrap();
+            cmd4k::write_preset_live(app, _d.as_mut().ok_or(Error::DeviceDisconnected)?, preset)
+        }
+        d if d == MEOWPAD_SE_DEVICE_NAME => {
+            let state = app.state::<Mutex<Option<Meowpad3k<HidDevice>>>>();
+            let mut _d = state.lock().unwrap();
+            cmd3k::write_preset_live(app, _d.as_mut().ok_or(Error::DeviceDisconnected)?, preset)
+        }
+        _ => Err(Error::DeviceNotFound),
+    }
+}
+
+/// 把已连接设备内存中的配置保存到闪存
+pub fn save_device_config(app: &tauri::AppHandle, device_name: &str) -> Result<()> {
+    match device_name {
+        d if d == PURE64_DEVICE_NAME => save_key_config_kb(app.clone(), app.state(), app.state()),
+        d if d == MEOWPAD_DEVICE_NAME => {
+            save_key_config_4k(app.clone(), app.state(), app.state())?;
+            save_light_config_4k(app.clone(), app.state(), app.state())
+        }
+        d if d == MEOWPAD_SE_DEVICE_NAME => {
+            save_key_config_3k(app.clone(), app.state(), app.state())?;
+            save_light_config_3k(app.clone(), app.state(), app.state())
+        }
+        _ => Err(Error::DeviceNotFound),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use meowpad::{keycode::KeyValue, KeyCode};
+
+    /// 部分应用预设时只修改选择的部分
+    #[test]
+    fn load_preset_selection() {
+        let mut source = meowboard::config::Device::default();
+        source.keys[0].dead_zone = 20;
+        source.keys[1].dead_zone = 30;
+        source.normal_layer[0] = KeyValue::Keyboard(KeyCode::A);
+        source.fn_layer[0] = KeyValue::Keyboard(KeyCode::B);
+        let device = meowboard::config::Device::default();
+        let selection = PresetSelection { layers: vec![0], keys: Some(vec![0]), ..Default::default() };
+        let config = load_preset_kb(device, gen_preset_kb("kb", source), Some(selection)).unwrap();
+        assert_eq!(config.keys[0].dead_zone, 20);
+        assert_eq!(config.keys[1].dead_zone, device.keys[1].dead_zone);
+        assert_eq!(config.normal_layer[0], KeyValue::Keyboard(KeyCode::A));
+        assert_eq!(config.fn_layer[0], device.fn_layer[0]);
+
+        // 只应用灯光
+        let (key, light) = (meowpad4k::config::Key::default(), meowpad4k::config::Light::default());
+        let mut source = (key, light);
+        source.0.keys[2].dead_zone = 20;
+        source.0.jitters_elimination_time = 80;
+        source.1.max_brightness = 10;
+        let selection = PresetSelection { key_configs: false, key_data: false, key_settings: false, ..Default::default() };
+        let config = load_preset_4k(key, light, gen_preset_4k("4k", source.0, source.1), Some(selection)).unwrap();
+        assert_eq!(config.key.keys[2].dead_zone, key.keys[2].dead_zone);
+        assert_eq!(config.key.jitters_elimination_time, key.jitters_elimination_time);
+        assert_eq!(config.light.max_brightness, 10);
+    }
 }
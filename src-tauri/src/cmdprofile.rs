@@ -0,0 +1,71 @@
+use std::{sync::Mutex, thread, time::Duration};
+
+use log::*;
+use tauri::{Manager, State};
+
+use crate::{
+    cmdpreset::{apply_preset_live, current_preset},
+    device_preset::DevicePreset,
+    error::Result,
+    preset_library::PresetLibrary,
+    profile::{self, PresetTarget, ProfileSettings, Profiles, Switcher, POLL_INTERVAL},
+    tray,
+};
+
+/// 通过设备命令写入已连接的设备
+struct DeviceTarget<'a>(&'a tauri::AppHandle);
+
+impl PresetTarget for DeviceTarget<'_> {
+    fn current(&mut self, device_name: &str) -> Result<DevicePreset> {
+        current_preset(self.0, device_name)
+    }
+
+    fn apply(&mut self, preset: &DevicePreset) -> Result<()> {
+        apply_preset_live(self.0, preset)
+    }
+}
+
+/// 在后台按前台应用切换预设，关闭自动切换时恢复切换前的配置，
+/// 每次切换后发送 `profile_switched` 事件，[`Switcher`] 与托盘共用
+pub fn spawn_profile_watcher(app: tauri::AppHandle) {
+    let mut source = profile::detect();
+    info!("前台应用检测方式：{}", source.name());
+    thread::spawn(move || {
+        let mut target = DeviceTarget(&app);
+        loop {
+            thread::sleep(Duration::from_millis(POLL_INTERVAL));
+            let settings = app.state::<Profiles>().settings();
+            let switcher = app.state::<Mutex<Switcher>>();
+            let mut switcher = switcher.lock().unwrap();
+            let switches = if settings.enabled {
+                let library = app.state::<PresetLibrary>();
+                switcher.update(&settings.rules, source.foreground().as_ref(), |id| library.get(id), &mut target)
+            } else {
+                switcher.reset(&mut target)
+            };
+            if !switches.is_empty() {
+                info!("自动切换预设：{:?}", switches);
+                let _ = app.emit_all("profile_switched", &switches);
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_profile_settings(profiles: State<'_, Profiles>) -> ProfileSettings {
+    profiles.settings()
+}
+
+/// 保存后重新注册全局快捷键
+#[tauri::command]
+pub fn set_profile_settings(app: tauri::AppHandle, profiles: State<'_, Profiles>, settings: ProfileSettings) -> Result<()> {
+    let hotkeys = settings.hotkeys.clone();
+    profiles.set_settings(settings)?;
+    tray::register_hotkeys(&app, &hotkeys)
+}
+
+/// 当前平台检测前台应用的方式，为 `none` 时不支持自动切换
+#[tauri::command]
+pub fn get_window_source() -> &'static str {
+    profile::detect().name()
+}
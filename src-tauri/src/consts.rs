@@ -5,3 +5,4 @@ pub static FIRMWARE_VERSION_KB: &str = "0.1.2";
 pub static MEOWPAD_DEVICE_NAME: &str = "Meowpad";
 pub static MEOWPAD_SE_DEVICE_NAME: &str = "Meowpad SE v2";
 pub static PURE64_DEVICE_NAME: &str = "Pure64";
+pub static FIRMWARE_MANIFEST_URL: &str = "https://assets.desu.life/device/firmware/manifest.json";
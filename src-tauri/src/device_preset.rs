@@ -1,30 +1,56 @@
-use meowpad::keycode::KeyValue;
+use meowpad::{keycode::KeyValue, validate::{Diagnostic, Severity}, KeyCode};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::device::DeviceInfoSerdi;
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+/// `.pcf` 预设格式的版本，没有版本号的预设为旧版本 (0)
+pub const PRESET_VERSION: u32 = 1;
+
+/// `.pcf` 预设文件，应用前的检查见 `preset_validate`
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
 pub struct DevicePreset {
+    #[serde(default)]
+    pub version: u32,
     pub id: String,
     pub name: String,
     pub device: DevicePresetInfo,
-    pub config: DevicePresetConfig
+    pub config: DevicePresetConfig,
+    #[serde(default)]
+    pub meta: PresetMeta,
+}
+
+/// 预设库中使用的信息，旧版本的预设中没有
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Default)]
+pub struct PresetMeta {
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub author: Option<String>,
+    pub notes: Option<String>,
+    /// Unix 时间戳（秒），未知时为0
+    #[serde(default)]
+    pub created: u64,
+    #[serde(default)]
+    pub modified: u64,
 }
 
 impl DevicePreset {
     pub fn new(name: &str, device: impl Into<DevicePresetInfo>, config: impl Into<DevicePresetConfig>) -> Self {
         Self {
+            version: PRESET_VERSION,
             id: Uuid::new_v4().to_string(),
             name: name.to_string(),
             device: device.into(),
-            config: config.into()
+            config: config.into(),
+            meta: PresetMeta::default(),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
 pub struct DevicePresetInfo {
+    /// 设备型号：`Pure64`、`Meowpad` 或 `Meowpad SE v2`
     pub device_name: String,
     pub serial_number: Option<String>,
 }
@@ -47,10 +73,42 @@ impl From<&str> for DevicePresetInfo {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+/// 各部分的长度与数值范围随型号不同
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Default)]
 pub struct DevicePresetConfig {
     pub key_layers: Option<Vec<KeyLayer>>,
     pub key_configs: Option<Vec<KeyConfig>>,
+    /// Meowpad每个按键绑定的组合键，按按键顺序排列
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_data: Option<Vec<[KeyCode; 6]>>,
+    /// Meowpad SE v2的侧键
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub side_btn: Option<[KeyCode; 6]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_settings: Option<KeySettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub light: Option<PresetLight>,
+}
+
+/// Meowpad的全局按键设置
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Copy)]
+pub struct KeySettings {
+    /// 单位与对应型号的配置相同
+    pub jitters_elimination_time: u16,
+    pub continuous_report: bool,
+    pub kalman_filter: bool,
+    /// 仅Meowpad 4K支持
+    pub enable_hs: Option<bool>,
+}
+
+/// 灯光设置随型号不同，按型号区分
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(tag = "model")]
+pub enum PresetLight {
+    #[serde(rename = "4k")]
+    Meowpad4k(meowpad4k::config::Light),
+    #[serde(rename = "3k")]
+    Meowpad3k(meowpad3k::config::Light),
 }
 
 impl From<meowboard::config::Device> for DevicePresetConfig {
@@ -65,16 +123,51 @@ impl From<meowboard::config::Device> for DevicePresetConfig {
             key_configs.push(key.into());
         }
 
-        Self { key_layers: Some(key_layers), key_configs: Some(key_configs) }
+        Self { key_layers: Some(key_layers), key_configs: Some(key_configs), ..Default::default() }
+    }
+}
+
+impl From<(meowpad4k::config::Key, meowpad4k::config::Light)> for DevicePresetConfig {
+    fn from((key, light): (meowpad4k::config::Key, meowpad4k::config::Light)) -> Self {
+        Self {
+            key_configs: Some(key.keys.iter().map(|&k| k.into()).collect()),
+            key_data: Some(key.keys.iter().map(|k| k.key_data).collect()),
+            key_settings: Some(KeySettings {
+                jitters_elimination_time: key.jitters_elimination_time,
+                continuous_report: key.continuous_report,
+                kalman_filter: key.kalman_filter,
+                enable_hs: Some(key.enable_hs),
+            }),
+            light: Some(PresetLight::Meowpad4k(light)),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<(meowpad3k::config::Key, meowpad3k::config::Light)> for DevicePresetConfig {
+    fn from((key, light): (meowpad3k::config::Key, meowpad3k::config::Light)) -> Self {
+        Self {
+            key_configs: Some(key.keys.iter().map(|&k| k.into()).collect()),
+            key_data: Some(key.keys.iter().map(|k| k.key_data).collect()),
+            side_btn: Some(key.side_btn),
+            key_settings: Some(KeySettings {
+                jitters_elimination_time: key.jitters_elimination_time,
+                continuous_report: key.continuous_report,
+                kalman_filter: key.kalman_filter,
+                enable_hs: None,
+            }),
+            light: Some(PresetLight::Meowpad3k(light)),
+            ..Default::default()
+        }
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
 pub struct KeyLayer {
     pub keys: Vec<KeyValue>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, Copy)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Copy)]
 pub struct KeyConfig {
     pub press_percentage: u32,
     pub release_percentage: u32,
@@ -95,14 +188,97 @@ impl From<meowboard::config::KeyConfig> for KeyConfig {
     }
 }
 
-impl From<KeyConfig> for meowboard::config::KeyConfig {
-    fn from(value: KeyConfig) -> Self {
+impl From<meowpad4k::config::KeyConfig> for KeyConfig {
+    fn from(value: meowpad4k::config::KeyConfig) -> Self {
         Self {
-            press_percentage: value.press_percentage as u8,
-            release_percentage: value.release_percentage as u8,
-            dead_zone: value.dead_zone as u8,
-            release_dead_zone: value.release_dead_zone as u8,
-            rt_enabled: value.rt_enabled.unwrap_or(true)
+            press_percentage: value.press_percentage as u32,
+            release_percentage: value.release_percentage as u32,
+            dead_zone: value.dead_zone as u32,
+            release_dead_zone: 0,
+            rt_enabled: None
+        }
+    }
+}
+
+impl From<meowpad3k::config::KeyConfig> for KeyConfig {
+    fn from(value: meowpad3k::config::KeyConfig) -> Self {
+        Self {
+            press_percentage: value.press_percentage as u32,
+            release_percentage: value.release_percentage as u32,
+            dead_zone: value.dead_zone as u32,
+            release_dead_zone: 0,
+            rt_enabled: None
         }
     }
-}
\ No newline at end of file
+}
+
+/// 数值超出 `u8` 时返回该字段的诊断，路径相对于按键
+impl TryFrom<KeyConfig> for meowboard::config::KeyConfig {
+    type Error = Diagnostic;
+
+    fn try_from(value: KeyConfig) -> Result<Self, Self::Error> {
+        let byte = |path: &str, v: u32| {
+            u8::try_from(v).map_err(|_| Diagnostic {
+                path: path.to_owned(),
+                value: v.into(),
+                min: 0,
+                max: u8::MAX.into(),
+                severity: Severity::Error,
+            })
+        };
+        Ok(Self {
+            press_percentage: byte("press_percentage", value.press_percentage)?,
+            release_percentage: byte("release_percentage", value.release_percentage)?,
+            dead_zone: byte("dead_zone", value.dead_zone)?,
+            release_dead_zone: byte("release_dead_zone", value.release_dead_zone)?,
+            rt_enabled: value.rt_enabled.unwrap_or(true)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn meowpad_preset_roundtrip() {
+        let mut key = meowpad3k::config::Key::default();
+        key.side_btn[0] = KeyCode::Escape;
+        key.keys[2].dead_zone = 20;
+        let preset = DevicePreset::new("3k", "Meowpad SE v2", (key, meowpad3k::config::Light::default()));
+        let preset: DevicePreset = serde_json::from_str(&serde_json::to_string(&preset).unwrap()).unwrap();
+        let config = preset.config;
+        assert_eq!(config.side_btn.unwrap()[0], KeyCode::Escape);
+        assert_eq!(config.key_configs.unwrap()[2].dead_zone, 20);
+        assert!(matches!(config.light, Some(PresetLight::Meowpad3k(_))));
+
+        // 旧版本的预设没有Meowpad的字段
+        let old = r#"{"key_layers":null,"key_configs":[]}"#;
+        let config: DevicePresetConfig = serde_json::from_str(old).unwrap();
+        assert!(config.light.is_none() && config.key_data.is_none());
+    }
+
+    /// 前端手写的预设类型与JSON Schema的字段一致
+    #[test]
+    fn ts_interfaces() {
+        use meowpad::schema::{schema_fields, ts_interface_fields};
+        let index = include_str!("../../src/apis/index.ts");
+        assert_eq!(ts_interface_fields(index, "IDevicePreset"), schema_fields::<DevicePreset>());
+        assert_eq!(ts_interface_fields(index, "IDevicePresetInfo"), schema_fields::<DevicePresetInfo>());
+        assert_eq!(ts_interface_fields(index, "IPresetMeta"), schema_fields::<PresetMeta>());
+        assert_eq!(ts_interface_fields(index, "IPresetConfig"), schema_fields::<DevicePresetConfig>());
+        assert_eq!(ts_interface_fields(index, "IKeyPresetSettings"), schema_fields::<KeySettings>());
+        assert_eq!(ts_interface_fields(index, "IKeyPresetLayer"), schema_fields::<KeyLayer>());
+        assert_eq!(ts_interface_fields(index, "IKeyPresetConfig"), schema_fields::<KeyConfig>());
+    }
+
+    #[test]
+    fn pure64_key_config_range() {
+        let mut key = KeyConfig::from(meowboard::config::KeyConfig::default());
+        key.dead_zone = 40;
+        assert_eq!(meowboard::config::KeyConfig::try_from(key).unwrap().dead_zone, 40);
+        key.release_dead_zone = 300;
+        let d = meowboard::config::KeyConfig::try_from(key).unwrap_err();
+        assert_eq!((d.path.as_str(), d.value), ("release_dead_zone", 300));
+    }
+}
@@ -18,4 +18,36 @@ pub enum Error {
     DeviceNotFound,
     #[error("设备未连接")]
     DeviceDisconnected,
+    #[error("固件更新正在进行中")]
+    IapBusy,
+    #[error("json error: {0}")]
+    Json(#[from] #[serde_as(as = "DisplayFromStr")] serde_json::Error),
+    #[error("找不到固件 {0} {1}")]
+    FirmwareNotFound(String, String),
+    #[error("固件校验失败")]
+    FirmwareHashMismatch,
+    #[error("配置解析失败: {0}")]
+    ConfigParse(crate::rawconfig::ParseError),
+    #[error("配置数值超出范围")]
+    InvalidConfig(Vec<meowpad::validate::Diagnostic>),
+    #[error("找不到快照 {0}")]
+    SnapshotNotFound(u64),
+    #[error("设备没有序列号，无法保存配置快照")]
+    MissingSerial,
+    #[error("快照属于其他型号的设备: {0}")]
+    SnapshotModelMismatch(String),
+    #[error("找不到预设 {0}")]
+    PresetNotFound(String),
+    #[error("预设ID无效: {0}")]
+    InvalidPresetId(String),
+    #[error("预设无效")]
+    InvalidPreset(crate::preset_validate::PresetReport),
+    #[error("分享码无效: {0}")]
+    InvalidShareCode(String),
+    #[error("分享码属于其他型号的设备: {0}")]
+    ShareCodeModelMismatch(String),
+    #[error("无法注册快捷键: {0}")]
+    Hotkey(String),
+    #[error("无法识别的配置文件")]
+    UnsupportedProfile,
 }
\ No newline at end of file
@@ -0,0 +1,269 @@
+use std::{collections::HashMap, fs, io, path::PathBuf};
+
+use log::*;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::{Error, Result},
+    utils::compare_version,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FirmwareRelease {
+    pub version: String,
+    pub notes: String,
+    #[serde(default)]
+    pub date: String,
+    pub url: String,
+    pub sha256: String,
+    /// 需要的最低bootloader版本
+    pub min_bootloader: Option<String>,
+}
+
+/// 固件清单，按产品名称（与设备名称一致）分组
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FirmwareManifest {
+    pub products: HashMap<String, Vec<FirmwareRelease>>,
+}
+
+impl FirmwareManifest {
+    /// 按版本从新到旧排列
+    pub fn releases(&self, product: &str) -> Vec<FirmwareRelease> {
+        let mut releases = self.products.get(product).cloned().unwrap_or_default();
+        releases.sort_by(|a, b| compare_version(&b.version, &a.version));
+        releases
+    }
+
+    pub fn find(&self, product: &str, version: &str) -> Option<&FirmwareRelease> {
+        self.products
+            .get(product)?
+            .iter()
+            .find(|r| r.version == version)
+    }
+}
+
+pub fn verify_sha256(data: &[u8], expected: &str) -> bool {
+    hex::encode(Sha256::digest(data)).eq_ignore_ascii_case(expected.trim())
+}
+
+/// 固件清单与镜像的客户端，下载的内容缓存在本地，离线时使用缓存
+pub struct FirmwareCatalog {
+    client: Client,
+    manifest_url: String,
+    cache_dir: PathBuf,
+}
+
+impl FirmwareCatalog {
+    pub fn new(client: Client, manifest_url: impl Into<String>, cache_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            client,
+            manifest_url: manifest_url.into(),
+            cache_dir: cache_dir.into(),
+        }
+    }
+
+    fn manifest_path(&self) -> PathBuf {
+        self.cache_dir.join("manifest.json")
+    }
+
+    fn image_path(&self, release: &FirmwareRelease) -> PathBuf {
+        self.cache_dir
+            .join("images")
+            .join(format!("{}.bin", release.sha256.trim().to_lowercase()))
+    }
+
+    /// 获取最新清单，网络不可用时回退到本地缓存
+    pub async fn manifest(&self) -> Result<FirmwareManifest> {
+        match self.fetch_manifest().await {
+            Ok(manifest) => {
+                fs::create_dir_all(&self.cache_dir)?;
+                fs::write(self.manifest_path(), serde_json::to_vec(&manifest)?)?;
+                Ok(manifest)
+            }
+            Err(e) => {
+                warn!("获取固件清单失败，使用本地缓存：{}", e);
+                self.cached_manifest()?.ok_or(e)
+            }
+        }
+    }
+
+    async fn fetch_manifest(&self) -> Result<FirmwareManifest> {
+        Ok(self
+            .client
+            .get(&self.manifest_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<FirmwareManifest>()
+            .await?)
+    }
+
+    pub fn cached_manifest(&self) -> Result<Option<FirmwareManifest>> {
+        match fs::read(self.manifest_path()) {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn is_cached(&self, release: &FirmwareRelease) -> bool {
+        fs::read(self.image_path(release))
+            .map(|data| verify_sha256(&data, &release.sha256))
+            .unwrap_or(false)
+    }
+
+    /// 获取固件镜像，优先使用校验通过的本地缓存
+    pub async fn image(&self, release: &FirmwareRelease) -> Result<Vec<u8>> {
+        let path = self.image_path(release);
+        if let Ok(data) = fs::read(&path) {
+            if verify_sha256(&data, &release.sha256) {
+                return Ok(data);
+            }
+            warn!("缓存的固件校验失败，重新下载：{}", path.to_string_lossy());
+            let _ = fs::remove_file(&path);
+        }
+
+        let data = self
+            .client
+            .get(&release.url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?
+            .to_vec();
+        if !verify_sha256(&data, &release.sha256) {
+            return Err(Error::FirmwareHashMismatch);
+        }
+
+        fs::create_dir_all(self.cache_dir.join("images"))?;
+        fs::write(&path, &data)?;
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    fn bind() -> (TcpListener, String) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        (listener, base)
+    }
+
+    /// 简易HTTP服务器，按路径返回固定内容
+    fn serve(listener: TcpListener, routes: Vec<(String, Vec<u8>)>) {
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                        break;
+                    }
+                }
+                let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+                let (status, body) = match routes.iter().find(|(p, _)| p == path) {
+                    Some((_, body)) => ("200 OK", body.clone()),
+                    None => ("404 Not Found", vec![]),
+                };
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    body.len()
+                );
+                let _ = stream.write_all(&body);
+            }
+        });
+    }
+
+    fn manifest(base: &str, image: &[u8]) -> FirmwareManifest {
+        let release = |version: &str| FirmwareRelease {
+            version: version.to_owned(),
+            notes: String::new(),
+            date: String::new(),
+            url: format!("{}/pure64.bin", base),
+            sha256: hex::encode(Sha256::digest(image)),
+            min_bootloader: None,
+        };
+        FirmwareManifest {
+            products: HashMap::from([(
+                "Pure64".to_owned(),
+                vec![release("0.1.1"), release("0.1.2")],
+            )]),
+        }
+    }
+
+    fn cache_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("firmware-test-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn catalog_online_then_offline() {
+        let image = vec![0x73u8; 1024];
+        let (listener, base) = bind();
+        serve(listener, vec![
+            ("/manifest".to_owned(), serde_json::to_vec(&manifest(&base, &image)).unwrap()),
+            ("/pure64.bin".to_owned(), image.clone()),
+        ]);
+        let dir = cache_dir();
+
+        tauri::async_runtime::block_on(async {
+            let catalog = FirmwareCatalog::new(Client::new(), format!("{}/manifest", base), &dir);
+            let m = catalog.manifest().await.unwrap();
+            let releases = m.releases("Pure64");
+            assert_eq!(releases[0].version, "0.1.2");
+            assert!(!catalog.is_cached(&releases[0]));
+            assert_eq!(catalog.image(&releases[0]).await.unwrap(), image);
+            assert!(catalog.is_cached(&releases[0]));
+
+            // 服务器不可达时使用缓存
+            let offline = FirmwareCatalog::new(Client::new(), "http://127.0.0.1:1/manifest", &dir);
+            let m = offline.manifest().await.unwrap();
+            let release = FirmwareRelease {
+                url: "http://127.0.0.1:1/pure64.bin".to_owned(),
+                ..m.find("Pure64", "0.1.2").unwrap().clone()
+            };
+            assert_eq!(offline.image(&release).await.unwrap(), image);
+        });
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn catalog_rejects_bad_hash() {
+        let (listener, base) = bind();
+        serve(listener, vec![("/pure64.bin".to_owned(), vec![0u8; 16])]);
+        let dir = cache_dir();
+        let release = FirmwareRelease {
+            version: "0.1.2".to_owned(),
+            notes: String::new(),
+            date: String::new(),
+            url: format!("{}/pure64.bin", base),
+            sha256: hex::encode(Sha256::digest([1u8; 16])),
+            min_bootloader: None,
+        };
+
+        tauri::async_runtime::block_on(async {
+            let catalog = FirmwareCatalog::new(Client::new(), format!("{}/manifest", base), &dir);
+            assert!(matches!(
+                catalog.image(&release).await,
+                Err(Error::FirmwareHashMismatch)
+            ));
+            assert!(!catalog.is_cached(&release));
+            assert!(catalog.manifest().await.is_err());
+        });
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
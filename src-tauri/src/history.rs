@@ -0,0 +1,250 @@
+use std::{
+    fs, io,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use meowpad::models::KeyHallConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// 每台设备默认保留的快照数量
+pub const DEFAULT_LIMIT: usize = 50;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SnapshotReason {
+    /// 写入设备内存之前，记录的是闪存中的配置，保存前多次写入只产生一个快照
+    Write,
+    /// 保存到闪存之前，记录的是闪存中的配置
+    Save,
+    Clear,
+    Firmware,
+    /// 恢复其他快照之前
+    Restore,
+    Manual,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Snapshot {
+    /// 同一设备内递增
+    pub id: u64,
+    /// Unix 时间戳（秒）
+    pub timestamp: u64,
+    pub reason: SnapshotReason,
+    pub label: Option<String>,
+    /// 设备型号：`kb`、`4k` 或 `3k`
+    pub model: String,
+    /// 与原始配置文本相同的结构
+    pub config: serde_json::Value,
+    /// 霍尔校准数据，设备不支持写入，仅作记录，写入前的快照中为空
+    #[serde(default)]
+    pub hall: Vec<KeyHallConfig>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Settings {
+    limit: Option<usize>,
+}
+
+/// 一台设备的快照文件
+#[derive(Serialize, Deserialize, Default)]
+struct DeviceHistory {
+    /// 下一个快照的ID，删除快照后也不会重复使用
+    next_id: u64,
+    /// 从旧到新排列
+    snapshots: Vec<Snapshot>,
+}
+
+/// 按设备序列号保存的配置快照，每台设备一个JSON文件
+pub struct History {
+    dir: PathBuf,
+    limit: AtomicUsize,
+}
+
+impl History {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        let dir = dir.into();
+        let limit = fs::read(dir.join("settings.json"))
+            .ok()
+            .and_then(|data| serde_json::from_slice::<Settings>(&data).ok())
+            .and_then(|s| s.limit)
+            .unwrap_or(DEFAULT_LIMIT);
+        Self {
+            dir,
+            limit: AtomicUsize::new(limit),
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit.load(Ordering::Relaxed)
+    }
+
+    /// 新的数量在下一次保存快照时生效，带标签的快照不受限制
+    pub fn set_limit(&self, limit: usize) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(
+            self.dir.join("settings.json"),
+            serde_json::to_vec(&Settings { limit: Some(limit) })?,
+        )?;
+        self.limit.store(limit, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// 文件名为序列号的十六进制编码，不同的序列号不会共用文件；
+    /// 没有序列号时无法区分设备，不保存快照
+    fn path(&self, serial: &str) -> Result<PathBuf> {
+        if serial.is_empty() {
+            return Err(Error::MissingSerial);
+        }
+        let name: String = serial.bytes().map(|b| format!("{:02x}", b)).collect();
+        Ok(self.dir.join(format!("{}.json", name)))
+    }
+
+    fn read(&self, serial: &str) -> Result<DeviceHistory> {
+        match fs::read(self.path(serial)?) {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(DeviceHistory { next_id: 1, snapshots: vec![] }),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn write(&self, serial: &str, history: &DeviceHistory) -> Result<()> {
+        let path = self.path(serial)?;
+        fs::create_dir_all(&self.dir)?;
+        fs::write(path, serde_json::to_vec(history)?)?;
+        Ok(())
+    }
+
+    /// 从新到旧排列
+    pub fn list(&self, serial: &str) -> Result<Vec<Snapshot>> {
+        let mut snapshots = self.read(serial)?.snapshots;
+        snapshots.reverse();
+        Ok(snapshots)
+    }
+
+    pub fn get(&self, serial: &str, id: u64) -> Result<Snapshot> {
+        self.read(serial)?
+            .snapshots
+            .into_iter()
+            .find(|s| s.id == id)
+            .ok_or(Error::SnapshotNotFound(id))
+    }
+
+    /// 与最新的快照相同时不重复保存，返回最新的快照
+    pub fn push(
+        &self,
+        serial: &str,
+        model: &str,
+        reason: SnapshotReason,
+        config: serde_json::Value,
+        hall: Vec<KeyHallConfig>,
+    ) -> Result<Snapshot> {
+        let mut history = self.read(serial)?;
+        if let Some(last) = history.snapshots.last() {
+            if last.model == model && last.config == config {
+                return Ok(last.clone());
+            }
+        }
+        let snapshot = Snapshot {
+            id: history.next_id,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            reason,
+            label: None,
+            model: model.to_owned(),
+            config,
+            hall,
+        };
+        history.next_id += 1;
+        history.snapshots.push(snapshot.clone());
+
+        // 超出数量时从最旧的无标签快照开始删除
+        let limit = self.limit();
+        let mut unlabeled = history.snapshots.iter().filter(|s| s.label.is_none()).count();
+        history.snapshots.retain(|s| {
+            if s.label.is_none() && unlabeled > limit {
+                unlabeled -= 1;
+                false
+            } else {
+                true
+            }
+        });
+        self.write(serial, &history)?;
+        Ok(snapshot)
+    }
+
+    pub fn set_label(&self, serial: &str, id: u64, label: Option<String>) -> Result<()> {
+        let mut history = self.read(serial)?;
+        let snapshot = history
+            .snapshots
+            .iter_mut()
+            .find(|s| s.id == id)
+            .ok_or(Error::SnapshotNotFound(id))?;
+        snapshot.label = label.filter(|l| !l.is_empty());
+        self.write(serial, &history)
+    }
+
+    pub fn remove(&self, serial: &str, id: u64) -> Result<()> {
+        let mut history = self.read(serial)?;
+        let len = history.snapshots.len();
+        history.snapshots.retain(|s| s.id != id);
+        if history.snapshots.len() == len {
+            return Err(Error::SnapshotNotFound(id));
+        }
+        self.write(serial, &history)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("history-test-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn history_retention() {
+        let dir = history_dir();
+        let history = History::new(&dir);
+        history.set_limit(3).unwrap();
+        let config = |n: u32| serde_json::json!({ "key": { "hall_filter": n } });
+
+        for n in 0..3 {
+            history.push("SN:01", "kb", SnapshotReason::Write, config(n), vec![]).unwrap();
+        }
+        // 相同的配置不重复保存
+        let last = history.push("SN:01", "kb", SnapshotReason::Save, config(2), vec![]).unwrap();
+        assert_eq!(last.id, 3);
+        history.set_label("SN:01", 1, Some("出厂".to_owned())).unwrap();
+
+        for n in 3..6 {
+            history.push("SN:01", "kb", SnapshotReason::Write, config(n), vec![]).unwrap();
+        }
+        let ids: Vec<u64> = history.list("SN:01").unwrap().iter().map(|s| s.id).collect();
+        assert_eq!(ids, vec![6, 5, 4, 1]);
+        assert_eq!(history.get("SN:01", 1).unwrap().config, config(0));
+        assert!(history.list("other").unwrap().is_empty());
+
+        // 设置在重新打开后保留
+        let history = History::new(&dir);
+        assert_eq!(history.limit(), 3);
+        history.remove("SN:01", 6).unwrap();
+        assert!(matches!(history.get("SN:01", 6), Err(Error::SnapshotNotFound(6))));
+        // 删除最新的快照后ID不会重复使用
+        let next = history.push("SN:01", "kb", SnapshotReason::Write, config(6), vec![]).unwrap();
+        assert_eq!(next.id, 7);
+
+        // 只有符号不同的序列号不共用文件，没有序列号时不保存
+        history.push("SN01", "kb", SnapshotReason::Write, config(0), vec![]).unwrap();
+        assert_eq!(history.list("SN01").unwrap().len(), 1);
+        assert_eq!(history.list("SN:01").unwrap().len(), 4);
+        assert!(matches!(history.push("", "kb", SnapshotReason::Write, config(0), vec![]), Err(Error::MissingSerial)));
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
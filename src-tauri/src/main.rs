@@ -25,6 +25,7 @@ use std::sync::{mpsc, Mutex};
 use std::time::Duration;
 use tauri::api::dialog::MessageDialogBuilder;
 use tauri::Manager;
+use tauri::SystemTray;
 use tauri::State;
 use tauri_plugin_log::fern::colors::ColoredLevelConfig;
 use tauri_plugin_log::LogTarget;
@@ -39,11 +40,30 @@ mod error;
 mod utils;
 mod device_preset;
 mod cmdpreset;
+mod preset_library;
+mod preset_validate;
+mod preset_code;
+mod preset_merge;
+mod preset_translate;
+mod preset_broadcast;
+mod preset_import;
+mod profile;
+mod cmdprofile;
+mod tray;
+mod firmware;
+mod cmdfirmware;
+mod rawconfig;
+mod cli;
+mod history;
+mod cmdhistory;
 use cmd3k::*;
 use cmd4k::*;
 use cmdiap::*;
 use cmdkbd::*;
 use cmdpreset::*;
+use cmdfirmware::*;
+use cmdhistory::*;
+use cmdprofile::*;
 use consts::*;
 use error::Result;
 
@@ -340,7 +360,26 @@ fn connect_device(
     true
 }
 
+/// 有未保存配置的设备，正在使用中的设备跳过
+fn unsaved_devices(app: &tauri::AppHandle) -> Vec<&'static str> {
+    let mut devices = vec![];
+    if app.state::<Mutex<Option<Meowpad4k<HidDevice>>>>().try_lock().is_ok_and(|d| d.as_ref().is_some_and(|d| d.unsaved().any())) {
+        devices.push(MEOWPAD_DEVICE_NAME);
+    }
+    if app.state::<Mutex<Option<Meowpad3k<HidDevice>>>>().try_lock().is_ok_and(|d| d.as_ref().is_some_and(|d| d.unsaved().any())) {
+        devices.push(MEOWPAD_SE_DEVICE_NAME);
+    }
+    if app.state::<Mutex<Option<Meowboard<HidDevice>>>>().try_lock().is_ok_and(|d| d.as_ref().is_some_and(|d| d.unsaved().any())) {
+        devices.push(PURE64_DEVICE_NAME);
+    }
+    devices
+}
+
 fn main() -> AnyResult<()> {
+    if let Some(code) = cli::run(&env::args().skip(1).collect::<Vec<_>>()) {
+        std::process::exit(code);
+    }
+
     panic::set_hook(Box::new(|e| {
         use better_panic::Settings;
         use std::backtrace::Backtrace;
@@ -382,7 +421,56 @@ fn main() -> AnyResult<()> {
                 })
                 .build(),
         )
+        .system_tray(SystemTray::new())
+        .on_system_tray_event(tray::on_tray_event)
+        .on_window_event(|event| {
+            if let tauri::WindowEvent::CloseRequested { api, .. } = event.event() {
+                let app = event.window().app_handle();
+                let devices = unsaved_devices(&app);
+                if !devices.is_empty() {
+                    api.prevent_close();
+                    tauri::api::dialog::ask(
+                        Some(event.window()),
+                        "Meowpad Configurator",
+                        format!("{} 有尚未保存的配置，断开或重启设备后会丢失，仍要退出吗？", devices.join("、")),
+                        move |exit| {
+                            if exit {
+                                app.exit(0);
+                            }
+                        },
+                    );
+                }
+            }
+        })
         .setup(|_app| {
+            let cache_dir = _app
+                .path_resolver()
+                .app_cache_dir()
+                .expect("failed to resolve cache dir")
+                .join("firmware");
+            let client = _app.state::<Client>().inner().clone();
+            _app.manage(firmware::FirmwareCatalog::new(client, FIRMWARE_MANIFEST_URL, cache_dir));
+            let history_dir = _app
+                .path_resolver()
+                .app_data_dir()
+                .expect("failed to resolve data dir")
+                .join("history");
+            _app.manage(history::History::new(history_dir));
+            let preset_dir = _app
+                .path_resolver()
+                .app_data_dir()
+                .expect("failed to resolve data dir")
+                .join("presets");
+            _app.manage(preset_library::PresetLibrary::new(preset_dir));
+            let data_dir = _app.path_resolver().app_data_dir().expect("failed to resolve data dir");
+            _app.manage(profile::Profiles::new(data_dir));
+            spawn_profile_watcher(_app.handle());
+            tray::refresh_tray(&_app.handle());
+            let hotkeys = _app.state::<profile::Profiles>().settings().hotkeys;
+            if let Err(e) = tray::register_hotkeys(&_app.handle(), &hotkeys) {
+                warn!("{}", e);
+            }
+
             #[cfg(debug_assertions)] // only include this code on debug builds
             {
                 let window = _app.get_window("main").unwrap();
@@ -407,7 +495,17 @@ fn main() -> AnyResult<()> {
             clear_config_4k,
             get_raw_config_4k,
             check_raw_config_4k,
+            import_v1_config_4k,
+            get_config_schema_4k,
             save_raw_config_4k,
+            apply_config_4k,
+            get_unsaved_4k,
+            diff_key_config_4k,
+            diff_light_config_4k,
+            diff_raw_config_4k,
+            diff_unsaved_config_4k,
+            create_snapshot_4k,
+            restore_snapshot_4k,
             connect_4k,
             reset_device_4k,
             calibration_key_3k,
@@ -424,13 +522,23 @@ fn main() -> AnyResult<()> {
             clear_config_3k,
             get_raw_config_3k,
             check_raw_config_3k,
+            get_config_schema_3k,
             save_raw_config_3k,
+            apply_config_3k,
+            get_unsaved_3k,
+            diff_key_config_3k,
+            diff_light_config_3k,
+            diff_raw_config_3k,
+            diff_unsaved_config_3k,
+            create_snapshot_3k,
+            restore_snapshot_3k,
             connect_3k,
             reset_device_3k,
             get_device_info_3k,
             get_device_status_3k,
             get_device_info_4k,
             get_device_status_4k,
+            get_capabilities_4k,
             get_latest_version,
             get_firmware_4k_version,
             get_firmware_3k_version,
@@ -438,7 +546,9 @@ fn main() -> AnyResult<()> {
             connect_iap,
             iap_start,
             iap_flush,
+            iap_cancel,
             connect_kb,
+            get_capabilities_kb,
             get_device_status_kb,
             calibration_key_kb,
             clear_config_kb,
@@ -456,7 +566,15 @@ fn main() -> AnyResult<()> {
             get_device_info_kb,
             get_raw_config_kb,
             check_raw_config_kb,
+            get_config_schema_kb,
             save_raw_config_kb,
+            apply_config_kb,
+            get_unsaved_kb,
+            diff_key_config_kb,
+            diff_raw_config_kb,
+            diff_unsaved_config_kb,
+            create_snapshot_kb,
+            restore_snapshot_kb,
             get_firmware_kb_version,
             get_key_calibrate_status_kb,
             get_debug_value_part_kb,
@@ -467,9 +585,41 @@ fn main() -> AnyResult<()> {
             connect_device,
             load_preset_kb,
             gen_preset_kb,
+            load_preset_4k,
+            gen_preset_4k,
+            load_preset_3k,
+            gen_preset_3k,
             load_preset_from_file,
+            check_preset,
+            get_preset_schema,
+            encode_preset_code,
+            import_preset_code,
+            merge_presets,
+            translate_preset,
+            apply_preset_to_devices,
+            import_external_profile,
+            get_profile_settings,
+            set_profile_settings,
+            get_window_source,
+            tray::refresh_tray_menu,
             save_preset_to_file,
-            update_firmware_call
+            list_presets,
+            save_preset,
+            rename_preset,
+            update_preset_meta,
+            duplicate_preset,
+            delete_preset,
+            import_presets,
+            import_preset_file,
+            export_preset_file,
+            update_firmware_call,
+            get_firmware_releases,
+            list_snapshots,
+            label_snapshot,
+            delete_snapshot,
+            get_history_limit,
+            set_history_limit,
+            download_firmware
         ])
         .manage(
             Client::builder()
@@ -481,7 +631,10 @@ fn main() -> AnyResult<()> {
         .manage::<Mutex<Option<Meowpad4k<HidDevice>>>>(Mutex::new(None))
         .manage::<Mutex<Option<Meowboard<HidDevice>>>>(Mutex::new(None))
         .manage::<Mutex<Option<IAP>>>(Mutex::new(None))
+        .manage::<Mutex<Option<IapJob>>>(Mutex::new(None))
         .manage::<Mutex<HidApi>>(Mutex::new(HidApi::new().unwrap()))
+        .manage(tray::ActivePresets::default())
+        .manage::<Mutex<profile::Switcher>>(Mutex::new(profile::Switcher::default()))
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
     Ok(())
@@ -0,0 +1,142 @@
+//! 把一个预设同时写入多台同型号的设备
+//!
+//! 每台设备单独读取、合并预设、写入并校验，一台设备失败不影响其他设备。
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+
+/// 要写入的设备
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BroadcastFilter {
+    /// 设备型号，与预设的型号相同
+    pub device_name: String,
+    /// 只写入这些序列号的设备，没有指定时写入该型号的所有设备
+    #[serde(default)]
+    pub serials: Option<Vec<String>>,
+}
+
+impl BroadcastFilter {
+    pub fn matches(&self, device_name: &str, serial_number: Option<&str>) -> bool {
+        device_name == self.device_name
+            && self.serials.as_ref().is_none_or(|s| serial_number.is_some_and(|n| s.iter().any(|s| s == n)))
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum BroadcastStatus {
+    Applied,
+    Failed { message: String },
+    /// `serials` 中指定但没有连接的设备
+    NotFound,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct BroadcastResult {
+    pub serial_number: Option<String>,
+    #[serde(flatten)]
+    pub status: BroadcastStatus,
+}
+
+/// 依次对匹配 `filter` 的设备调用 `apply`，`devices` 为（型号，序列号，设备）
+pub fn broadcast<T>(
+    filter: &BroadcastFilter,
+    devices: impl IntoIterator<Item = (String, Option<String>, T)>,
+    mut apply: impl FnMut(Option<&str>, T) -> Result<()>,
+) -> Vec<BroadcastResult> {
+    let mut results: Vec<BroadcastResult> = devices
+        .into_iter()
+        .filter(|(name, serial, _)| filter.matches(name, serial.as_deref()))
+        .map(|(_, serial_number, device)| {
+            let status = match apply(serial_number.as_deref(), device) {
+                Ok(()) => BroadcastStatus::Applied,
+                Err(e) => {
+                    log::warn!("写入设备 {} 失败：{}", serial_number.as_deref().unwrap_or_default(), e);
+                    BroadcastStatus::Failed { message: e.to_string() }
+                }
+            };
+            BroadcastResult { serial_number, status }
+        })
+        .collect();
+    for serial in filter.serials.iter().flatten() {
+        if !results.iter().any(|r| r.serial_number.as_ref() == Some(serial)) {
+            results.push(BroadcastResult {
+                serial_number: Some(serial.clone()),
+                status: BroadcastStatus::NotFound,
+            });
+        }
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        consts::{MEOWPAD_DEVICE_NAME, PURE64_DEVICE_NAME},
+        error::Error,
+    };
+
+    #[test]
+    fn preset_broadcast() {
+        let devices = || {
+            vec![
+                (PURE64_DEVICE_NAME.to_owned(), Some("A".to_owned()), 1),
+                (MEOWPAD_DEVICE_NAME.to_owned(), Some("B".to_owned()), 2),
+                (PURE64_DEVICE_NAME.to_owned(), Some("C".to_owned()), 3),
+            ]
+        };
+        let all = BroadcastFilter { device_name: PURE64_DEVICE_NAME.to_owned(), serials: None };
+        let mut written = vec![];
+        let results = broadcast(&all, devices(), |_, d| {
+            written.push(d);
+            if d == 3 {
+                return Err(Error::PresetNotFound("x".to_owned()));
+            }
+            Ok(())
+        });
+        assert_eq!(written, vec![1, 3]);
+        assert_eq!(results[0].status, BroadcastStatus::Applied);
+        assert!(matches!(results[1].status, BroadcastStatus::Failed { .. }));
+
+        // 只写入指定序列号的设备，没有连接的序列号单独列出
+        let some = BroadcastFilter {
+            serials: Some(vec!["C".to_owned(), "D".to_owned()]),
+            ..all
+        };
+        let results = broadcast(&some, devices(), |serial, _| {
+            assert_eq!(serial, Some("C"));
+            Ok(())
+        });
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1], BroadcastResult { serial_number: Some("D".to_owned()), status: BroadcastStatus::NotFound });
+        assert!(!some.matches(PURE64_DEVICE_NAME, None));
+    }
+
+    #[test]
+    fn broadcast_by_serial() {
+        let devices = vec![
+            (MEOWPAD_DEVICE_NAME.to_owned(), Some("A".to_owned()), 1),
+            (MEOWPAD_DEVICE_NAME.to_owned(), None, 2),
+            (MEOWPAD_DEVICE_NAME.to_owned(), Some("B".to_owned()), 3),
+        ];
+        let filter = BroadcastFilter {
+            device_name: MEOWPAD_DEVICE_NAME.to_owned(),
+            serials: Some(vec!["B".to_owned()]),
+        };
+        let mut written = vec![];
+        let results = broadcast(&filter, devices.clone(), |_, d| {
+            written.push(d);
+            Ok(())
+        });
+        // 没有序列号的设备不会被指定序列号的筛选选中
+        assert_eq!(written, vec![3]);
+        assert_eq!(results, vec![BroadcastResult { serial_number: Some("B".to_owned()), status: BroadcastStatus::Applied }]);
+
+        // 结果中带有每台设备的序列号
+        let all = BroadcastFilter { serials: None, ..filter };
+        let serials: Vec<_> = broadcast(&all, devices, |_, _| Ok(())).into_iter().map(|r| r.serial_number).collect();
+        assert_eq!(serials, vec![Some("A".to_owned()), None, Some("B".to_owned())]);
+    }
+}
@@ -0,0 +1,134 @@
+//! 预设分享码
+//!
+//! 分享码为 `PCF1-` 加上 base64url（无填充）编码的数据：
+//! 型号 (1字节) | CRC32 (4字节，大端) | deflate压缩的CBOR预设，
+//! CRC32 按型号与压缩前的CBOR计算。
+
+use std::io::{Cursor, Read, Write};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+
+use crate::{
+    consts::{MEOWPAD_DEVICE_NAME, MEOWPAD_SE_DEVICE_NAME, PURE64_DEVICE_NAME},
+    device_preset::DevicePreset,
+    error::{Error, Result},
+};
+
+const PREFIX: &str = "PCF1-";
+/// 解压后的上限，防止异常的分享码占用过多内存
+const MAX_SIZE: u64 = 1 << 20;
+
+fn model_id(device_name: &str) -> Option<u8> {
+    match device_name {
+        d if d == PURE64_DEVICE_NAME => Some(1),
+        d if d == MEOWPAD_DEVICE_NAME => Some(2),
+        d if d == MEOWPAD_SE_DEVICE_NAME => Some(3),
+        _ => None,
+    }
+}
+
+fn model_name(id: u8) -> Option<&'static str> {
+    match id {
+        1 => Some(PURE64_DEVICE_NAME),
+        2 => Some(MEOWPAD_DEVICE_NAME),
+        3 => Some(MEOWPAD_SE_DEVICE_NAME),
+        _ => None,
+    }
+}
+
+fn checksum(model: u8, cbor: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&[model]);
+    hasher.update(cbor);
+    hasher.finalize()
+}
+
+fn invalid(reason: impl ToString) -> Error {
+    Error::InvalidShareCode(reason.to_string())
+}
+
+/// 生成分享码，不包含设备序列号
+pub fn encode(preset: &DevicePreset) -> Result<String> {
+    let model = model_id(&preset.device.device_name).ok_or_else(|| invalid(&preset.device.device_name))?;
+    let mut preset = preset.clone();
+    preset.device.serial_number = None;
+    let mut cbor = vec![];
+    ciborium::ser::into_writer(&preset, &mut cbor).map_err(invalid)?;
+
+    let mut data = vec![model];
+    data.extend(checksum(model, &cbor).to_be_bytes());
+    let mut encoder = DeflateEncoder::new(data, Compression::best());
+    encoder.write_all(&cbor)?;
+    Ok(format!("{}{}", PREFIX, URL_SAFE_NO_PAD.encode(encoder.finish()?)))
+}
+
+/// 解析分享码，属于其他型号的分享码返回 `ShareCodeModelMismatch`，
+/// 分享码中的空白字符会被忽略，方便从聊天记录中复制
+pub fn decode(code: &str, device_name: &str) -> Result<DevicePreset> {
+    let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+    let Some(body) = code.strip_prefix(PREFIX) else {
+        return Err(invalid("无法识别的分享码版本"));
+    };
+    let data = URL_SAFE_NO_PAD.decode(body).map_err(invalid)?;
+    let [model, c0, c1, c2, c3, compressed @ ..] = data.as_slice() else {
+        return Err(invalid("分享码不完整"));
+    };
+    let name = model_name(*model).ok_or_else(|| invalid("未知的设备型号"))?;
+    if name != device_name {
+        return Err(Error::ShareCodeModelMismatch(name.to_owned()));
+    }
+
+    let mut cbor = vec![];
+    DeflateDecoder::new(compressed)
+        .take(MAX_SIZE)
+        .read_to_end(&mut cbor)
+        .map_err(invalid)?;
+    if checksum(*model, &cbor) != u32::from_be_bytes([*c0, *c1, *c2, *c3]) {
+        return Err(invalid("校验失败"));
+    }
+    let preset: DevicePreset = ciborium::de::from_reader(Cursor::new(cbor)).map_err(invalid)?;
+    if preset.device.device_name != name {
+        return Err(invalid("型号与分享码不一致"));
+    }
+    Ok(preset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preset_code() {
+        let mut key = meowpad4k::config::Key::default();
+        key.keys[1].dead_zone = 12;
+        let mut preset = DevicePreset::new("osu", MEOWPAD_DEVICE_NAME, (key, meowpad4k::config::Light::default()));
+        preset.device.serial_number = Some("SN:01".to_owned());
+        preset.meta.tags = vec!["rhythm".to_owned()];
+
+        let code = encode(&preset).unwrap();
+        assert!(code.starts_with(PREFIX));
+        assert!(code.len() < 1000, "{}", code.len());
+        // 从聊天中复制时可能被换行
+        let wrapped = format!("{}\n{}", &code[..20], &code[20..]);
+        let decoded = decode(&wrapped, MEOWPAD_DEVICE_NAME).unwrap();
+        assert_eq!(decoded.id, preset.id);
+        assert_eq!(decoded.meta.tags, preset.meta.tags);
+        assert_eq!(decoded.config.key_configs.unwrap()[1].dead_zone, 12);
+        assert!(decoded.device.serial_number.is_none());
+
+        assert!(matches!(decode(&code, MEOWPAD_SE_DEVICE_NAME), Err(Error::ShareCodeModelMismatch(m)) if m == MEOWPAD_DEVICE_NAME));
+        assert!(matches!(decode(&code.replacen("PCF1", "PCF9", 1), MEOWPAD_DEVICE_NAME), Err(Error::InvalidShareCode(_))));
+
+        // 校验值不一致时拒绝
+        let mut data = URL_SAFE_NO_PAD.decode(&code[PREFIX.len()..]).unwrap();
+        data[1] ^= 0xFF;
+        let corrupted = format!("{}{}", PREFIX, URL_SAFE_NO_PAD.encode(&data));
+        assert!(matches!(decode(&corrupted, MEOWPAD_DEVICE_NAME), Err(Error::InvalidShareCode(_))));
+
+        let kb = DevicePreset::new("kb", PURE64_DEVICE_NAME, meowboard::config::Device::default());
+        let code = encode(&kb).unwrap();
+        assert!(code.len() < 2000, "{}", code.len());
+        assert_eq!(decode(&code, PURE64_DEVICE_NAME).unwrap().config.key_layers.unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,505 @@
+//! 导入以毫米描述行程的通用JSON按键配置
+//!
+//! 支持两种格式，文件中有 `profiles` 数组时导入其中的每个配置：
+//! - 嵌套格式：驼峰命名，顶层为全局的 `actuationPoint` 与 `rapidTrigger`，
+//!   `keys` 按键名覆盖单个按键，`keymap` 为每层的按键改键；
+//! - 按键列表：`keys` 为 `{ key, actuation, rapid_trigger, press, release, bottom_dead_zone, remap, fn_remap }` 的数组，
+//!   顶层的同名字段为全局设置。
+//!
+//! 两种格式都是本程序自定的格式，需要用户按格式手动整理，不兼容任何其他软件的导出文件。
+//!
+//! 行程以毫米为单位，按总行程换算为预设的百分比（Pure64以0.5%为单位）。
+//! 按键按HID键名对应到目标设备普通层或按键绑定中键码相同的按键，与 `preset_translate` 相同。
+
+use std::collections::BTreeMap;
+
+use meowpad::{keycode::KeyValue, KeyCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    consts::PURE64_DEVICE_NAME,
+    device_preset::{DevicePreset, KeyConfig},
+    error::{Error, Result},
+    preset_translate::key_codes,
+};
+
+/// 默认的按键总行程（毫米），与界面中的默认值相同
+pub const DEFAULT_TRAVEL: f64 = 4.0;
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ImportWarning {
+    /// 无法识别的键名
+    UnknownKey { name: String },
+    /// 目标设备上没有对应按键的键名
+    Unmapped { keys: Vec<String> },
+    /// 超出目标型号范围、已被限制的数值
+    Clamped { path: String },
+    /// 目标型号没有的设置，没有被导入
+    Dropped { path: String },
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Conversion {
+    pub preset: DevicePreset,
+    pub warnings: Vec<ImportWarning>,
+}
+
+/// 按键的行程设置，单位为毫米，没有的设置使用全局设置
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+struct Travel {
+    actuation: Option<f64>,
+    rapid_trigger: Option<bool>,
+    press: Option<f64>,
+    release: Option<f64>,
+    bottom_dead_zone: Option<f64>,
+}
+
+impl Travel {
+    fn or(self, d: Travel) -> Travel {
+        Travel {
+            actuation: self.actuation.or(d.actuation),
+            rapid_trigger: self.rapid_trigger.or(d.rapid_trigger),
+            press: self.press.or(d.press),
+            release: self.release.or(d.release),
+            bottom_dead_zone: self.bottom_dead_zone.or(d.bottom_dead_zone),
+        }
+    }
+}
+
+/// 读取后与格式无关的配置
+#[derive(Default)]
+struct SourceProfile {
+    name: String,
+    defaults: Travel,
+    keys: Vec<(String, Travel)>,
+    /// 每层的（按键，改为的按键）
+    layers: Vec<Vec<(String, String)>>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct NestedRapidTrigger {
+    enabled: Option<bool>,
+    press_sensitivity: Option<f64>,
+    release_sensitivity: Option<f64>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct NestedKey {
+    actuation_point: Option<f64>,
+    rapid_trigger: NestedRapidTrigger,
+    bottom_dead_zone: Option<f64>,
+}
+
+impl From<NestedKey> for Travel {
+    fn from(k: NestedKey) -> Self {
+        Travel {
+            actuation: k.actuation_point,
+            rapid_trigger: k.rapid_trigger.enabled,
+            press: k.rapid_trigger.press_sensitivity,
+            release: k.rapid_trigger.release_sensitivity,
+            bottom_dead_zone: k.bottom_dead_zone,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NestedProfile {
+    #[serde(default)]
+    name: String,
+    #[serde(flatten)]
+    global: NestedKey,
+    #[serde(default)]
+    keys: BTreeMap<String, NestedKey>,
+    #[serde(default)]
+    keymap: Vec<BTreeMap<String, String>>,
+}
+
+impl From<NestedProfile> for SourceProfile {
+    fn from(p: NestedProfile) -> Self {
+        SourceProfile {
+            name: p.name,
+            defaults: p.global.into(),
+            keys: p.keys.into_iter().map(|(name, k)| (name, k.into())).collect(),
+            layers: p.keymap.into_iter().map(|l| l.into_iter().collect()).collect(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ListKey {
+    key: String,
+    #[serde(flatten)]
+    travel: Travel,
+    remap: Option<String>,
+    fn_remap: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ListProfile {
+    #[serde(default)]
+    name: String,
+    #[serde(flatten)]
+    global: Travel,
+    keys: Vec<ListKey>,
+}
+
+impl From<ListProfile> for SourceProfile {
+    fn from(p: ListProfile) -> Self {
+        let mut layers = vec![vec![], vec![]];
+        for k in &p.keys {
+            for (layer, remap) in [&k.remap, &k.fn_remap].into_iter().enumerate() {
+                if let Some(to) = remap {
+                    layers[layer].push((k.key.clone(), to.clone()));
+                }
+            }
+        }
+        SourceProfile {
+            name: p.name,
+            defaults: p.global,
+            keys: p.keys.into_iter().map(|k| (k.key, k.travel)).collect(),
+            layers,
+        }
+    }
+}
+
+fn read_profile(value: Value) -> Result<SourceProfile> {
+    let Some(object) = value.as_object() else {
+        return Err(Error::UnsupportedProfile);
+    };
+    if ["actuationPoint", "rapidTrigger", "keymap"].iter().any(|k| object.contains_key(*k)) {
+        Ok(serde_json::from_value::<NestedProfile>(value)?.into())
+    } else if object.get("keys").is_some_and(Value::is_array) {
+        Ok(serde_json::from_value::<ListProfile>(value)?.into())
+    } else {
+        Err(Error::UnsupportedProfile)
+    }
+}
+
+/// 浏览器 `KeyboardEvent.code`、QMK与USB HID规范中的键名，其余键名与 `KeyCode` 相同
+const ALIASES: &[(&str, KeyCode)] = &[
+    ("NONE", KeyCode::None),
+    ("NO", KeyCode::None),
+    ("ESC", KeyCode::Escape),
+    ("BACKSPACE", KeyCode::BSpace),
+    ("BSPC", KeyCode::BSpace),
+    ("RETURN", KeyCode::Enter),
+    ("ENT", KeyCode::Enter),
+    ("SPC", KeyCode::Space),
+    ("SPACEBAR", KeyCode::Space),
+    ("EQL", KeyCode::Equal),
+    ("MINS", KeyCode::Minus),
+    ("BRACKETLEFT", KeyCode::LBracket),
+    ("LBRC", KeyCode::LBracket),
+    ("BRACKETRIGHT", KeyCode::RBracket),
+    ("RBRC", KeyCode::RBracket),
+    ("BACKSLASH", KeyCode::Bslash),
+    ("BSLS", KeyCode::Bslash),
+    ("SEMICOLON", KeyCode::SColon),
+    ("SCLN", KeyCode::SColon),
+    ("QUOT", KeyCode::Quote),
+    ("BACKQUOTE", KeyCode::Grave),
+    ("GRV", KeyCode::Grave),
+    ("COMM", KeyCode::Comma),
+    ("PERIOD", KeyCode::Dot),
+    ("SLSH", KeyCode::Slash),
+    ("CAPS", KeyCode::CapsLock),
+    ("PRINTSCREEN", KeyCode::PScreen),
+    ("PSCR", KeyCode::PScreen),
+    ("INS", KeyCode::Insert),
+    ("DEL", KeyCode::Delete),
+    ("PAGEUP", KeyCode::PgUp),
+    ("PAGEDOWN", KeyCode::PgDown),
+    ("PGDN", KeyCode::PgDown),
+    ("ARROWRIGHT", KeyCode::Right),
+    ("RGHT", KeyCode::Right),
+    ("ARROWLEFT", KeyCode::Left),
+    ("ARROWDOWN", KeyCode::Down),
+    ("ARROWUP", KeyCode::Up),
+    ("KPDIVIDE", KeyCode::KpSlash),
+    ("KPMULTIPLY", KeyCode::KpAsterisk),
+    ("KPSUBTRACT", KeyCode::KpMinus),
+    ("KPADD", KeyCode::KpPlus),
+    ("KPDECIMAL", KeyCode::KpDot),
+    ("INTLBACKSLASH", KeyCode::NonUsBslash),
+    ("NUBS", KeyCode::NonUsBslash),
+    ("CONTEXTMENU", KeyCode::Application),
+    ("APP", KeyCode::Application),
+    ("CONTROLLEFT", KeyCode::LCtrl),
+    ("LEFTCONTROL", KeyCode::LCtrl),
+    ("LCTL", KeyCode::LCtrl),
+    ("SHIFTLEFT", KeyCode::LShift),
+    ("LEFTSHIFT", KeyCode::LShift),
+    ("LSFT", KeyCode::LShift),
+    ("ALTLEFT", KeyCode::LAlt),
+    ("LEFTALT", KeyCode::LAlt),
+    ("METALEFT", KeyCode::LGui),
+    ("LEFTGUI", KeyCode::LGui),
+    ("LWIN", KeyCode::LGui),
+    ("CONTROLRIGHT", KeyCode::RCtrl),
+    ("RIGHTCONTROL", KeyCode::RCtrl),
+    ("RCTL", KeyCode::RCtrl),
+    ("SHIFTRIGHT", KeyCode::RShift),
+    ("RIGHTSHIFT", KeyCode::RShift),
+    ("RSFT", KeyCode::RShift),
+    ("ALTRIGHT", KeyCode::RAlt),
+    ("RIGHTALT", KeyCode::RAlt),
+    ("METARIGHT", KeyCode::RGui),
+    ("RIGHTGUI", KeyCode::RGui),
+    ("RWIN", KeyCode::RGui),
+];
+
+/// HID键名对应的键码，例如 `KeyZ`、`KC_Z`、`Z`，不区分大小写
+pub fn key_code(name: &str) -> Option<KeyCode> {
+    let upper = name.trim().to_ascii_uppercase();
+    let upper = upper.strip_prefix("KC_").unwrap_or(&upper);
+    let mut name: String = upper.chars().filter(|c| *c != '_' && *c != ' ').collect();
+    // KeyA、Digit1、Numpad1
+    for prefix in ["KEY", "DIGIT"] {
+        if let Some(rest) = name.strip_prefix(prefix).filter(|r| r.len() == 1) {
+            name = rest.to_owned();
+        }
+    }
+    if let Some(rest) = name.strip_prefix("NUMPAD") {
+        name = format!("KP{}", rest);
+    }
+    if name.len() == 1 && name.as_bytes()[0].is_ascii_digit() {
+        name = format!("KB{}", name);
+    }
+    if let Some((_, code)) = ALIASES.iter().find(|(alias, _)| *alias == name) {
+        return Some(*code);
+    }
+    (1..=u8::MAX)
+        .map(KeyCode::from)
+        .find(|code| *code != KeyCode::None && format!("{:?}", code).eq_ignore_ascii_case(&name))
+}
+
+struct Converter {
+    /// 每毫米的单位数
+    per_mm: f64,
+    max: u32,
+    unmapped: Vec<String>,
+    warnings: Vec<ImportWarning>,
+}
+
+impl Converter {
+    fn warn(&mut self, warning: ImportWarning) {
+        if !self.warnings.contains(&warning) {
+            self.warnings.push(warning);
+        }
+    }
+
+    fn units(&mut self, path: String, mm: f64, min: u32) -> u32 {
+        let value = (mm * self.per_mm).round();
+        let clamped = value.clamp(min as f64, self.max as f64);
+        if clamped != value {
+            self.warn(ImportWarning::Clamped { path });
+        }
+        clamped as u32
+    }
+
+    fn position(&mut self, codes: &[Option<KeyCode>], name: &str) -> Option<usize> {
+        let Some(code) = key_code(name) else {
+            self.warn(ImportWarning::UnknownKey { name: name.to_owned() });
+            return None;
+        };
+        let position = codes.iter().position(|c| *c == Some(code));
+        if position.is_none() && !self.unmapped.iter().any(|n| n == name) {
+            self.unmapped.push(name.to_owned());
+        }
+        position
+    }
+
+    /// 开启RT时触发点为死区，按下与抬起为RT的行程；关闭RT时按下与抬起都在触发点。
+    /// Meowpad没有RT开关，关闭RT的按键同样把按下与抬起设为触发点
+    fn key(&mut self, i: usize, k: &mut KeyConfig, t: Travel) {
+        let path = |f: &str| format!("key_configs[{}].{}", i, f);
+        let rt = match (k.rt_enabled, t.rapid_trigger) {
+            (Some(current), rt) => rt.unwrap_or(current),
+            (None, Some(false)) => {
+                self.warn(ImportWarning::Dropped { path: path("rt_enabled") });
+                false
+            }
+            (None, _) => true,
+        };
+        if k.rt_enabled.is_some() {
+            k.rt_enabled = Some(rt);
+        }
+        if rt {
+            if let Some(mm) = t.actuation {
+                k.dead_zone = self.units(path("dead_zone"), mm, 0);
+            }
+            if let Some(mm) = t.press {
+                k.press_percentage = self.units(path("press_percentage"), mm, 1);
+            }
+            if let Some(mm) = t.release.or(t.press) {
+                k.release_percentage = self.units(path("release_percentage"), mm, 1);
+            }
+        } else if let Some(mm) = t.actuation {
+            k.press_percentage = self.units(path("press_percentage"), mm, 1);
+            k.release_percentage = k.press_percentage;
+        }
+        match (t.bottom_dead_zone, k.rt_enabled) {
+            (Some(mm), Some(_)) => k.release_dead_zone = self.units(path("release_dead_zone"), mm, 0),
+            (Some(_), None) => self.warn(ImportWarning::Dropped { path: "key_configs.release_dead_zone".to_owned() }),
+            _ => {}
+        }
+    }
+}
+
+/// 把读取的配置转换为 `target` 的型号，没有设置的部分保持 `target` 的配置
+fn convert(source: SourceProfile, target: &DevicePreset, travel: f64, name: &str) -> Conversion {
+    let pure64 = target.device.device_name == PURE64_DEVICE_NAME;
+    let (per_percentage, max) = if pure64 { (2.0, 200) } else { (1.0, 100) };
+    let mut c = Converter {
+        per_mm: 100.0 * per_percentage / travel,
+        max,
+        unmapped: vec![],
+        warnings: vec![],
+    };
+    let codes = key_codes(target);
+    let mut config = target.config.clone();
+
+    if let Some(dst) = config.key_configs.as_mut() {
+        let mut travels = vec![source.defaults; dst.len()];
+        for (name, t) in &source.keys {
+            if let Some(t0) = c.position(&codes, name).and_then(|i| travels.get_mut(i)) {
+                *t0 = t.or(source.defaults);
+            }
+        }
+        for (i, (k, t)) in dst.iter_mut().zip(travels).enumerate() {
+            c.key(i, k, t);
+        }
+    }
+
+    for (layer, remaps) in source.layers.iter().enumerate() {
+        for (from, to) in remaps {
+            let Some(i) = c.position(&codes, from) else { continue };
+            let Some(code) = key_code(to) else {
+                c.warn(ImportWarning::UnknownKey { name: to.clone() });
+                continue;
+            };
+            if let Some(l) = config.key_layers.as_mut().and_then(|l| l.get_mut(layer)) {
+                if let Some(k) = l.keys.get_mut(i) {
+                    *k = KeyValue::from(code);
+                }
+            } else if let (0, Some(data)) = (layer, config.key_data.as_mut()) {
+                if let Some(d) = data.get_mut(i) {
+                    *d = [code, KeyCode::None, KeyCode::None, KeyCode::None, KeyCode::None, KeyCode::None];
+                }
+            } else {
+                c.warn(ImportWarning::Dropped { path: format!("key_layers[{}]", layer) });
+            }
+        }
+    }
+
+    if !c.unmapped.is_empty() {
+        let keys = std::mem::take(&mut c.unmapped);
+        c.warnings.insert(0, ImportWarning::Unmapped { keys });
+    }
+    let name = if source.name.is_empty() { name } else { &source.name };
+    Conversion {
+        preset: DevicePreset::new(name, target.device.clone(), config),
+        warnings: c.warnings,
+    }
+}
+
+/// 读取JSON按键配置并转换为 `target` 的型号，`travel` 为按键总行程（毫米），
+/// 配置中没有名称时使用 `name`
+pub fn import(text: &str, target: &DevicePreset, travel: f64, name: &str) -> Result<Vec<Conversion>> {
+    let value: Value = serde_json::from_str(text)?;
+    let profiles = match value {
+        Value::Object(mut o) if o.contains_key("profiles") => match o.remove("profiles") {
+            Some(Value::Array(profiles)) => profiles,
+            _ => return Err(Error::UnsupportedProfile),
+        },
+        value => vec![value],
+    };
+    profiles
+        .into_iter()
+        .map(|p| Ok(convert(read_profile(p)?, target, travel, name)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::MEOWPAD_DEVICE_NAME;
+
+    #[test]
+    fn key_names() {
+        for name in ["KeyZ", "KC_Z", "z", "Z"] {
+            assert_eq!(key_code(name), Some(KeyCode::Z));
+        }
+        assert_eq!(key_code("Digit1"), Some(KeyCode::Kb1));
+        assert_eq!(key_code("ShiftLeft"), Some(KeyCode::LShift));
+        assert_eq!(key_code("KC_LSFT"), Some(KeyCode::LShift));
+        assert_eq!(key_code("Numpad5"), Some(KeyCode::Kp5));
+        assert_eq!(key_code("PageDown"), Some(KeyCode::PgDown));
+        assert_eq!(key_code("F12"), Some(KeyCode::F12));
+        assert_eq!(key_code("none"), Some(KeyCode::None));
+        assert_eq!(key_code("Hyper"), None);
+    }
+
+    #[test]
+    fn preset_import() {
+        let pure64 = DevicePreset::new("kb", PURE64_DEVICE_NAME, meowboard::config::Device::default());
+        let codes = key_codes(&pure64);
+        let (z, caps) = (
+            codes.iter().position(|c| *c == Some(KeyCode::Z)).unwrap(),
+            codes.iter().position(|c| *c == Some(KeyCode::CapsLock)).unwrap(),
+        );
+        let nested = r#"{
+            "name": "osu",
+            "actuationPoint": 2.0,
+            "rapidTrigger": { "enabled": false },
+            "keys": {
+                "KeyZ": { "actuationPoint": 0.4, "rapidTrigger": { "enabled": true, "pressSensitivity": 0.1, "releaseSensitivity": 0.15 } },
+                "F13": { "actuationPoint": 1.0 },
+                "Hyper": {}
+            },
+            "keymap": [{ "CapsLock": "Escape" }]
+        }"#;
+        let [c] = &import(nested, &pure64, DEFAULT_TRAVEL, "file").unwrap()[..] else { panic!() };
+        assert_eq!(c.preset.name, "osu");
+        let keys = c.preset.config.key_configs.as_ref().unwrap();
+        // 4mm行程，Pure64以0.5%为单位：0.4mm为20，0.1mm为5
+        assert_eq!((keys[z].dead_zone, keys[z].press_percentage, keys[z].release_percentage), (20, 5, 8));
+        assert_eq!(keys[z].rt_enabled, Some(true));
+        assert_eq!((keys[0].press_percentage, keys[0].rt_enabled), (100, Some(false)));
+        assert_eq!(c.preset.config.key_layers.as_ref().unwrap()[0].keys[caps], KeyValue::Keyboard(KeyCode::Escape));
+        assert_eq!(c.warnings[0], ImportWarning::Unmapped { keys: vec!["F13".to_owned()] });
+        assert!(c.warnings.contains(&ImportWarning::UnknownKey { name: "Hyper".to_owned() }));
+
+        // 按键列表格式导入到Meowpad：以1%为单位，底部死区与Fn层无法导入
+        let mut key = meowpad4k::config::Key::default();
+        for (k, code) in key.keys.iter_mut().zip([KeyCode::Z, KeyCode::X, KeyCode::C, KeyCode::V]) {
+            k.key_data = [code, KeyCode::None, KeyCode::None, KeyCode::None, KeyCode::None, KeyCode::None];
+        }
+        let meowpad = DevicePreset::new("4k", MEOWPAD_DEVICE_NAME, (key, meowpad4k::config::Light::default()));
+        let list = r#"{"profiles": [{
+            "press": 0.2,
+            "keys": [
+                { "key": "KC_X", "actuation": 5.0, "bottom_dead_zone": 0.2, "remap": "KC_A", "fn_remap": "KC_B" },
+                { "key": "KC_C", "actuation": 1.0, "rapid_trigger": false }
+            ]
+        }]}"#;
+        let [c] = &import(list, &meowpad, DEFAULT_TRAVEL, "file").unwrap()[..] else { panic!() };
+        assert_eq!(c.preset.name, "file");
+        let keys = c.preset.config.key_configs.as_ref().unwrap();
+        assert_eq!((keys[0].press_percentage, keys[1].press_percentage, keys[1].dead_zone), (5, 5, 100));
+        assert_eq!(c.preset.config.key_data.as_ref().unwrap()[1][0], KeyCode::A);
+        assert!(c.warnings.contains(&ImportWarning::Clamped { path: "key_configs[1].dead_zone".to_owned() }));
+        assert!(c.warnings.contains(&ImportWarning::Dropped { path: "key_configs.release_dead_zone".to_owned() }));
+        // 关闭RT的按键按下与抬起都在触发点，并提示该按键无法关闭RT
+        assert_eq!((keys[2].press_percentage, keys[2].release_percentage), (25, 25));
+        assert!(c.warnings.contains(&ImportWarning::Dropped { path: "key_configs[2].rt_enabled".to_owned() }));
+        assert!(c.warnings.contains(&ImportWarning::Dropped { path: "key_layers[1]".to_owned() }));
+
+        assert!(matches!(import(r#"{"foo": 1}"#, &pure64, DEFAULT_TRAVEL, "file"), Err(Error::UnsupportedProfile)));
+    }
+}
@@ -0,0 +1,257 @@
+use std::{
+    fs, io,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    device_preset::{DevicePreset, PRESET_VERSION},
+    error::{Error, Result},
+};
+
+/// 预设库的筛选条件，为空的条件不参与筛选
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PresetFilter {
+    /// 在名称、作者、备注与标签中查找，不区分大小写
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub device_name: Option<String>,
+    /// 需要包含全部标签
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl PresetFilter {
+    pub fn matches(&self, preset: &DevicePreset) -> bool {
+        if self.device_name.as_ref().is_some_and(|d| *d != preset.device.device_name) {
+            return false;
+        }
+        if !self.tags.iter().all(|t| preset.meta.tags.contains(t)) {
+            return false;
+        }
+        let Some(text) = self.text.as_ref().map(|t| t.to_lowercase()).filter(|t| !t.is_empty()) else {
+            return true;
+        };
+        [Some(&preset.name), preset.meta.author.as_ref(), preset.meta.notes.as_ref()]
+            .into_iter()
+            .flatten()
+            .chain(&preset.meta.tags)
+            .any(|s| s.to_lowercase().contains(&text))
+    }
+}
+
+/// 导入时与库中预设的关系
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportStatus {
+    Added,
+    /// 同一预设，只合并了标签等信息
+    Merged,
+    /// 导入的预设较新，替换了库中的配置
+    Updated,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ImportResult {
+    pub status: ImportStatus,
+    pub preset: DevicePreset,
+}
+
+/// 保存在本地的预设，每个预设一个文件，按 `DevicePreset.id` 命名
+pub struct PresetLibrary {
+    dir: PathBuf,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+impl PresetLibrary {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// ID直接用作文件名，只允许字母、数字、`-` 与 `_`，例如UUID
+    fn path(&self, id: &str) -> Result<PathBuf> {
+        let valid = (1..=64).contains(&id.len())
+            && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(Error::InvalidPresetId(id.to_owned()));
+        }
+        Ok(self.dir.join(format!("{}.pcf", id)))
+    }
+
+    fn write(&self, preset: &DevicePreset) -> Result<()> {
+        let path = self.path(&preset.id)?;
+        fs::create_dir_all(&self.dir)?;
+        fs::write(path, serde_json::to_vec_pretty(preset)?)?;
+        Ok(())
+    }
+
+    pub fn get(&self, id: &str) -> Result<DevicePreset> {
+        match fs::read(self.path(id)?) {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Err(Error::PresetNotFound(id.to_owned())),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// 按修改时间从新到旧排列，无法解析的文件会被跳过
+    pub fn list(&self, filter: &PresetFilter) -> Result<Vec<DevicePreset>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+        let mut presets = vec![];
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == "pcf") {
+                match fs::read(&path).map_err(Error::from).and_then(|d| Ok(serde_json::from_slice::<DevicePreset>(&d)?)) {
+                    Ok(p) if filter.matches(&p) => presets.push(p),
+                    Ok(_) => {}
+                    Err(e) => log::warn!("无法读取预设 {}：{}", path.display(), e),
+                }
+            }
+        }
+        presets.sort_by(|a, b| b.meta.modified.cmp(&a.meta.modified).then_with(|| a.name.cmp(&b.name)));
+        Ok(presets)
+    }
+
+    /// 保存新的或修改过的预设，更新修改时间与格式版本
+    pub fn save(&self, mut preset: DevicePreset) -> Result<DevicePreset> {
+        preset.version = PRESET_VERSION;
+        preset.meta.modified = now();
+        if preset.meta.created == 0 {
+            preset.meta.created = preset.meta.modified;
+        }
+        self.write(&preset)?;
+        Ok(preset)
+    }
+
+    pub fn rename(&self, id: &str, name: String) -> Result<DevicePreset> {
+        let mut preset = self.get(id)?;
+        preset.name = name;
+        self.save(preset)
+    }
+
+    /// 复制为新的预设，使用新的ID
+    pub fn duplicate(&self, id: &str, name: String) -> Result<DevicePreset> {
+        let mut preset = self.get(id)?;
+        preset.id = Uuid::new_v4().to_string();
+        preset.name = name;
+        preset.meta.created = 0;
+        self.save(preset)
+    }
+
+    pub fn remove(&self, id: &str) -> Result<()> {
+        match fs::remove_file(self.path(id)?) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Err(Error::PresetNotFound(id.to_owned())),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// 库中已有相同ID的预设时合并：标签取并集，配置与名称等取修改时间较新的一方
+    pub fn import(&self, mut preset: DevicePreset) -> Result<ImportResult> {
+        // 旧版本的预设可以直接升级
+        preset.version = preset.version.max(PRESET_VERSION);
+        let existing = match self.get(&preset.id) {
+            Ok(p) => p,
+            Err(Error::PresetNotFound(_)) => {
+                if preset.meta.created == 0 {
+                    preset.meta.created = now();
+                }
+                if preset.meta.modified == 0 {
+                    preset.meta.modified = preset.meta.created;
+                }
+                self.write(&preset)?;
+                return Ok(ImportResult { status: ImportStatus::Added, preset });
+            }
+            Err(e) => return Err(e),
+        };
+
+        let same_config = serde_json::to_value(&existing.config)? == serde_json::to_value(&preset.config)?;
+        let (status, mut merged, other) = if !same_config && preset.meta.modified > existing.meta.modified {
+            (ImportStatus::Updated, preset, existing)
+        } else {
+            (ImportStatus::Merged, existing, preset)
+        };
+        for tag in other.meta.tags {
+            if !merged.meta.tags.contains(&tag) {
+                merged.meta.tags.push(tag);
+            }
+        }
+        merged.meta.author = merged.meta.author.or(other.meta.author);
+        merged.meta.notes = merged.meta.notes.or(other.meta.notes);
+        if other.meta.created > 0 && (merged.meta.created == 0 || other.meta.created < merged.meta.created) {
+            merged.meta.created = other.meta.created;
+        }
+        self.write(&merged)?;
+        Ok(ImportResult { status, preset: merged })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("preset-test-{}", Uuid::new_v4()))
+    }
+
+    #[test]
+    fn preset_library() {
+        let dir = library_dir();
+        let library = PresetLibrary::new(&dir);
+        let mut preset = DevicePreset::new("osu", "Pure64", meowboard::config::Device::default());
+        preset.meta.tags = vec!["rhythm".to_owned()];
+        let preset = library.save(preset).unwrap();
+        library.save(DevicePreset::new("daily", "Meowpad", (meowpad4k::config::Key::default(), meowpad4k::config::Light::default()))).unwrap();
+
+        let filter = |text: &str, device: Option<&str>| PresetFilter {
+            text: Some(text.to_owned()),
+            device_name: device.map(str::to_owned),
+            tags: vec![],
+        };
+        assert_eq!(library.list(&PresetFilter::default()).unwrap().len(), 2);
+        assert_eq!(library.list(&filter("RHY", None)).unwrap()[0].id, preset.id);
+        assert!(library.list(&filter("osu", Some("Meowpad"))).unwrap().is_empty());
+
+        let copy = library.duplicate(&preset.id, "osu 2".to_owned()).unwrap();
+        assert_ne!(copy.id, preset.id);
+        assert_eq!(library.list(&filter("osu", None)).unwrap().len(), 2);
+
+        // 相同ID的预设合并标签，配置不同时保留较新的一方
+        let mut incoming = preset.clone();
+        incoming.meta.tags = vec!["shared".to_owned()];
+        incoming.meta.modified = preset.meta.modified + 10;
+        let result = library.import(incoming.clone()).unwrap();
+        assert_eq!(result.status, ImportStatus::Merged);
+        assert_eq!(result.preset.meta.tags, vec!["rhythm".to_owned(), "shared".to_owned()]);
+        incoming.config.key_configs.as_mut().unwrap()[0].dead_zone = 5;
+        incoming.name = "osu (shared)".to_owned();
+        let result = library.import(incoming).unwrap();
+        assert_eq!(result.status, ImportStatus::Updated);
+        assert_eq!(library.get(&preset.id).unwrap().name, "osu (shared)");
+        assert_eq!(library.list(&PresetFilter::default()).unwrap().len(), 3);
+
+        library.remove(&copy.id).unwrap();
+        assert!(matches!(library.get(&copy.id), Err(Error::PresetNotFound(_))));
+
+        // 不能用作文件名的ID
+        for id in ["", "../osu", "a/b", "a.b", "预设"] {
+            assert!(matches!(library.get(id), Err(Error::InvalidPresetId(_))));
+        }
+        let mut invalid = preset.clone();
+        invalid.id = "a:b".to_owned();
+        assert!(matches!(library.import(invalid), Err(Error::InvalidPresetId(_))));
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,168 @@
+//! 部分应用预设与合并两个预设
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::device_preset::{DevicePreset, DevicePresetConfig};
+
+/// 应用预设时选择的部分，默认全部应用
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct PresetSelection {
+    /// 按键映射的层，0为普通层，1为Fn层
+    pub layers: Vec<usize>,
+    /// 按键行程与RT设置
+    pub key_configs: bool,
+    /// Meowpad的按键绑定与侧键
+    pub key_data: bool,
+    /// Meowpad的全局按键设置
+    pub key_settings: bool,
+    pub light: bool,
+    /// 只应用这些按键的映射、行程与绑定，为空时应用全部按键
+    pub keys: Option<Vec<usize>>,
+}
+
+impl Default for PresetSelection {
+    fn default() -> Self {
+        Self {
+            layers: vec![0, 1],
+            key_configs: true,
+            key_data: true,
+            key_settings: true,
+            light: true,
+            keys: None,
+        }
+    }
+}
+
+impl PresetSelection {
+    pub fn layer(&self, layer: usize) -> bool {
+        self.layers.contains(&layer)
+    }
+
+    pub fn key(&self, index: usize) -> bool {
+        self.keys.as_ref().is_none_or(|k| k.contains(&index))
+    }
+}
+
+/// 两个预设不同时选择的一方
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Side {
+    Base,
+    Other,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum MergeResult {
+    Merged { preset: Box<DevicePreset> },
+    /// 没有指定选择的冲突，例如 `key_layers[1]`、`key_configs[3]`、`light`，不会生成预设
+    Conflicts { conflicts: Vec<String> },
+}
+
+struct Merger<'a> {
+    resolutions: &'a BTreeMap<String, Side>,
+    conflicts: Vec<String>,
+}
+
+impl Merger<'_> {
+    /// 冲突按部分的路径查找选择，找不到时再按分组查找，例如 `key_configs` 对所有按键生效
+    fn pick<T: Serialize + Clone>(&mut self, part: String, group: &str, base: Option<&T>, other: Option<&T>) -> Option<T> {
+        match (base, other) {
+            (Some(b), Some(o)) if serde_json::to_value(b).ok() != serde_json::to_value(o).ok() => {
+                match self.resolutions.get(&part).or_else(|| self.resolutions.get(group)) {
+                    Some(Side::Other) => Some(o.clone()),
+                    Some(Side::Base) => Some(b.clone()),
+                    None => {
+                        self.conflicts.push(part);
+                        Some(b.clone())
+                    }
+                }
+            }
+            (b, o) => b.or(o).cloned(),
+        }
+    }
+
+    /// 按元素合并，长度不同时整体作为一个部分
+    fn pick_each<T: Serialize + Clone>(&mut self, group: &str, base: Option<&Vec<T>>, other: Option<&Vec<T>>) -> Option<Vec<T>> {
+        match (base, other) {
+            (Some(b), Some(o)) if b.len() == o.len() => Some(
+                b.iter()
+                    .zip(o)
+                    .enumerate()
+                    .map(|(i, (b, o))| self.pick(format!("{}[{}]", group, i), group, Some(b), Some(o)).unwrap())
+                    .collect(),
+            ),
+            (b, o) => self.pick(group.to_owned(), group, b, o),
+        }
+    }
+}
+
+/// 合并同一型号的两个预设，只有一方有的部分直接使用，双方不同的部分按 `resolutions` 选择
+pub fn merge(base: &DevicePreset, other: &DevicePreset, name: &str, resolutions: &BTreeMap<String, Side>) -> MergeResult {
+    let mut m = Merger { resolutions, conflicts: vec![] };
+    let (b, o) = (&base.config, &other.config);
+    let config = DevicePresetConfig {
+        key_layers: m.pick_each("key_layers", b.key_layers.as_ref(), o.key_layers.as_ref()),
+        key_configs: m.pick_each("key_configs", b.key_configs.as_ref(), o.key_configs.as_ref()),
+        key_data: m.pick_each("key_data", b.key_data.as_ref(), o.key_data.as_ref()),
+        side_btn: m.pick("side_btn".to_owned(), "side_btn", b.side_btn.as_ref(), o.side_btn.as_ref()),
+        key_settings: m.pick("key_settings".to_owned(), "key_settings", b.key_settings.as_ref(), o.key_settings.as_ref()),
+        light: m.pick("light".to_owned(), "light", b.light.as_ref(), o.light.as_ref()),
+    };
+    if !m.conflicts.is_empty() {
+        return MergeResult::Conflicts { conflicts: m.conflicts };
+    }
+
+    let mut preset = DevicePreset::new(name, base.device.clone(), config);
+    preset.meta.tags = base.meta.tags.clone();
+    for tag in &other.meta.tags {
+        if !preset.meta.tags.contains(tag) {
+            preset.meta.tags.push(tag.clone());
+        }
+    }
+    MergeResult::Merged { preset: Box::new(preset) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::PURE64_DEVICE_NAME;
+    use meowpad::{keycode::KeyValue, KeyCode};
+
+    #[test]
+    fn preset_merge() {
+        let default = meowboard::config::Device::default();
+        let mut rt = default;
+        rt.keys[3].press_percentage = 20;
+        let mut keymap = default;
+        keymap.fn_layer[0] = KeyValue::Keyboard(KeyCode::F1);
+        keymap.keys[5].dead_zone = 10;
+        let rt = DevicePreset::new("rt", PURE64_DEVICE_NAME, rt);
+        let keymap = DevicePreset::new("keymap", PURE64_DEVICE_NAME, keymap);
+
+        let MergeResult::Conflicts { conflicts } = merge(&rt, &keymap, "merged", &BTreeMap::new()) else {
+            panic!("没有选择时应给出冲突");
+        };
+        assert_eq!(conflicts, vec!["key_layers[1]", "key_configs[3]", "key_configs[5]"]);
+
+        // RT设置取第一个预设，Fn层取第二个预设
+        let resolutions = BTreeMap::from([
+            ("key_configs".to_owned(), Side::Base),
+            ("key_layers[1]".to_owned(), Side::Other),
+        ]);
+        let MergeResult::Merged { preset } = merge(&rt, &keymap, "merged", &resolutions) else {
+            panic!("所有冲突都已选择");
+        };
+        let config = preset.config;
+        assert_eq!(config.key_configs.as_ref().unwrap()[3].press_percentage, 20);
+        assert_eq!(config.key_configs.as_ref().unwrap()[5].dead_zone, default.keys[5].dead_zone as u32);
+        assert_eq!(config.key_layers.unwrap()[1].keys[0], KeyValue::Keyboard(KeyCode::F1));
+
+        let selection = PresetSelection { layers: vec![1], keys: Some(vec![0, 2]), ..Default::default() };
+        assert!(selection.layer(1) && !selection.layer(0));
+        assert!(selection.key(2) && !selection.key(1));
+    }
+}
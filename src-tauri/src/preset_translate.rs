@@ -0,0 +1,294 @@
+//! 不同型号之间的预设转换
+//!
+//! 源预设中的按键按 [`KeyMapping`] 写到目标预设对应的按键上，没有对应的按键保持目标的设置。
+//! 默认按普通层或按键绑定中的键码对应，例如绑定为Z/X/C/V的Meowpad按键对应Pure64上的Z/X/C/V键。
+//! 行程与死区按两个型号的单位换算，换算后超出目标型号范围的数值截断到范围内并给出警告。
+
+use std::collections::BTreeMap;
+
+use meowpad::{keycode::KeyValue, KeyCode};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    consts::{MEOWPAD_DEVICE_NAME, PURE64_DEVICE_NAME},
+    device_preset::{DevicePreset, PresetLight},
+};
+
+/// 源按键序号到目标按键序号
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct KeyMapping(pub BTreeMap<usize, usize>);
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TranslateWarning {
+    /// 在目标设备上没有对应按键的源按键
+    Unmapped { keys: Vec<usize> },
+    /// 目标型号没有的设置，没有被转换
+    Dropped { path: String },
+    /// 无法完整转换的按键绑定，例如Meowpad的组合键或Pure64的鼠标键，按键序号为源按键
+    Binding { key: usize },
+    /// 换算后超出目标型号范围、被截断的数值
+    Clamped { path: String },
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Translation {
+    pub preset: DevicePreset,
+    pub mapping: KeyMapping,
+    pub warnings: Vec<TranslateWarning>,
+}
+
+/// 每个按键在普通层或按键绑定中的主键码，修饰键不参与对应
+pub fn key_codes(preset: &DevicePreset) -> Vec<Option<KeyCode>> {
+    let config = &preset.config;
+    if let Some(layer) = config.key_layers.as_ref().and_then(|l| l.first()) {
+        layer
+            .keys
+            .iter()
+            .map(|k| match k {
+                KeyValue::Keyboard(code) => Some(*code),
+                _ => None,
+            })
+            .collect()
+    } else if let Some(data) = &config.key_data {
+        data.iter().map(main_code).collect()
+    } else {
+        vec![]
+    }
+}
+
+fn main_code(data: &[KeyCode; 6]) -> Option<KeyCode> {
+    data.iter().copied().find(|k| *k != KeyCode::None && !k.is_modifier())
+}
+
+/// 按键码对应源与目标的按键，键码相同的第一个目标按键
+pub fn mapping_by_keycode(source: &DevicePreset, target: &DevicePreset) -> KeyMapping {
+    let target_codes = key_codes(target);
+    KeyMapping(
+        key_codes(source)
+            .iter()
+            .enumerate()
+            .filter_map(|(i, code)| {
+                let code = (*code)?;
+                target_codes.iter().position(|c| *c == Some(code)).map(|j| (i, j))
+            })
+            .collect(),
+    )
+}
+
+/// 防抖时间的单位：Meowpad 4K为1/8 ms，Meowpad SE为1 ms
+fn jitters_per_ms(device_name: &str) -> u32 {
+    if device_name == MEOWPAD_DEVICE_NAME {
+        8
+    } else {
+        1
+    }
+}
+
+/// 行程与死区的单位：Pure64为0.5%，Meowpad为1%
+fn travel_per_percent(device_name: &str) -> u32 {
+    if device_name == PURE64_DEVICE_NAME {
+        2
+    } else {
+        1
+    }
+}
+
+/// 按两个型号的单位换算行程，四舍五入后截断到 `min` 与100%之间
+fn convert_travel(value: u32, (from, to): (u32, u32), min: u32, path: String, warnings: &mut Vec<TranslateWarning>) -> u32 {
+    let converted = (value.saturating_mul(to) + from / 2) / from;
+    let clamped = converted.clamp(min, 100 * to);
+    if clamped != converted {
+        warnings.push(TranslateWarning::Clamped { path });
+    }
+    clamped
+}
+
+/// 把源预设转换到目标预设的型号，`target` 通常由目标设备当前的配置生成，
+/// 没有指定 `mapping` 时按键码对应
+pub fn translate(source: &DevicePreset, target: &DevicePreset, mapping: Option<KeyMapping>) -> Translation {
+    let mapping = mapping.unwrap_or_else(|| mapping_by_keycode(source, target));
+    let mut warnings = vec![];
+    let (s, mut config) = (&source.config, target.config.clone());
+    let dropped = |path: &str| TranslateWarning::Dropped { path: path.to_owned() };
+
+    let source_keys = s.key_configs.as_ref().map_or(0, Vec::len);
+    let unmapped: Vec<usize> = (0..source_keys).filter(|i| !mapping.0.contains_key(i)).collect();
+    if !unmapped.is_empty() {
+        warnings.push(TranslateWarning::Unmapped { keys: unmapped });
+    }
+
+    if let (Some(src), Some(dst)) = (&s.key_configs, config.key_configs.as_mut()) {
+        let (mut release_dead_zone, mut rt_enabled) = (false, false);
+        let units = (travel_per_percent(&source.device.device_name), travel_per_percent(&target.device.device_name));
+        for (&from, &to) in &mapping.0 {
+            let (Some(k), Some(d)) = (src.get(from), dst.get_mut(to)) else { continue };
+            let path = |field: &str| format!("key_configs[{}].{}", to, field);
+            d.press_percentage = convert_travel(k.press_percentage, units, 1, path("press_percentage"), &mut warnings);
+            d.release_percentage = convert_travel(k.release_percentage, units, 1, path("release_percentage"), &mut warnings);
+            d.dead_zone = convert_travel(k.dead_zone, units, 0, path("dead_zone"), &mut warnings);
+            match (k.rt_enabled, d.rt_enabled) {
+                // 只有Pure64有释放死区，单位相同
+                (Some(rt), Some(_)) => {
+                    d.rt_enabled = Some(rt);
+                    d.release_dead_zone = k.release_dead_zone;
+                }
+                (Some(rt), None) => {
+                    release_dead_zone |= k.release_dead_zone != 0;
+                    rt_enabled |= !rt;
+                }
+                _ => {}
+            }
+        }
+        if release_dead_zone {
+            warnings.push(dropped("key_configs.release_dead_zone"));
+        }
+        if rt_enabled {
+            warnings.push(dropped("key_configs.rt_enabled"));
+        }
+    }
+
+    // 按键绑定：Pure64为两层键位，Meowpad为每个按键的组合键
+    match (&s.key_layers, &s.key_data) {
+        (Some(src), _) => {
+            if let Some(dst) = config.key_layers.as_mut() {
+                for (src, dst) in src.iter().zip(dst.iter_mut()) {
+                    for (&from, &to) in &mapping.0 {
+                        if let (Some(k), Some(d)) = (src.keys.get(from), dst.keys.get_mut(to)) {
+                            *d = *k;
+                        }
+                    }
+                }
+            } else if let Some(dst) = config.key_data.as_mut() {
+                let fn_layer = src.get(1);
+                let normal = src.first();
+                for (&from, &to) in &mapping.0 {
+                    let (Some(k), Some(d)) = (normal.and_then(|l| l.keys.get(from)), dst.get_mut(to)) else { continue };
+                    match k {
+                        KeyValue::Keyboard(code) => *d = [*code, KeyCode::None, KeyCode::None, KeyCode::None, KeyCode::None, KeyCode::None],
+                        KeyValue::None => *d = [KeyCode::None; 6],
+                        _ => warnings.push(TranslateWarning::Binding { key: from }),
+                    }
+                }
+                if mapping.0.keys().any(|&from| fn_layer.and_then(|l| l.keys.get(from)).is_some_and(|k| *k != KeyValue::None)) {
+                    warnings.push(dropped("key_layers[1]"));
+                }
+            }
+        }
+        (None, Some(src)) => {
+            if let Some(dst) = config.key_data.as_mut() {
+                for (&from, &to) in &mapping.0 {
+                    if let (Some(k), Some(d)) = (src.get(from), dst.get_mut(to)) {
+                        *d = *k;
+                    }
+                }
+            } else if let Some(normal) = config.key_layers.as_mut().and_then(|l| l.first_mut()) {
+                for (&from, &to) in &mapping.0 {
+                    let (Some(k), Some(d)) = (src.get(from), normal.keys.get_mut(to)) else { continue };
+                    let codes = k.iter().filter(|c| **c != KeyCode::None).count();
+                    if codes > 1 {
+                        warnings.push(TranslateWarning::Binding { key: from });
+                    }
+                    *d = main_code(k).map_or(KeyValue::None, KeyValue::Keyboard);
+                }
+            }
+        }
+        (None, None) => {}
+    }
+
+    match (s.side_btn, config.side_btn.as_mut()) {
+        (Some(src), Some(dst)) => *dst = src,
+        (Some(_), None) => warnings.push(dropped("side_btn")),
+        _ => {}
+    }
+
+    match (s.key_settings, config.key_settings.as_mut()) {
+        (Some(src), Some(dst)) => {
+            let (from, to) = (jitters_per_ms(&source.device.device_name), jitters_per_ms(&target.device.device_name));
+            dst.jitters_elimination_time = (src.jitters_elimination_time as u32 * to / from).min(u16::MAX as u32) as u16;
+            dst.continuous_report = src.continuous_report;
+            dst.kalman_filter = src.kalman_filter;
+            match (src.enable_hs, dst.enable_hs) {
+                (Some(hs), Some(_)) => dst.enable_hs = Some(hs),
+                (Some(_), None) => warnings.push(dropped("key_settings.enable_hs")),
+                _ => {}
+            }
+        }
+        (Some(_), None) => warnings.push(dropped("key_settings")),
+        _ => {}
+    }
+
+    match (&s.light, &config.light) {
+        (Some(src @ PresetLight::Meowpad4k(_)), Some(PresetLight::Meowpad4k(_)))
+        | (Some(src @ PresetLight::Meowpad3k(_)), Some(PresetLight::Meowpad3k(_))) => config.light = Some(src.clone()),
+        (Some(_), _) => warnings.push(dropped("light")),
+        _ => {}
+    }
+
+    let mut preset = DevicePreset::new(&source.name, target.device.clone(), config);
+    preset.meta.tags = source.meta.tags.clone();
+    preset.meta.author = source.meta.author.clone();
+    preset.meta.notes = source.meta.notes.clone();
+    Translation { preset, mapping, warnings }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::{MEOWPAD_SE_DEVICE_NAME, PURE64_DEVICE_NAME};
+
+    fn meowpad4k() -> DevicePreset {
+        let mut key = meowpad4k::config::Key::default();
+        for (k, code) in key.keys.iter_mut().zip([KeyCode::Z, KeyCode::X, KeyCode::C, KeyCode::V]) {
+            k.key_data = [code, KeyCode::None, KeyCode::None, KeyCode::None, KeyCode::None, KeyCode::None];
+        }
+        key.keys[1].press_percentage = 15;
+        key.keys[3].key_data[1] = KeyCode::LShift;
+        key.jitters_elimination_time = 16;
+        DevicePreset::new("osu", MEOWPAD_DEVICE_NAME, (key, meowpad4k::config::Light::default()))
+    }
+
+    #[test]
+    fn preset_translate() {
+        let source = meowpad4k();
+        let pure64 = DevicePreset::new("kb", PURE64_DEVICE_NAME, meowboard::config::Device::default());
+        let codes = key_codes(&pure64);
+        let x = codes.iter().position(|c| *c == Some(KeyCode::X)).unwrap();
+
+        let t = translate(&source, &pure64, None);
+        assert_eq!(t.mapping.0.len(), 4);
+        assert_eq!(t.mapping.0[&1], x);
+        assert_eq!(t.preset.device.device_name, PURE64_DEVICE_NAME);
+        let configs = t.preset.config.key_configs.as_ref().unwrap();
+        // Pure64的行程单位为0.5%
+        assert_eq!(configs[x].press_percentage, 30);
+        assert!(t.warnings.contains(&TranslateWarning::Binding { key: 3 }));
+        assert!(t.warnings.contains(&TranslateWarning::Dropped { path: "key_settings".to_owned() }));
+        assert!(t.warnings.contains(&TranslateWarning::Dropped { path: "light".to_owned() }));
+
+        // 转回Meowpad：按键码对应，Pure64独有的设置给出警告
+        let mut back = t.preset.clone();
+        back.config.key_configs.as_mut().unwrap()[x].release_dead_zone = 5;
+        let t = translate(&back, &source, None);
+        assert_eq!(t.preset.config.key_configs.as_ref().unwrap()[1].press_percentage, 15);
+        assert!(t.warnings.contains(&TranslateWarning::Dropped { path: "key_configs.release_dead_zone".to_owned() }));
+        assert!(matches!(&t.warnings[0], TranslateWarning::Unmapped { keys } if keys.len() == 60));
+
+        // 超出范围的数值截断并给出警告
+        let mut over = source.clone();
+        over.config.key_configs.as_mut().unwrap()[1].dead_zone = 120;
+        let t = translate(&over, &pure64, None);
+        assert_eq!(t.preset.config.key_configs.as_ref().unwrap()[x].dead_zone, 200);
+        assert!(t.warnings.contains(&TranslateWarning::Clamped { path: format!("key_configs[{}].dead_zone", x) }));
+
+        // 自定义对应，防抖时间换算为Meowpad SE的单位
+        let se = DevicePreset::new("se", MEOWPAD_SE_DEVICE_NAME, (meowpad3k::config::Key::default(), meowpad3k::config::Light::default()));
+        let mapping = KeyMapping(BTreeMap::from([(0, 2), (1, 1), (2, 0)]));
+        let t = translate(&source, &se, Some(mapping));
+        assert_eq!(t.preset.config.key_data.as_ref().unwrap()[0][0], KeyCode::C);
+        assert_eq!(t.preset.config.key_settings.unwrap().jitters_elimination_time, 2);
+        assert_eq!(t.warnings[0], TranslateWarning::Unmapped { keys: vec![3] });
+        assert!(t.warnings.contains(&TranslateWarning::Dropped { path: "key_settings.enable_hs".to_owned() }));
+    }
+}
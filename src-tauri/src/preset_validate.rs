@@ -0,0 +1,271 @@
+//! `.pcf` 预设文件的检查
+//!
+//! 预设在应用到设备配置之前先按型号检查，有问题时给出报告而不是只应用其中一部分。
+
+use meowpad::{
+    validate::{Diagnostic, Validator},
+    KeyCode,
+};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    consts::{MEOWPAD_DEVICE_NAME, MEOWPAD_SE_DEVICE_NAME, PURE64_DEVICE_NAME},
+    device_preset::{DevicePreset, PresetLight, PRESET_VERSION},
+};
+
+/// 预设中的结构问题，数值范围的问题见 `PresetReport::diagnostics`
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PresetIssue {
+    /// 由更新版本的配置器保存
+    UnsupportedVersion { version: u32 },
+    UnknownDevice { device_name: String },
+    /// 预设属于其他型号的设备
+    DeviceMismatch { expected: String, device_name: String },
+    WrongLength { path: String, expected: usize, actual: usize },
+    UnknownKeyCode { path: String, value: i64 },
+    /// 该型号没有的设置，例如Meowpad 4K预设中的侧键
+    Unsupported { path: String },
+    /// 无法解析的文件，预设不会被导入
+    Malformed { message: String },
+}
+
+/// 预设的检查结果
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct PresetReport {
+    pub issues: Vec<PresetIssue>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl PresetReport {
+    /// 没有结构问题与错误级别的诊断时可以应用，超出推荐范围只是警告
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty() && !self.diagnostics.iter().any(Diagnostic::is_error)
+    }
+}
+
+/// 各型号预设的长度与数值范围，与设备配置的JSON Schema一致
+struct Model {
+    keys: usize,
+    layers: usize,
+    percentage: i64,
+    dead_zone: i64,
+    /// 推荐的死区上限
+    dead_zone_recommended: i64,
+    jitters_elimination_time: i64,
+    side_btn: bool,
+}
+
+fn model(device_name: &str) -> Option<Model> {
+    let meowpad = |keys, jitters_elimination_time, side_btn| Model {
+        keys,
+        layers: 0,
+        percentage: 100,
+        dead_zone: 100,
+        dead_zone_recommended: 30,
+        jitters_elimination_time,
+        side_btn,
+    };
+    match device_name {
+        d if d == PURE64_DEVICE_NAME => Some(Model {
+            keys: 64,
+            layers: 2,
+            percentage: 200,
+            dead_zone: 200,
+            dead_zone_recommended: 200,
+            jitters_elimination_time: 0,
+            side_btn: false,
+        }),
+        d if d == MEOWPAD_DEVICE_NAME => Some(meowpad(4, 400, false)),
+        d if d == MEOWPAD_SE_DEVICE_NAME => Some(meowpad(3, 50, true)),
+        _ => None,
+    }
+}
+
+/// 检查预设能否应用到对应型号的设备
+pub fn check(preset: &DevicePreset) -> PresetReport {
+    let mut report = PresetReport::default();
+    let issues = &mut report.issues;
+    if preset.version > PRESET_VERSION {
+        issues.push(PresetIssue::UnsupportedVersion { version: preset.version });
+    }
+    let device_name = &preset.device.device_name;
+    let Some(model) = model(device_name) else {
+        issues.push(PresetIssue::UnknownDevice { device_name: device_name.clone() });
+        return report;
+    };
+    let config = &preset.config;
+
+    if let Some(layers) = &config.key_layers {
+        issues.extend(length("config.key_layers", model.layers, layers.len()));
+        for (i, layer) in layers.iter().enumerate() {
+            issues.extend(length(&format!("config.key_layers[{}].keys", i), model.keys, layer.keys.len()));
+        }
+    }
+    if let Some(data) = &config.key_data {
+        if model.layers > 0 {
+            issues.push(PresetIssue::Unsupported { path: "config.key_data".to_owned() });
+        } else {
+            issues.extend(length("config.key_data", model.keys, data.len()));
+        }
+    }
+    let mut v = Validator::default();
+    if let Some(keys) = &config.key_configs {
+        issues.extend(length("config.key_configs", model.keys, keys.len()));
+        for (i, k) in keys.iter().enumerate() {
+            let path = |f: &str| format!("config.key_configs[{}].{}", i, f);
+            v.range(path("press_percentage"), k.press_percentage, 1, model.percentage);
+            v.range(path("release_percentage"), k.release_percentage, 1, model.percentage);
+            v.range(path("dead_zone"), k.dead_zone, 0, model.dead_zone);
+            v.recommend(path("dead_zone"), k.dead_zone, 0, model.dead_zone_recommended);
+            v.range(path("release_dead_zone"), k.release_dead_zone, 0, model.dead_zone);
+        }
+    }
+    if config.side_btn.is_some() && !model.side_btn {
+        issues.push(PresetIssue::Unsupported { path: "config.side_btn".to_owned() });
+    }
+    if let Some(s) = &config.key_settings {
+        if model.layers > 0 {
+            issues.push(PresetIssue::Unsupported { path: "config.key_settings".to_owned() });
+        } else {
+            v.range("config.key_settings.jitters_elimination_time", s.jitters_elimination_time, 0, model.jitters_elimination_time);
+        }
+    }
+    match (&config.light, model.keys) {
+        (None, _) => {}
+        (Some(PresetLight::Meowpad4k(l)), 4) => report.diagnostics.extend(prefixed(l.validate())),
+        (Some(PresetLight::Meowpad3k(l)), 3) => report.diagnostics.extend(prefixed(l.validate())),
+        (Some(_), _) => issues.push(PresetIssue::Unsupported { path: "config.light".to_owned() }),
+    }
+    report.diagnostics.extend(v.finish());
+    report
+}
+
+fn length(path: &str, expected: usize, actual: usize) -> Option<PresetIssue> {
+    (actual != expected).then(|| PresetIssue::WrongLength { path: path.to_owned(), expected, actual })
+}
+
+fn prefixed(diagnostics: Vec<Diagnostic>) -> impl Iterator<Item = Diagnostic> {
+    diagnostics.into_iter().map(|d| Diagnostic {
+        path: format!("config.light.{}", d.path),
+        ..d
+    })
+}
+
+/// 检查预设是否属于指定型号，其余检查见 [`check`]
+pub fn check_for(preset: &DevicePreset, device_name: &str) -> PresetReport {
+    let mut report = check(preset);
+    if preset.device.device_name != device_name {
+        report.issues.insert(0, PresetIssue::DeviceMismatch {
+            expected: device_name.to_owned(),
+            device_name: preset.device.device_name.clone(),
+        });
+    }
+    report
+}
+
+/// 解析 `.pcf` 文件，无法识别的键码记入报告并替换为空键位，
+/// 旧版本的预设升级到当前版本
+pub fn parse(text: &str) -> (Option<DevicePreset>, PresetReport) {
+    let malformed = |e: serde_json::Error| {
+        let report = PresetReport {
+            issues: vec![PresetIssue::Malformed { message: e.to_string() }],
+            ..Default::default()
+        };
+        (None, report)
+    };
+    let mut value: Value = match serde_json::from_str(text) {
+        Ok(v) => v,
+        Err(e) => return malformed(e),
+    };
+    let mut unknown = vec![];
+    if let Some(config) = value.get_mut("config") {
+        if let Some(data) = config.get_mut("key_data").and_then(Value::as_array_mut) {
+            for (i, keys) in data.iter_mut().enumerate() {
+                key_codes(keys, &format!("config.key_data[{}]", i), &mut unknown);
+            }
+        }
+        if let Some(keys) = config.get_mut("side_btn") {
+            key_codes(keys, "config.side_btn", &mut unknown);
+        }
+        if let Some(layers) = config.get_mut("key_layers").and_then(Value::as_array_mut) {
+            for (i, layer) in layers.iter_mut().enumerate() {
+                let Some(keys) = layer.get_mut("keys").and_then(Value::as_array_mut) else { continue };
+                for (j, key) in keys.iter_mut().enumerate() {
+                    if key.get("t").and_then(Value::as_str) == Some("Keyboard") {
+                        if let Some(code) = key.get_mut("c") {
+                            key_code(code, format!("config.key_layers[{}].keys[{}].c", i, j), &mut unknown);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    let mut preset: DevicePreset = match serde_json::from_value(value) {
+        Ok(p) => p,
+        Err(e) => return malformed(e),
+    };
+    let mut report = check(&preset);
+    report.issues.extend(unknown);
+    if preset.version < PRESET_VERSION {
+        preset.version = PRESET_VERSION;
+    }
+    (Some(preset), report)
+}
+
+fn key_codes(keys: &mut Value, path: &str, unknown: &mut Vec<PresetIssue>) {
+    if let Some(keys) = keys.as_array_mut() {
+        for (i, code) in keys.iter_mut().enumerate() {
+            key_code(code, format!("{}[{}]", path, i), unknown);
+        }
+    }
+}
+
+fn key_code(code: &mut Value, path: String, unknown: &mut Vec<PresetIssue>) {
+    let Some(value) = code.as_i64() else { return };
+    if serde_json::from_value::<KeyCode>(code.clone()).is_err() {
+        unknown.push(PresetIssue::UnknownKeyCode { path, value });
+        *code = Value::from(KeyCode::None as u8);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preset_report() {
+        let preset = DevicePreset::new("osu", PURE64_DEVICE_NAME, meowboard::config::Device::default());
+        let mut value = serde_json::to_value(&preset).unwrap();
+        assert!(check(&preset).is_valid());
+
+        // 旧版本没有版本号
+        value.as_object_mut().unwrap().remove("version");
+        value["config"]["key_configs"][3]["dead_zone"] = 300.into();
+        value["config"]["key_layers"][1]["keys"] = serde_json::json!([{ "t": "Keyboard", "c": 254 }]);
+        let (preset, report) = parse(&value.to_string());
+        let preset = preset.unwrap();
+        assert_eq!(preset.version, PRESET_VERSION);
+        assert_eq!(report.issues, vec![
+            PresetIssue::WrongLength { path: "config.key_layers[1].keys".to_owned(), expected: 64, actual: 1 },
+            PresetIssue::UnknownKeyCode { path: "config.key_layers[1].keys[0].c".to_owned(), value: 254 },
+        ]);
+        assert_eq!(report.diagnostics[0].path, "config.key_configs[3].dead_zone");
+        assert!(!report.is_valid());
+
+        let report = check_for(&preset, MEOWPAD_DEVICE_NAME);
+        assert!(matches!(report.issues[0], PresetIssue::DeviceMismatch { .. }));
+
+        let mut preset = DevicePreset::new("3k", MEOWPAD_SE_DEVICE_NAME, (meowpad3k::config::Key::default(), meowpad3k::config::Light::default()));
+        preset.device.device_name = "Meowpad Pro".to_owned();
+        assert_eq!(check(&preset).issues, vec![PresetIssue::UnknownDevice { device_name: "Meowpad Pro".to_owned() }]);
+        preset.version = PRESET_VERSION + 1;
+        preset.device.device_name = MEOWPAD_DEVICE_NAME.to_owned();
+        let issues = check(&preset).issues;
+        assert!(issues.contains(&PresetIssue::UnsupportedVersion { version: PRESET_VERSION + 1 }));
+        assert!(issues.contains(&PresetIssue::Unsupported { path: "config.side_btn".to_owned() }));
+
+        assert!(matches!(parse("{").1.issues[0], PresetIssue::Malformed { .. }));
+    }
+}
@@ -0,0 +1,627 @@
+//! 按前台应用自动切换预设
+//!
+//! 前台窗口由 [`WindowSource`] 检测，不同的桌面环境使用不同的实现。
+//! 切换时预设只写入设备内存而不保存，前台应用不再匹配任何规则时恢复切换前的配置。
+//! 全局快捷键的设置也保存在这里，快捷键切换的预设会被保存。
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{device_preset::DevicePreset, error::Result};
+
+/// 切换检查的间隔（毫秒）
+pub const POLL_INTERVAL: u64 = 1000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ForegroundApp {
+    /// Windows上为程序文件名，Linux上为窗口类名或Wayland的app_id，macOS上为应用名称
+    pub app_id: String,
+    pub title: String,
+}
+
+/// 前台窗口的检测方式
+pub trait WindowSource: Send {
+    fn name(&self) -> &'static str;
+    /// 无法检测时返回 `None`，例如锁屏或没有焦点窗口
+    fn foreground(&mut self) -> Option<ForegroundApp>;
+}
+
+/// 不支持的桌面环境，不会切换预设，界面在开启自动切换时提示
+pub struct NoSource;
+
+impl WindowSource for NoSource {
+    fn name(&self) -> &'static str {
+        "none"
+    }
+
+    fn foreground(&mut self) -> Option<ForegroundApp> {
+        None
+    }
+}
+
+/// 前台应用由 `set` 指定，用于测试
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct FakeSource(std::sync::Arc<Mutex<Option<ForegroundApp>>>);
+
+#[cfg(test)]
+impl FakeSource {
+    pub fn set(&self, app: Option<ForegroundApp>) {
+        *self.0.lock().unwrap() = app;
+    }
+}
+
+#[cfg(test)]
+impl WindowSource for FakeSource {
+    fn name(&self) -> &'static str {
+        "fake"
+    }
+
+    fn foreground(&mut self) -> Option<ForegroundApp> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+#[cfg(unix)]
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program).args(args).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// X11，通过 `xprop` 读取 `_NET_ACTIVE_WINDOW`，Wayland下只能检测到XWayland窗口
+#[cfg(all(unix, not(target_os = "macos")))]
+pub struct X11Source;
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl WindowSource for X11Source {
+    fn name(&self) -> &'static str {
+        "x11"
+    }
+
+    fn foreground(&mut self) -> Option<ForegroundApp> {
+        let root = command_output("xprop", &["-root", "_NET_ACTIVE_WINDOW"])?;
+        let id = root.split_whitespace().last().filter(|id| *id != "0x0")?;
+        parse_xprop(&command_output("xprop", &["-id", id, "WM_CLASS", "_NET_WM_NAME"])?)
+    }
+}
+
+/// 解析 `xprop -id <窗口> WM_CLASS _NET_WM_NAME` 的输出，应用标识使用类名
+#[cfg(all(unix, not(target_os = "macos")))]
+fn parse_xprop(output: &str) -> Option<ForegroundApp> {
+    let quoted = |line: &str| -> Vec<String> { line.split('"').skip(1).step_by(2).map(str::to_owned).collect() };
+    let mut app = ForegroundApp::default();
+    for line in output.lines() {
+        if line.starts_with("WM_CLASS") {
+            app.app_id = quoted(line).pop().unwrap_or_default();
+        } else if line.starts_with("_NET_WM_NAME") {
+            app.title = quoted(line).concat();
+        }
+    }
+    (!app.app_id.is_empty()).then_some(app)
+}
+
+/// Hyprland，通过 `hyprctl activewindow -j`
+#[cfg(all(unix, not(target_os = "macos")))]
+pub struct HyprlandSource;
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl WindowSource for HyprlandSource {
+    fn name(&self) -> &'static str {
+        "hyprland"
+    }
+
+    fn foreground(&mut self) -> Option<ForegroundApp> {
+        let window: serde_json::Value = serde_json::from_str(&command_output("hyprctl", &["activewindow", "-j"])?).ok()?;
+        let app_id = window.get("class")?.as_str().filter(|c| !c.is_empty())?;
+        Some(ForegroundApp {
+            app_id: app_id.to_owned(),
+            title: window.get("title").and_then(|t| t.as_str()).unwrap_or_default().to_owned(),
+        })
+    }
+}
+
+/// Sway等兼容i3 IPC的Wayland合成器，通过 `swaymsg -t get_tree`
+#[cfg(all(unix, not(target_os = "macos")))]
+pub struct SwaySource;
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl WindowSource for SwaySource {
+    fn name(&self) -> &'static str {
+        "sway"
+    }
+
+    fn foreground(&mut self) -> Option<ForegroundApp> {
+        let tree = serde_json::from_str(&command_output("swaymsg", &["-t", "get_tree"])?).ok()?;
+        focused_node(&tree)
+    }
+}
+
+/// 在Sway的窗口树中查找有焦点的窗口，XWayland窗口没有app_id，使用窗口类名
+#[cfg(all(unix, not(target_os = "macos")))]
+fn focused_node(node: &serde_json::Value) -> Option<ForegroundApp> {
+    if node.get("focused").and_then(|f| f.as_bool()) == Some(true) {
+        let app_id = node
+            .get("app_id")
+            .and_then(|a| a.as_str())
+            .or_else(|| node.pointer("/window_properties/class").and_then(|c| c.as_str()))?;
+        return Some(ForegroundApp {
+            app_id: app_id.to_owned(),
+            title: node.get("name").and_then(|n| n.as_str()).unwrap_or_default().to_owned(),
+        });
+    }
+    ["nodes", "floating_nodes"]
+        .iter()
+        .filter_map(|key| node.get(key).and_then(|n| n.as_array()))
+        .flatten()
+        .find_map(focused_node)
+}
+
+/// macOS，通过System Events读取最前面的应用，需要辅助功能权限
+#[cfg(target_os = "macos")]
+pub struct MacSource;
+
+#[cfg(target_os = "macos")]
+impl WindowSource for MacSource {
+    fn name(&self) -> &'static str {
+        "macos"
+    }
+
+    fn foreground(&mut self) -> Option<ForegroundApp> {
+        let script = "tell application \"System Events\" to get name of first application process whose frontmost is true";
+        let name = command_output("osascript", &["-e", script])?;
+        let name = name.trim();
+        (!name.is_empty()).then(|| ForegroundApp {
+            app_id: name.to_owned(),
+            title: String::new(),
+        })
+    }
+}
+
+/// Windows，应用标识为前台窗口所属进程的程序文件名，例如 `osu!.exe`
+#[cfg(windows)]
+pub struct WindowsSource;
+
+#[cfg(windows)]
+impl WindowSource for WindowsSource {
+    fn name(&self) -> &'static str {
+        "windows"
+    }
+
+    fn foreground(&mut self) -> Option<ForegroundApp> {
+        use windows_sys::Win32::{
+            Foundation::CloseHandle,
+            System::Threading::{OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION},
+            UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowTextW, GetWindowThreadProcessId},
+        };
+
+        unsafe {
+            let hwnd = GetForegroundWindow();
+            if hwnd == 0 {
+                return None;
+            }
+            let mut title = [0u16; 512];
+            let len = GetWindowTextW(hwnd, title.as_mut_ptr(), title.len() as i32).max(0) as usize;
+            let mut pid = 0;
+            GetWindowThreadProcessId(hwnd, &mut pid);
+            let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+            if process == 0 {
+                return None;
+            }
+            let mut path = [0u16; 1024];
+            let mut size = path.len() as u32;
+            let ok = QueryFullProcessImageNameW(process, PROCESS_NAME_WIN32, path.as_mut_ptr(), &mut size);
+            CloseHandle(process);
+            if ok == 0 {
+                return None;
+            }
+            let path = String::from_utf16_lossy(&path[..size as usize]);
+            Some(ForegroundApp {
+                app_id: path.rsplit('\\').next().unwrap_or_default().to_owned(),
+                title: String::from_utf16_lossy(&title[..len]),
+            })
+        }
+    }
+}
+
+/// 按当前的平台与桌面环境选择检测方式，没有可用的方式时返回 [`NoSource`]
+///
+/// xdg-desktop-portal没有读取前台窗口的接口，Hyprland与Sway以外的Wayland合成器
+/// （例如GNOME与KDE）不在支持范围内，只能检测到XWayland窗口
+pub fn detect() -> Box<dyn WindowSource> {
+    #[cfg(windows)]
+    {
+        Box::new(WindowsSource)
+    }
+    #[cfg(target_os = "macos")]
+    {
+        Box::new(MacSource)
+    }
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        let has = |var: &str| std::env::var_os(var).is_some_and(|v| !v.is_empty());
+        if has("HYPRLAND_INSTANCE_SIGNATURE") {
+            Box::new(HyprlandSource)
+        } else if has("SWAYSOCK") {
+            Box::new(SwaySource)
+        } else if has("DISPLAY") {
+            Box::new(X11Source)
+        } else {
+            Box::new(NoSource)
+        }
+    }
+    #[cfg(not(any(windows, unix)))]
+    {
+        Box::new(NoSource)
+    }
+}
+
+/// 前台应用与预设的绑定
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProfileRule {
+    /// 与 [`ForegroundApp::app_id`] 比较，不区分大小写
+    pub app: String,
+    /// 窗口标题需要包含的文字，不区分大小写
+    #[serde(default)]
+    pub title: Option<String>,
+    pub preset_id: String,
+}
+
+impl ProfileRule {
+    pub fn matches(&self, app: &ForegroundApp) -> bool {
+        self.app.eq_ignore_ascii_case(&app.app_id)
+            && self
+                .title
+                .as_ref()
+                .filter(|t| !t.is_empty())
+                .is_none_or(|t| app.title.to_lowercase().contains(&t.to_lowercase()))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ProfileSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    /// 同一设备有多条规则匹配时使用第一条
+    #[serde(default)]
+    pub rules: Vec<ProfileRule>,
+    #[serde(default)]
+    pub hotkeys: HotkeySettings,
+}
+
+/// 全局快捷键，格式与Tauri的快捷键相同，例如 `CmdOrCtrl+Alt+Right`，为空时不注册
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct HotkeySettings {
+    /// 每台已连接的设备切换到下一个预设
+    #[serde(default)]
+    pub next: Option<String>,
+    #[serde(default)]
+    pub previous: Option<String>,
+    /// 切换到指定的预设
+    #[serde(default)]
+    pub presets: Vec<PresetHotkey>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PresetHotkey {
+    pub accelerator: String,
+    pub preset_id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HotkeyAction {
+    /// 按顺序向后（1）或向前（-1）切换
+    Cycle(isize),
+    Select(String),
+}
+
+impl HotkeySettings {
+    /// 需要注册的快捷键，同一快捷键只使用第一个设置
+    pub fn actions(&self) -> Vec<(String, HotkeyAction)> {
+        let mut actions: Vec<(String, HotkeyAction)> = vec![];
+        let all = [(&self.next, HotkeyAction::Cycle(1)), (&self.previous, HotkeyAction::Cycle(-1))]
+            .into_iter()
+            .filter_map(|(a, action)| Some((a.as_ref()?.trim(), action)))
+            .chain(self.presets.iter().map(|p| (p.accelerator.trim(), HotkeyAction::Select(p.preset_id.clone()))));
+        for (accelerator, action) in all {
+            if !accelerator.is_empty() && !actions.iter().any(|(a, _)| a.eq_ignore_ascii_case(accelerator)) {
+                actions.push((accelerator.to_owned(), action));
+            }
+        }
+        actions
+    }
+}
+
+/// 在同一设备的预设中循环切换，`current` 不在列表中时从第一个或最后一个开始
+pub fn cycle<'a>(presets: &'a [DevicePreset], current: Option<&str>, step: isize) -> Option<&'a DevicePreset> {
+    let len = presets.len() as isize;
+    if len == 0 {
+        return None;
+    }
+    let index = match presets.iter().position(|p| Some(p.id.as_str()) == current) {
+        Some(i) => (i as isize + step).rem_euclid(len),
+        None if step < 0 => len - 1,
+        None => 0,
+    };
+    presets.get(index as usize)
+}
+
+/// 保存在 `profiles.json` 中的自动切换设置
+pub struct Profiles {
+    path: PathBuf,
+    settings: Mutex<ProfileSettings>,
+}
+
+impl Profiles {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        let path = dir.into().join("profiles.json");
+        let settings = fs::read(&path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            settings: Mutex::new(settings),
+        }
+    }
+
+    pub fn settings(&self) -> ProfileSettings {
+        self.settings.lock().unwrap().clone()
+    }
+
+    /// 新的设置在下一次检查前台应用时生效
+    pub fn set_settings(&self, settings: ProfileSettings) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, serde_json::to_vec_pretty(&settings)?)?;
+        *self.settings.lock().unwrap() = settings;
+        Ok(())
+    }
+}
+
+/// 切换时写入预设的设备，由命令层实现
+pub trait PresetTarget {
+    /// 设备当前的配置，用于之后恢复
+    fn current(&mut self, device_name: &str) -> Result<DevicePreset>;
+    /// 写入设备内存，不保存
+    fn apply(&mut self, preset: &DevicePreset) -> Result<()>;
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Switch {
+    /// 自动切换的预设不会被保存，`saved` 为 `false`
+    Applied { device_name: String, preset_id: String, name: String, saved: bool },
+    /// 恢复为切换前的配置
+    Restored { device_name: String },
+    Failed { device_name: String, message: String },
+}
+
+/// 按前台应用决定每台设备使用的预设
+#[derive(Default)]
+pub struct Switcher {
+    /// 每台设备切换前的配置
+    defaults: BTreeMap<String, DevicePreset>,
+    /// 每台设备当前应用的预设ID
+    active: BTreeMap<String, String>,
+    /// 手动切换过预设的设备，前台应用变化前不再自动切换
+    paused: BTreeSet<String>,
+    /// 上一次检测到的前台应用
+    app_id: Option<String>,
+}
+
+impl Switcher {
+    /// 前台应用未知时保持不变，返回这次进行的切换
+    pub fn update(
+        &mut self,
+        rules: &[ProfileRule],
+        app: Option<&ForegroundApp>,
+        presets: impl Fn(&str) -> Result<DevicePreset>,
+        target: &mut impl PresetTarget,
+    ) -> Vec<Switch> {
+        let Some(app) = app else { return vec![] };
+        if self.app_id.as_deref() != Some(app.app_id.as_str()) {
+            self.app_id = Some(app.app_id.clone());
+            self.paused.clear();
+        }
+        let mut wanted: BTreeMap<String, DevicePreset> = BTreeMap::new();
+        for rule in rules.iter().filter(|r| r.matches(app)) {
+            match presets(&rule.preset_id) {
+                Ok(preset) => {
+                    wanted.entry(preset.device.device_name.clone()).or_insert(preset);
+                }
+                Err(e) => log::warn!("无法读取预设 {}：{}", rule.preset_id, e),
+            }
+        }
+
+        let unused: Vec<String> = self.active.keys().filter(|d| !wanted.contains_key(*d)).cloned().collect();
+        let mut switches = vec![];
+        for device_name in unused {
+            switches.push(self.restore(&device_name, target));
+        }
+        for (device_name, preset) in wanted {
+            if self.active.get(&device_name) == Some(&preset.id) || self.paused.contains(&device_name) {
+                continue;
+            }
+            // 记录切换前的配置，设备未连接时跳过
+            if !self.defaults.contains_key(&device_name) {
+                match target.current(&device_name) {
+                    Ok(current) => {
+                        self.defaults.insert(device_name.clone(), current);
+                    }
+                    Err(e) => {
+                        log::debug!("无法读取 {} 的配置：{}", device_name, e);
+                        continue;
+                    }
+                }
+            }
+            self.active.insert(device_name.clone(), preset.id.clone());
+            switches.push(match target.apply(&preset) {
+                Ok(()) => Switch::Applied { device_name, preset_id: preset.id, name: preset.name, saved: false },
+                Err(e) => Switch::Failed { device_name, message: e.to_string() },
+            });
+        }
+        switches
+    }
+
+    fn restore(&mut self, device_name: &str, target: &mut impl PresetTarget) -> Switch {
+        self.active.remove(device_name);
+        let device_name = device_name.to_owned();
+        match self.defaults.remove(&device_name).map(|d| target.apply(&d)) {
+            Some(Err(e)) => Switch::Failed { device_name, message: e.to_string() },
+            _ => Switch::Restored { device_name },
+        }
+    }
+
+    /// 从托盘或快捷键切换并保存预设后调用，保存的配置成为新的基准，不再恢复切换前的配置
+    pub fn manual_override(&mut self, device_name: &str) {
+        self.defaults.remove(device_name);
+        self.active.remove(device_name);
+        self.paused.insert(device_name.to_owned());
+    }
+
+    /// 恢复所有设备切换前的配置，例如关闭自动切换时
+    pub fn reset(&mut self, target: &mut impl PresetTarget) -> Vec<Switch> {
+        self.paused.clear();
+        let devices: Vec<String> = self.active.keys().cloned().collect();
+        devices.into_iter().map(|d| self.restore(&d, target)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{consts::PURE64_DEVICE_NAME, error::Error};
+
+    /// 记录写入的预设名称
+    #[derive(Default)]
+    struct FakeTarget {
+        connected: bool,
+        applied: Vec<String>,
+    }
+
+    impl PresetTarget for FakeTarget {
+        fn current(&mut self, device_name: &str) -> Result<DevicePreset> {
+            if !self.connected {
+                return Err(Error::PresetNotFound(device_name.to_owned()));
+            }
+            Ok(DevicePreset::new("default", device_name, meowboard::config::Device::default()))
+        }
+
+        fn apply(&mut self, preset: &DevicePreset) -> Result<()> {
+            self.applied.push(preset.name.clone());
+            Ok(())
+        }
+    }
+
+    fn app(app_id: &str, title: &str) -> Option<ForegroundApp> {
+        Some(ForegroundApp { app_id: app_id.to_owned(), title: title.to_owned() })
+    }
+
+    #[test]
+    fn profile_switch() {
+        let osu = DevicePreset::new("osu", PURE64_DEVICE_NAME, meowboard::config::Device::default());
+        let rules = vec![
+            ProfileRule { app: "osu!.exe".to_owned(), title: None, preset_id: osu.id.clone() },
+            ProfileRule { app: "firefox".to_owned(), title: Some("osu!".to_owned()), preset_id: osu.id.clone() },
+        ];
+        let presets = |id: &str| if id == osu.id { Ok(osu.clone()) } else { Err(Error::PresetNotFound(id.to_owned())) };
+        let source = FakeSource::default();
+        let mut window = source.clone();
+        let mut target = FakeTarget::default();
+        let mut switcher = Switcher::default();
+        let mut step = |switcher: &mut Switcher, target: &mut FakeTarget| switcher.update(&rules, window.foreground().as_ref(), presets, target);
+
+        // 设备未连接时不切换，连接后重试
+        source.set(app("OSU!.EXE", "osu!"));
+        assert!(step(&mut switcher, &mut target).is_empty());
+        target.connected = true;
+        assert!(matches!(&step(&mut switcher, &mut target)[..], [Switch::Applied { name, .. }] if name == "osu"));
+        assert!(step(&mut switcher, &mut target).is_empty());
+
+        // 窗口标题匹配同一预设时保持不变，前台应用未知时也不变
+        source.set(app("firefox", "osu! - beatmaps"));
+        assert!(step(&mut switcher, &mut target).is_empty());
+        source.set(None);
+        assert!(step(&mut switcher, &mut target).is_empty());
+
+        source.set(app("firefox", "Mozilla Firefox"));
+        assert_eq!(step(&mut switcher, &mut target), vec![Switch::Restored { device_name: PURE64_DEVICE_NAME.to_owned() }]);
+        assert_eq!(target.applied, vec!["osu", "default"]);
+
+        source.set(app("osu!.exe", ""));
+        step(&mut switcher, &mut target);
+        assert_eq!(switcher.reset(&mut target).len(), 1);
+        assert_eq!(target.applied, vec!["osu", "default", "osu", "default"]);
+    }
+
+    #[test]
+    fn manual_override() {
+        let osu = DevicePreset::new("osu", PURE64_DEVICE_NAME, meowboard::config::Device::default());
+        let rules = vec![ProfileRule { app: "osu!.exe".to_owned(), title: None, preset_id: osu.id.clone() }];
+        let presets = |_: &str| Ok(osu.clone());
+        let mut target = FakeTarget { connected: true, ..Default::default() };
+        let mut switcher = Switcher::default();
+
+        switcher.update(&rules, app("osu!.exe", "a").as_ref(), presets, &mut target);
+        // 手动切换后同一前台应用不再自动切换，也不恢复为之前的配置
+        switcher.manual_override(PURE64_DEVICE_NAME);
+        assert!(switcher.update(&rules, app("osu!.exe", "b").as_ref(), presets, &mut target).is_empty());
+        assert!(switcher.update(&rules, app("firefox", "").as_ref(), presets, &mut target).is_empty());
+        assert_eq!(target.applied, vec!["osu"]);
+
+        // 前台应用变化后恢复自动切换，切换前的配置重新读取
+        assert_eq!(switcher.update(&rules, app("osu!.exe", "").as_ref(), presets, &mut target).len(), 1);
+        assert_eq!(target.applied, vec!["osu", "osu"]);
+    }
+
+    #[test]
+    fn hotkeys() {
+        let presets: Vec<DevicePreset> = ["a", "b", "c"]
+            .iter()
+            .map(|n| DevicePreset::new(n, PURE64_DEVICE_NAME, meowboard::config::Device::default()))
+            .collect();
+        let name = |p: Option<&DevicePreset>| p.map(|p| p.name.clone());
+        assert_eq!(name(cycle(&presets, None, 1)), Some("a".to_owned()));
+        assert_eq!(name(cycle(&presets, None, -1)), Some("c".to_owned()));
+        assert_eq!(name(cycle(&presets, Some(&presets[2].id), 1)), Some("a".to_owned()));
+        assert_eq!(name(cycle(&presets, Some(&presets[0].id), -1)), Some("c".to_owned()));
+        assert!(cycle(&[], None, 1).is_none());
+
+        let hotkeys = HotkeySettings {
+            next: Some("CmdOrCtrl+Alt+Right".to_owned()),
+            previous: Some(" ".to_owned()),
+            presets: vec![
+                PresetHotkey { accelerator: "cmdorctrl+alt+right".to_owned(), preset_id: presets[1].id.clone() },
+                PresetHotkey { accelerator: "CmdOrCtrl+Alt+1".to_owned(), preset_id: presets[0].id.clone() },
+            ],
+        };
+        assert_eq!(hotkeys.actions(), vec![
+            ("CmdOrCtrl+Alt+Right".to_owned(), HotkeyAction::Cycle(1)),
+            ("CmdOrCtrl+Alt+1".to_owned(), HotkeyAction::Select(presets[0].id.clone())),
+        ]);
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn window_sources() {
+        let xprop = "WM_CLASS(STRING) = \"osu!.exe\", \"osu!.exe\"\n_NET_WM_NAME(UTF8_STRING) = \"osu! - Song\"\n";
+        assert_eq!(parse_xprop(xprop), app("osu!.exe", "osu! - Song"));
+        assert_eq!(parse_xprop("WM_CLASS:  not found.\n"), None);
+
+        let tree = serde_json::json!({
+            "focused": false,
+            "nodes": [{ "focused": false, "nodes": [
+                { "focused": false, "app_id": "foot", "name": "shell" },
+                { "focused": true, "app_id": null, "window_properties": { "class": "osu!" }, "name": "osu!" },
+            ]}],
+        });
+        assert_eq!(focused_node(&tree), app("osu!", "osu!"));
+    }
+}
@@ -0,0 +1,273 @@
+use meowpad::validate::Diagnostic;
+use schemars::JsonSchema;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+/// 原始配置编辑器支持的格式
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Toml,
+    Json,
+    Yaml,
+}
+
+/// 解析失败的位置与原因，行列从 1 开始，无法定位时为空
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub format: Format,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+/// 编辑器的检查结果
+#[derive(Serialize, Debug, Clone)]
+pub struct RawConfigCheck {
+    pub format: Format,
+    pub error: Option<ParseError>,
+    pub diagnostics: Vec<Diagnostic>,
+    /// 结构中没有、解析时会被忽略的字段
+    pub unknown: Vec<String>,
+}
+
+impl RawConfigCheck {
+    pub fn new(text: &str, result: Result<Vec<Diagnostic>, ParseError>) -> Self {
+        let format = Format::detect(text);
+        match result {
+            Ok(diagnostics) => Self { format, error: None, diagnostics, unknown: vec![] },
+            Err(e) => Self { format, error: Some(e), diagnostics: vec![], unknown: vec![] },
+        }
+    }
+
+    /// 按 `T` 的JSON Schema找出多余的字段，旧版本的配置字段不同，只检查版本为 `version` 的配置
+    pub fn check_schema<T: JsonSchema>(mut self, text: &str, version: u32) -> Self {
+        if self.error.is_some() {
+            return self;
+        }
+        let Ok(value) = self.format.parse::<Value>(text) else { return self };
+        if value.get("version").and_then(Value::as_u64).is_some_and(|v| v != version as u64) {
+            return self;
+        }
+        let schema = schemars::schema_for!(T);
+        self.unknown = unknown_fields(schema.as_value(), &value);
+        self
+    }
+}
+
+/// 列出 `value` 中JSON Schema没有定义的字段
+pub fn unknown_fields(schema: &Value, value: &Value) -> Vec<String> {
+    let mut unknown = vec![];
+    walk_unknown(schema, schema, value, String::new(), &mut unknown);
+    unknown
+}
+
+fn resolve_ref<'a>(root: &'a Value, schema: &'a Value) -> &'a Value {
+    match schema.get("$ref").and_then(Value::as_str) {
+        Some(r) => r.strip_prefix('#').and_then(|p| root.pointer(p)).unwrap_or(schema),
+        None => schema,
+    }
+}
+
+fn walk_unknown(root: &Value, schema: &Value, value: &Value, path: String, unknown: &mut Vec<String>) {
+    let resolve = |s| resolve_ref(root, s);
+    // `Option` 与枚举生成 `anyOf`/`oneOf`，字段在任一分支中出现即可
+    let schema = resolve(schema);
+    let mut candidates = vec![schema];
+    for key in ["anyOf", "oneOf", "allOf"] {
+        if let Some(Value::Array(branches)) = schema.get(key) {
+            candidates.extend(branches.iter().map(resolve));
+        }
+    }
+    match value {
+        Value::Object(map) => {
+            let properties: Vec<_> = candidates.iter().filter_map(|s| s.get("properties")).collect();
+            if properties.is_empty() {
+                return;
+            }
+            for (k, v) in map {
+                let path = if path.is_empty() { k.clone() } else { format!("{}.{}", path, k) };
+                match properties.iter().find_map(|p| p.get(k)) {
+                    Some(s) => walk_unknown(root, s, v, path, unknown),
+                    None => unknown.push(path),
+                }
+            }
+        }
+        Value::Array(items) => {
+            let Some(s) = candidates.iter().find_map(|s| s.get("items")) else { return };
+            for (i, v) in items.iter().enumerate() {
+                walk_unknown(root, s, v, format!("{}[{}]", path, i), unknown);
+            }
+        }
+        _ => {}
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => write!(f, "{}:{}: {}", line, column, self.message),
+            _ => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl Format {
+    /// 根据内容判断格式：对象开头为JSON，首行是表头或 `key = value` 为TOML，其余含 `key: value` 的为YAML
+    pub fn detect(text: &str) -> Self {
+        if text.trim_start().starts_with('{') {
+            return Format::Json;
+        }
+        let line = text
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty() && !l.starts_with('#') && *l != "---");
+        match line {
+            Some(l) if l.starts_with('[') => Format::Toml,
+            Some(l) => match (l.find('='), l.find(':')) {
+                (Some(eq), Some(colon)) if colon < eq => Format::Yaml,
+                (None, Some(_)) => Format::Yaml,
+                _ => Format::Toml,
+            },
+            None => Format::Toml,
+        }
+    }
+
+    pub fn parse<T: DeserializeOwned>(self, text: &str) -> Result<T, ParseError> {
+        match self {
+            Format::Toml => toml::from_str(text).map_err(|e| {
+                let (line, column) = match e.span() {
+                    Some(span) => {
+                        let (line, column) = position(text, span.start);
+                        (Some(line), Some(column))
+                    }
+                    None => (None, None),
+                };
+                ParseError {
+                    format: self,
+                    line,
+                    column,
+                    message: e.message().to_owned(),
+                }
+            }),
+            Format::Json => serde_json::from_str(text).map_err(|e| {
+                let located = e.line() > 0;
+                ParseError {
+                    format: self,
+                    line: located.then_some(e.line()),
+                    column: located.then_some(e.column()),
+                    message: strip_location(e.to_string(), e.line(), e.column()),
+                }
+            }),
+            Format::Yaml => serde_yaml::from_str(text).map_err(|e| {
+                let location = e.location();
+                ParseError {
+                    format: self,
+                    line: location.as_ref().map(|l| l.line()),
+                    column: location.as_ref().map(|l| l.column()),
+                    message: match location {
+                        Some(l) => strip_location(e.to_string(), l.line(), l.column()),
+                        None => e.to_string(),
+                    },
+                }
+            }),
+        }
+    }
+
+    /// 迁移后的数据已经丢失了位置信息，按当前结构重新解析原文以尽量定位错误
+    pub fn locate<T: DeserializeOwned>(self, text: &str, message: impl ToString) -> ParseError {
+        let (line, column) = match self.parse::<T>(text) {
+            Err(e) => (e.line, e.column),
+            Ok(_) => (None, None),
+        };
+        ParseError {
+            format: self,
+            line,
+            column,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// 字节偏移转换为行列
+fn position(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+    (line, column)
+}
+
+fn strip_location(message: String, line: usize, column: usize) -> String {
+    let suffix = format!(" at line {} column {}", line, column);
+    match message.strip_suffix(&suffix) {
+        Some(m) => m.to_owned(),
+        None => message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Sample {
+        key: Inner,
+    }
+
+    #[derive(Deserialize, Debug, schemars::JsonSchema)]
+    #[allow(dead_code)]
+    struct Inner {
+        dead_zone: u8,
+    }
+
+    #[derive(Deserialize, schemars::JsonSchema)]
+    #[allow(dead_code)]
+    struct Versioned {
+        version: u32,
+        keys: Vec<Inner>,
+        light: Option<Light>,
+    }
+
+    #[derive(Deserialize, schemars::JsonSchema)]
+    #[allow(dead_code)]
+    struct Light {
+        mode: u8,
+    }
+
+    #[test]
+    fn unknown_fields() {
+        let text = "version = 2\nextra = 1\n[[keys]]\ndead_zone = 1\ntypo = 3\n[light]\nmode = 1\nspeed = 2\n";
+        let check = RawConfigCheck::new(text, Ok(vec![])).check_schema::<Versioned>(text, 2);
+        assert_eq!(check.unknown, ["extra", "keys[0].typo", "light.speed"]);
+
+        // 旧版本的字段由迁移处理，不做检查
+        let check = RawConfigCheck::new(text, Ok(vec![])).check_schema::<Versioned>(text, 3);
+        assert!(check.unknown.is_empty());
+    }
+
+    #[test]
+    fn detect_format() {
+        assert_eq!(Format::detect("[key]\ndead_zone = 1"), Format::Toml);
+        assert_eq!(Format::detect("# comment\nversion = 2\n"), Format::Toml);
+        assert_eq!(Format::detect("  {\"key\": {}}"), Format::Json);
+        assert_eq!(Format::detect("---\nkey:\n  dead_zone: 1\n"), Format::Yaml);
+        assert_eq!(Format::detect("version: 2"), Format::Yaml);
+    }
+
+    #[test]
+    fn parse_errors_have_location() {
+        let e = Format::Toml.parse::<Sample>("[key]\ndead_zone = 300\n").unwrap_err();
+        assert_eq!((e.line, e.column), (Some(2), Some(13)));
+
+        let e = Format::Json.parse::<Sample>("{\n  \"key\": {\"dead_zone\": }\n}").unwrap_err();
+        assert_eq!(e.line, Some(2));
+        assert!(!e.message.contains("at line"));
+
+        let e = Format::Yaml.parse::<Sample>("key:\n  dead_zone: abc\n").unwrap_err();
+        assert_eq!(e.line, Some(2));
+        assert!(!e.message.contains("at line"));
+
+        assert!(Format::Yaml.parse::<Sample>("key:\n  dead_zone: 3\n").is_ok());
+    }
+}
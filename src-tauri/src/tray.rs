@@ -0,0 +1,212 @@
+//! 系统托盘菜单与全局快捷键，不打开主窗口也能切换预设
+//!
+//! 切换的预设写入设备后会被保存，结果通过系统通知显示，并发送 `profile_switched` 事件。
+
+use std::{collections::BTreeMap, sync::Mutex, thread};
+
+use log::*;
+use meowboard::Meowboard;
+use meowpad3k::Meowpad as Meowpad3k;
+use meowpad4k::Meowpad as Meowpad4k;
+use tauri::{
+    api::notification::Notification, AppHandle, CustomMenuItem, GlobalShortcutManager, Manager, SystemTrayEvent,
+    SystemTrayMenu, SystemTrayMenuItem, SystemTraySubmenu,
+};
+
+use crate::{
+    cmdpreset::{apply_preset_live, save_device_config},
+    device::HidDevice,
+    device_preset::DevicePreset,
+    error::{Error, Result},
+    preset_library::{PresetFilter, PresetLibrary},
+    profile::{self, HotkeyAction, HotkeySettings, Switch, Switcher},
+    MEOWPAD_DEVICE_NAME, MEOWPAD_SE_DEVICE_NAME, PURE64_DEVICE_NAME,
+};
+
+const PRESET_ITEM: &str = "preset:";
+
+/// 每台设备最近一次从托盘或快捷键切换的预设ID
+#[derive(Default)]
+pub struct ActivePresets(Mutex<BTreeMap<String, String>>);
+
+/// 已连接的设备，正在读写的设备会等待读写完成
+fn connected_devices(app: &AppHandle) -> Vec<&'static str> {
+    let mut devices = vec![];
+    if app.state::<Mutex<Option<Meowboard<HidDevice>>>>().lock().unwrap().is_some() {
+        devices.push(PURE64_DEVICE_NAME);
+    }
+    if app.state::<Mutex<Option<Meowpad4k<HidDevice>>>>().lock().unwrap().is_some() {
+        devices.push(MEOWPAD_DEVICE_NAME);
+    }
+    if app.state::<Mutex<Option<Meowpad3k<HidDevice>>>>().lock().unwrap().is_some() {
+        devices.push(MEOWPAD_SE_DEVICE_NAME);
+    }
+    devices
+}
+
+/// 设备型号的预设，按名称排列，托盘菜单与循环切换使用相同的顺序
+fn device_presets(app: &AppHandle, device_name: &str) -> Vec<DevicePreset> {
+    let filter = PresetFilter {
+        device_name: Some(device_name.to_owned()),
+        ..Default::default()
+    };
+    match app.state::<PresetLibrary>().list(&filter) {
+        Ok(mut presets) => {
+            presets.sort_by(|a, b| a.name.cmp(&b.name));
+            presets
+        }
+        Err(e) => {
+            warn!("无法读取预设库：{}", e);
+            vec![]
+        }
+    }
+}
+
+/// 每台已连接的设备一个子菜单，列出该型号的预设
+pub fn tray_menu(app: &AppHandle) -> SystemTrayMenu {
+    let active = app.state::<ActivePresets>().0.lock().unwrap().clone();
+    let devices = connected_devices(app);
+    let mut menu = SystemTrayMenu::new();
+    if devices.is_empty() {
+        menu = menu.add_item(CustomMenuItem::new("no_device", "没有已连接的设备").disabled());
+    }
+    for device_name in devices {
+        let presets = device_presets(app, device_name);
+        let mut submenu = SystemTrayMenu::new();
+        if presets.is_empty() {
+            submenu = submenu.add_item(CustomMenuItem::new(format!("no_preset:{}", device_name), "没有预设").disabled());
+        }
+        for preset in presets {
+            let mut item = CustomMenuItem::new(format!("{}{}", PRESET_ITEM, preset.id), preset.name);
+            if active.get(device_name) == Some(&preset.id) {
+                item = item.selected();
+            }
+            submenu = submenu.add_item(item);
+        }
+        menu = menu.add_submenu(SystemTraySubmenu::new(device_name, submenu));
+    }
+    menu.add_native_item(SystemTrayMenuItem::Separator)
+        .add_item(CustomMenuItem::new("show", "显示窗口"))
+        .add_item(CustomMenuItem::new("quit", "退出"))
+}
+
+/// 设备连接状态或预设库变化后更新托盘菜单
+pub fn refresh_tray(app: &AppHandle) {
+    if let Err(e) = app.tray_handle().set_menu(tray_menu(app)) {
+        warn!("更新托盘菜单失败：{}", e);
+    }
+}
+
+pub fn on_tray_event(app: &AppHandle, event: SystemTrayEvent) {
+    let SystemTrayEvent::MenuItemClick { id, .. } = event else { return };
+    match id.as_str() {
+        "show" => {
+            if let Some(window) = app.get_window("main") {
+                let _ = window.show();
+                let _ = window.set_focus();
+            }
+        }
+        // 与关闭窗口相同，有未保存的配置时先询问
+        "quit" => {
+            if let Some(window) = app.get_window("main") {
+                let _ = window.close();
+            }
+        }
+        id => {
+            if let Some(id) = id.strip_prefix(PRESET_ITEM) {
+                let (app, id) = (app.clone(), id.to_owned());
+                thread::spawn(move || select_preset(&app, &id));
+            }
+        }
+    }
+}
+
+fn notify(app: &AppHandle, body: &str) {
+    let result = Notification::new(&app.config().tauri.bundle.identifier)
+        .title("Meowpad Configurator")
+        .body(body)
+        .show();
+    if let Err(e) = result {
+        warn!("无法显示通知：{}", e);
+    }
+}
+
+/// 写入并保存预设，与在界面中应用预设后保存相同，
+/// 自动切换不会恢复为之前的配置，前台应用变化前也不再切换该设备
+fn switch_to(app: &AppHandle, preset: &DevicePreset) {
+    let device_name = preset.device.device_name.clone();
+    let switcher = app.state::<Mutex<Switcher>>();
+    let mut switcher = switcher.lock().unwrap();
+    let switch = match apply_preset_live(app, preset).and_then(|_| save_device_config(app, &device_name)) {
+        Ok(()) => {
+            switcher.manual_override(&device_name);
+            notify(app, &format!("{} 已切换到预设 {}", device_name, preset.name));
+            app.state::<ActivePresets>().0.lock().unwrap().insert(device_name.clone(), preset.id.clone());
+            Switch::Applied {
+                device_name,
+                preset_id: preset.id.clone(),
+                name: preset.name.clone(),
+                saved: true,
+            }
+        }
+        Err(e) => {
+            warn!("切换预设失败：{}", e);
+            notify(app, &format!("{} 切换预设失败：{}", device_name, e));
+            Switch::Failed { device_name, message: e.to_string() }
+        }
+    };
+    drop(switcher);
+    let _ = app.emit_all("profile_switched", vec![switch]);
+    refresh_tray(app);
+}
+
+fn select_preset(app: &AppHandle, id: &str) {
+    match app.state::<PresetLibrary>().get(id) {
+        Ok(preset) => switch_to(app, &preset),
+        Err(e) => notify(app, &e.to_string()),
+    }
+}
+
+/// 每台已连接的设备切换到下一个或上一个预设
+fn cycle_presets(app: &AppHandle, step: isize) {
+    for device_name in connected_devices(app) {
+        let presets = device_presets(app, device_name);
+        let current = app.state::<ActivePresets>().0.lock().unwrap().get(device_name).cloned();
+        if let Some(preset) = profile::cycle(&presets, current.as_deref(), step) {
+            switch_to(app, preset);
+        }
+    }
+}
+
+/// 替换已注册的全局快捷键，无法注册的快捷键（例如已被其他程序占用）会在错误中列出，其余快捷键仍然有效
+pub fn register_hotkeys(app: &AppHandle, hotkeys: &HotkeySettings) -> Result<()> {
+    let mut manager = app.global_shortcut_manager();
+    if let Err(e) = manager.unregister_all() {
+        warn!("取消注册快捷键失败：{}", e);
+    }
+    let mut failed = vec![];
+    for (accelerator, action) in hotkeys.actions() {
+        let app = app.clone();
+        let result = manager.register(&accelerator, move || {
+            let (app, action) = (app.clone(), action.clone());
+            thread::spawn(move || match action {
+                HotkeyAction::Cycle(step) => cycle_presets(&app, step),
+                HotkeyAction::Select(id) => select_preset(&app, &id),
+            });
+        });
+        if let Err(e) = result {
+            warn!("无法注册快捷键 {}：{}", accelerator, e);
+            failed.push(accelerator);
+        }
+    }
+    if !failed.is_empty() {
+        return Err(Error::Hotkey(failed.join(", ")));
+    }
+    Ok(())
+}
+
+/// 前端在设备连接、断开或预设库变化后调用
+#[tauri::command]
+pub async fn refresh_tray_menu(app: AppHandle) {
+    refresh_tray(&app);
+}
@@ -26,3 +26,50 @@ pub fn compare_version(version1: &str, version2: &str) -> std::cmp::Ordering {
     Equal
 }
 
+/// 有错误级别的诊断时拒绝写入，警告只记录日志
+pub fn check_diagnostics(diagnostics: Vec<meowpad::validate::Diagnostic>) -> crate::error::Result<()> {
+    for d in diagnostics.iter().filter(|d| !d.is_error()) {
+        log::warn!("配置 {} = {} 超出推荐范围 {}-{}", d.path, d.value, d.min, d.max);
+    }
+    if diagnostics.iter().any(|d| d.is_error()) {
+        return Err(crate::error::Error::InvalidConfig(
+            diagnostics.into_iter().filter(|d| d.is_error()).collect(),
+        ));
+    }
+    Ok(())
+}
+
+#[derive(serde::Serialize, Clone)]
+struct UnsavedEvent {
+    model: &'static str,
+    #[serde(flatten)]
+    unsaved: meowpad::models::Unsaved,
+}
+
+/// 通知前端设备是否有未保存的配置
+pub fn emit_unsaved(app: &tauri::AppHandle, model: &'static str, unsaved: meowpad::models::Unsaved) {
+    use tauri::Manager;
+    let _ = app.emit_all("unsaved_changed", UnsavedEvent { model, unsaved });
+}
+
+/// 重新连接时之前的设备还有未保存的配置，通知前端提示用户
+pub fn emit_unsaved_lost(app: &tauri::AppHandle, model: &'static str, unsaved: meowpad::models::Unsaved) {
+    use tauri::Manager;
+    let _ = app.emit_all("unsaved_lost", UnsavedEvent { model, unsaved });
+}
+
+#[derive(serde::Serialize, Clone)]
+struct DecodeEvent {
+    model: &'static str,
+    part: &'static str,
+    #[serde(flatten)]
+    report: meowpad::cbor::DecodeReport,
+}
+
+/// 设备中的配置与当前版本不一致时通知前端，`part` 为 `key` 或 `light`
+pub fn emit_decode_report(app: &tauri::AppHandle, model: &'static str, part: &'static str, report: meowpad::cbor::DecodeReport) {
+    use tauri::Manager;
+    if !report.is_empty() {
+        let _ = app.emit_all("config_decoded", DecodeEvent { model, part, report });
+    }
+}
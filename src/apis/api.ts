@@ -1,6 +1,8 @@
 import { invoke } from "@tauri-apps/api/tauri";
-import { IDevicePreset, IHidDeviceInfo, IVersion } from ".";
+import { DeviceName, IBroadcastFilter, IBroadcastResult, IDevicePreset, IExternalImport, IFirmwareRelease, IHidDeviceInfo, IPresetFilter, IPresetImportResult, IPresetMeta, IPresetReport, IPresetSelection, IPresetMergeResult, IPresetTranslation, IProfileSettings, ISnapshot, IVersion } from ".";
 import { IKeyboard as PureConfig } from "./meowboard/config";
+import { IKeyboard as IKB4K, ILighting as ILT4K } from "./meowpad4k/config";
+import { IKeyboard as IKB3K, ILighting as ILT3K } from "./meowpad3k/config";
 
 export async function connect_iap() {
   return (await invoke("connect_iap")) as void;
@@ -10,7 +12,10 @@ export async function iap_start(data: number[]) {
   // Array.from(new Uint8Array(data))
 }
 export async function iap_flush() {
-  return (await invoke("iap_flush")) as void;
+  return (await invoke("iap_flush")) as string;
+}
+export async function iap_cancel(jobId: string) {
+  return (await invoke("iap_cancel", { jobId })) as boolean;
 }
 export async function check_update(version: IVersion[]) {
   return (await invoke("check_update", { version })) as boolean;
@@ -33,18 +38,111 @@ export async function refresh_devices() {
 export async function connect_device(deviceInfo: IHidDeviceInfo) {
   return (await invoke("connect_device", { deviceInfo })) as boolean;
 }
-export async function load_preset_kb(config: PureConfig, preset: IDevicePreset) {
-  return (await invoke("load_preset_kb", { config, preset })) as PureConfig;
+export async function load_preset_kb(config: PureConfig, preset: IDevicePreset, selection?: IPresetSelection) {
+  return (await invoke("load_preset_kb", { config, preset, selection })) as PureConfig;
 }
 export async function gen_preset_kb(name: string, config: PureConfig) {
   return (await invoke("gen_preset_kb", { name, config })) as IDevicePreset;
 }
+export async function load_preset_4k(key: IKB4K, light: ILT4K, preset: IDevicePreset, selection?: IPresetSelection) {
+  return (await invoke("load_preset_4k", { key, light, preset, selection })) as { key: IKB4K, light: ILT4K };
+}
+export async function gen_preset_4k(name: string, key: IKB4K, light: ILT4K) {
+  return (await invoke("gen_preset_4k", { name, key, light })) as IDevicePreset;
+}
+export async function load_preset_3k(key: IKB3K, light: ILT3K, preset: IDevicePreset, selection?: IPresetSelection) {
+  return (await invoke("load_preset_3k", { key, light, preset, selection })) as { key: IKB3K, light: ILT3K };
+}
+export async function gen_preset_3k(name: string, key: IKB3K, light: ILT3K) {
+  return (await invoke("gen_preset_3k", { name, key, light })) as IDevicePreset;
+}
+export async function list_presets(filter?: IPresetFilter) {
+  return (await invoke("list_presets", { filter })) as IDevicePreset[];
+}
+export async function save_preset(preset: IDevicePreset) {
+  return (await invoke("save_preset", { preset })) as IDevicePreset;
+}
+export async function rename_preset(id: string, name: string) {
+  return (await invoke("rename_preset", { id, name })) as IDevicePreset;
+}
+export async function update_preset_meta(id: string, meta: IPresetMeta) {
+  return (await invoke("update_preset_meta", { id, meta })) as IDevicePreset;
+}
+export async function duplicate_preset(id: string, name: string) {
+  return (await invoke("duplicate_preset", { id, name })) as IDevicePreset;
+}
+export async function delete_preset(id: string) {
+  return (await invoke("delete_preset", { id })) as void;
+}
+export async function import_presets(presets: IDevicePreset[]) {
+  return (await invoke("import_presets", { presets })) as IPresetImportResult[];
+}
+export async function import_preset_file() {
+  return (await invoke("import_preset_file")) as IPresetImportResult | null;
+}
+export async function export_preset_file(id: string) {
+  return (await invoke("export_preset_file", { id })) as boolean;
+}
+export async function merge_presets(base: string, other: string, name: string, resolutions: Record<string, 'base' | 'other'>) {
+  return (await invoke("merge_presets", { base, other, name, resolutions })) as IPresetMergeResult;
+}
+export async function translate_preset(id: string, target: IDevicePreset, mapping?: Record<number, number>) {
+  return (await invoke("translate_preset", { id, target, mapping })) as IPresetTranslation;
+}
+export async function apply_preset_to_devices(preset: IDevicePreset, filter: IBroadcastFilter) {
+  return (await invoke("apply_preset_to_devices", { preset, filter })) as IBroadcastResult[];
+}
+export async function import_external_profile(target: IDevicePreset, travel?: number) {
+  return (await invoke("import_external_profile", { target, travel })) as IExternalImport[] | null;
+}
+export async function encode_preset_code(id: string) {
+  return (await invoke("encode_preset_code", { id })) as string;
+}
+export async function import_preset_code(code: string, device_name: DeviceName) {
+  return (await invoke("import_preset_code", { code, deviceName: device_name })) as IPresetImportResult;
+}
 export async function load_preset_from_file() {
   return (await invoke("load_preset_from_file")) as IDevicePreset | null;
 }
+export async function check_preset(preset: IDevicePreset) {
+  return (await invoke("check_preset", { preset })) as IPresetReport;
+}
+export async function get_profile_settings() {
+  return (await invoke("get_profile_settings")) as IProfileSettings;
+}
+export async function set_profile_settings(settings: IProfileSettings) {
+  return (await invoke("set_profile_settings", { settings })) as void;
+}
+export async function get_window_source() {
+  return (await invoke("get_window_source")) as string;
+}
+export async function refresh_tray_menu() {
+  return (await invoke("refresh_tray_menu")) as void;
+}
 export async function save_preset_to_file(preset: IDevicePreset) {
   return (await invoke("save_preset_to_file", { preset }));
 }
 export async function update_firmware_call() {
   return (await invoke("update_firmware_call"));
 }
+export async function get_firmware_releases(product: DeviceName) {
+  return (await invoke("get_firmware_releases", { product })) as IFirmwareRelease[];
+}
+export async function download_firmware(product: DeviceName, version: string) {
+  return (await invoke("download_firmware", { product, version })) as number[];
+}
+export async function list_snapshots(serial: string) {
+  return (await invoke("list_snapshots", { serial })) as ISnapshot[];
+}
+export async function label_snapshot(serial: string, id: number, label?: string) {
+  return (await invoke("label_snapshot", { serial, id, label })) as void;
+}
+export async function delete_snapshot(serial: string, id: number) {
+  return (await invoke("delete_snapshot", { serial, id })) as void;
+}
+export async function get_history_limit() {
+  return (await invoke("get_history_limit")) as number;
+}
+export async function set_history_limit(limit: number) {
+  return (await invoke("set_history_limit", { limit })) as void;
+}
@@ -3,8 +3,10 @@ import { KeyCode } from "@/keycode";
 import * as api4k from '@/apis/meowpad4k/api'
 import * as api3k from '@/apis/meowpad3k/api'
 import * as api from '@/apis/api'
+import { ILighting as ILT4K } from '@/apis/meowpad4k/config'
+import { ILighting as ILT3K } from '@/apis/meowpad3k/config'
 
-export declare type Error = 'DeviceDisconnected' | 'DeviceNotFound' | 'Network' | 'Meowpad' | 'Iap';
+export declare type Error = 'DeviceDisconnected' | 'DeviceNotFound' | 'Network' | 'Meowpad' | 'Iap' | 'invalid_preset' | 'invalid_share_code' | 'share_code_model_mismatch' | 'hotkey' | 'unsupported_profile' | 'invalid_preset_id';
 export declare type DeviceName = 'Meowpad' | 'Meowpad SE v2' | 'Pure64';
 export declare type KeyType = 'None' | 'Keyboard' | 'Custom' | 'Mouse' | 'Media';
 
@@ -13,6 +15,41 @@ export interface IError {
     data?: any
 }
 
+export interface IDiagnostic {
+    path: string
+    value: number
+    min: number
+    max: number
+    severity: 'warning' | 'error'
+}
+
+export interface IParseError {
+    format: 'toml' | 'json' | 'yaml'
+    line?: number
+    column?: number
+    message: string
+}
+
+export interface IRawConfigCheck {
+    format: 'toml' | 'json' | 'yaml'
+    error?: IParseError
+    diagnostics: IDiagnostic[]
+    unknown: string[]
+}
+
+export interface IChange {
+    path: string
+    old: any
+    new: any
+}
+
+export interface IConfigDiff {
+    keys: { index: number, changes: IChange[] }[]
+    keymap: { layer: number, position: number, old: any, new: any }[]
+    light: IChange[]
+    device: IChange[]
+}
+
 export interface IDeviceStatus {
     key: boolean
     light?: boolean
@@ -20,9 +57,71 @@ export interface IDeviceStatus {
     enabled: boolean
 }
 
+export interface IDescriptor {
+    pv: number
+    kc: number
+    sv: number
+    mps: number
+    pk: number[]
+    lm: number[]
+}
+
 export interface IDeviceInfo {
     name: DeviceName
     version: string
+    serial: string
+    descriptor: IDescriptor | null
+}
+
+export declare type IapPhase = 'programming' | 'verifying' | 'done' | 'cancelled' | 'failed';
+
+export interface IIapProgress {
+    job_id: string
+    phase: IapPhase
+    bytes_done: number
+    total_bytes: number
+    throughput: number
+    eta?: number
+    error?: string
+}
+
+export interface IFirmwareRelease {
+    version: string
+    notes: string
+    date: string
+    url: string
+    sha256: string
+    min_bootloader?: string
+    cached: boolean
+}
+
+export interface IV1Import {
+    config: string
+    ignored: string[]
+}
+
+export interface IUnsaved {
+    key: boolean
+    light: boolean
+}
+
+export interface IDecodeReport {
+    model: 'kb' | '4k' | '3k'
+    part: 'key' | 'light'
+    defaulted: string[]
+    ignored: string[]
+}
+
+export declare type SnapshotReason = 'write' | 'save' | 'clear' | 'firmware' | 'restore' | 'manual';
+
+export interface ISnapshot {
+    id: number
+    timestamp: number
+    reason: SnapshotReason
+    label?: string
+    model: 'kb' | '4k' | '3k'
+    config: object
+    hall: IKeyHallConfig[]
 }
 
 export interface IVersion {
@@ -91,7 +190,111 @@ export interface IDevicePreset {
     id: string
     name: string
     device: IDevicePresetInfo
+    version?: number
     config: IPresetConfig
+    meta?: IPresetMeta
+}
+
+export interface IPresetMeta {
+    tags: string[]
+    author?: string
+    notes?: string
+    created: number
+    modified: number
+}
+
+export interface IPresetFilter {
+    text?: string
+    device_name?: DeviceName
+    tags?: string[]
+}
+
+export interface IPresetIssue {
+    kind: 'unsupported_version' | 'unknown_device' | 'device_mismatch' | 'wrong_length' | 'unknown_key_code' | 'unsupported' | 'malformed'
+    path?: string
+    version?: number
+    device_name?: string
+    expected?: string | number
+    actual?: number
+    value?: number
+    message?: string
+}
+
+export interface IPresetReport {
+    issues: IPresetIssue[]
+    diagnostics: IDiagnostic[]
+}
+
+export interface IPresetSelection {
+    layers?: number[]
+    key_configs?: boolean
+    key_data?: boolean
+    key_settings?: boolean
+    light?: boolean
+    keys?: number[]
+}
+
+export type IPresetMergeResult =
+    | { status: 'merged', preset: IDevicePreset }
+    | { status: 'conflicts', conflicts: string[] }
+
+export type IPresetTranslateWarning =
+    | { kind: 'unmapped', keys: number[] }
+    | { kind: 'dropped', path: string }
+    | { kind: 'binding', key: number }
+    | { kind: 'clamped', path: string }
+
+export interface IPresetTranslation {
+    preset: IDevicePreset
+    mapping: Record<number, number>
+    warnings: IPresetTranslateWarning[]
+}
+
+export interface IBroadcastFilter {
+    device_name: DeviceName
+    serials?: string[]
+}
+
+export type IBroadcastResult = { serial_number?: string } & (
+    | { status: 'applied' }
+    | { status: 'failed', message: string }
+    | { status: 'not_found' }
+)
+
+export type IImportWarning =
+    | { kind: 'unknown_key', name: string }
+    | { kind: 'unmapped', keys: string[] }
+    | { kind: 'clamped', path: string }
+    | { kind: 'dropped', path: string }
+
+export type IExternalImport = IPresetImportResult & { warnings: IImportWarning[] }
+
+export interface IProfileRule {
+    app: string
+    title?: string
+    preset_id: string
+}
+
+export interface IProfileSettings {
+    enabled: boolean
+    rules: IProfileRule[]
+    hotkeys: IHotkeySettings
+}
+
+export interface IHotkeySettings {
+    next?: string
+    previous?: string
+    presets: { accelerator: string, preset_id: string }[]
+}
+
+export type IProfileSwitch =
+    | { kind: 'applied', device_name: DeviceName, preset_id: string, name: string, saved: boolean }
+    | { kind: 'restored', device_name: DeviceName }
+    | { kind: 'failed', device_name: DeviceName, message: string }
+
+export interface IPresetImportResult {
+    status: 'added' | 'merged' | 'updated'
+    preset: IDevicePreset
 }
 
 
@@ -104,8 +307,21 @@ export interface IDevicePresetInfo {
 export interface IPresetConfig {
     key_layers?: IKeyPresetLayer[]
     key_configs?: IKeyPresetConfig[]
+    key_data?: KeyCode[][]
+    side_btn?: KeyCode[]
+    key_settings?: IKeyPresetSettings
+    light?: IPresetLight
+}
+
+export interface IKeyPresetSettings {
+    jitters_elimination_time: number
+    continuous_report: boolean
+    kalman_filter: boolean
+    enable_hs?: boolean
 }
 
+export declare type IPresetLight = ({ model: '4k' } & ILT4K) | ({ model: '3k' } & ILT3K)
+
 export interface IKeyPresetLayer {
     keys: IMixedKey[]
 }
@@ -1,6 +1,6 @@
 import { invoke } from "@tauri-apps/api/tauri";
-import { IDeviceInfo, IDeviceStatus, IKeyHallConfig, IKeyRTStatus, KeyState } from "..";
-import { IKeyboard } from "./config";
+import { IDeviceInfo, IDeviceStatus, IKeyHallConfig, IKeyRTStatus, KeyState, IRawConfigCheck, IConfigDiff, ISnapshot, IUnsaved } from "..";
+import { ICapabilities, IKeyboard } from "./config";
 
 export async function calibration_key(keyIndexs: number[]) {
   return (await invoke("calibration_key_kb", { keyIndexs })) as void;
@@ -41,12 +41,34 @@ export async function clear_config() {
 export async function get_raw_config() {
   return (await invoke("get_raw_config_kb")) as string;
 }
+
 export async function check_raw_config(config: string) {
-  return (await invoke("check_raw_config_kb", { config })) as boolean;
+  return (await invoke("check_raw_config_kb", { config })) as IRawConfigCheck;
 }
 export async function save_raw_config(config: string) {
   return (await invoke("save_raw_config_kb", { config })) as void;
 }
+export async function apply_config(key: IKeyboard) {
+  return (await invoke("apply_config_kb", { key })) as void;
+}
+export async function get_unsaved() {
+  return (await invoke("get_unsaved_kb")) as IUnsaved;
+}
+export async function diff_key_config(old: IKeyboard, new_: IKeyboard) {
+  return (await invoke("diff_key_config_kb", { old, new: new_ })) as IConfigDiff;
+}
+export async function diff_raw_config(config: string) {
+  return (await invoke("diff_raw_config_kb", { config })) as IConfigDiff;
+}
+export async function diff_unsaved_config() {
+  return (await invoke("diff_unsaved_config_kb")) as IConfigDiff;
+}
+export async function create_snapshot(label?: string) {
+  return (await invoke("create_snapshot_kb", { label })) as ISnapshot;
+}
+export async function restore_snapshot(id: number) {
+  return (await invoke("restore_snapshot_kb", { id })) as void;
+}
 export async function connect() {
   return (await invoke("connect_kb")) as boolean;
 }
@@ -59,6 +81,9 @@ export async function get_device_info() {
 export async function get_device_status() {
   return (await invoke("get_device_status_kb")) as IDeviceStatus;
 }
+export async function get_capabilities() {
+  return (await invoke("get_capabilities_kb")) as ICapabilities;
+}
 export async function get_hall_config() {
   return (await invoke("get_hall_config_kb")) as IKeyHallConfig[];
 }
@@ -12,6 +12,11 @@ export interface IKeyConfigBoard {
 }
 
 
+export interface ICapabilities {
+    release_dead_zone: boolean
+    rt_toggle: boolean
+}
+
 export interface IKeyboard {
     keys: IKeyConfigBoard[]
     normal_layer: IMixedKey[]
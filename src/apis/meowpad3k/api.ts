@@ -1,6 +1,6 @@
 import { invoke } from "@tauri-apps/api/tauri";
 import { IDebugValue, IKeyboard, ILighting } from "./config";
-import { IDeviceInfo, IDeviceStatus } from "..";
+import { IDeviceInfo, IDeviceStatus, IRawConfigCheck, IConfigDiff, ISnapshot, IUnsaved } from "..";
 
 export async function calibration_key() {
   return (await invoke("calibration_key_3k")) as void;
@@ -41,12 +41,37 @@ export async function clear_config() {
 export async function get_raw_config() {
   return (await invoke("get_raw_config_3k")) as string;
 }
+
 export async function check_raw_config(config: string) {
-  return (await invoke("check_raw_config_3k", { config })) as boolean;
+  return (await invoke("check_raw_config_3k", { config })) as IRawConfigCheck;
 }
 export async function save_raw_config(config: string) {
   return (await invoke("save_raw_config_3k", { config })) as void;
 }
+export async function apply_config(key: IKeyboard, light: ILighting) {
+  return (await invoke("apply_config_3k", { key, light })) as void;
+}
+export async function get_unsaved() {
+  return (await invoke("get_unsaved_3k")) as IUnsaved;
+}
+export async function diff_key_config(old: IKeyboard, new_: IKeyboard) {
+  return (await invoke("diff_key_config_3k", { old, new: new_ })) as IConfigDiff;
+}
+export async function diff_light_config(old: ILighting, new_: ILighting) {
+  return (await invoke("diff_light_config_3k", { old, new: new_ })) as IConfigDiff;
+}
+export async function diff_raw_config(config: string) {
+  return (await invoke("diff_raw_config_3k", { config })) as IConfigDiff;
+}
+export async function diff_unsaved_config() {
+  return (await invoke("diff_unsaved_config_3k")) as IConfigDiff;
+}
+export async function create_snapshot(label?: string) {
+  return (await invoke("create_snapshot_3k", { label })) as ISnapshot;
+}
+export async function restore_snapshot(id: number) {
+  return (await invoke("restore_snapshot_3k", { id })) as void;
+}
 export async function connect() {
   return (await invoke("connect_3k")) as boolean;
 }
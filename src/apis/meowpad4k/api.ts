@@ -1,6 +1,6 @@
 import { invoke } from "@tauri-apps/api/tauri";
-import { IDeviceInfo, IDeviceStatus, IKeyRTStatus } from "..";
-import { IKeyboard, ILighting } from "./config";
+import { IDeviceInfo, IDeviceStatus, IKeyRTStatus, IRawConfigCheck, IConfigDiff, ISnapshot, IUnsaved, IV1Import } from "..";
+import { ICapabilities, IKeyboard, ILighting } from "./config";
 
 export async function calibration_key() {
   return (await invoke("calibration_key_4k")) as void;
@@ -41,12 +41,40 @@ export async function clear_config() {
 export async function get_raw_config() {
   return (await invoke("get_raw_config_4k")) as string;
 }
+
 export async function check_raw_config(config: string) {
-  return (await invoke("check_raw_config_4k", { config })) as boolean;
+  return (await invoke("check_raw_config_4k", { config })) as IRawConfigCheck;
 }
 export async function save_raw_config(config: string) {
   return (await invoke("save_raw_config_4k", { config })) as void;
 }
+export async function apply_config(key: IKeyboard, light: ILighting) {
+  return (await invoke("apply_config_4k", { key, light })) as void;
+}
+export async function import_v1_config(data: number[]) {
+  return (await invoke("import_v1_config_4k", { data })) as IV1Import;
+}
+export async function get_unsaved() {
+  return (await invoke("get_unsaved_4k")) as IUnsaved;
+}
+export async function diff_key_config(old: IKeyboard, new_: IKeyboard) {
+  return (await invoke("diff_key_config_4k", { old, new: new_ })) as IConfigDiff;
+}
+export async function diff_light_config(old: ILighting, new_: ILighting) {
+  return (await invoke("diff_light_config_4k", { old, new: new_ })) as IConfigDiff;
+}
+export async function diff_raw_config(config: string) {
+  return (await invoke("diff_raw_config_4k", { config })) as IConfigDiff;
+}
+export async function diff_unsaved_config() {
+  return (await invoke("diff_unsaved_config_4k")) as IConfigDiff;
+}
+export async function create_snapshot(label?: string) {
+  return (await invoke("create_snapshot_4k", { label })) as ISnapshot;
+}
+export async function restore_snapshot(id: number) {
+  return (await invoke("restore_snapshot_4k", { id })) as void;
+}
 export async function connect() {
   return (await invoke("connect_4k")) as boolean;
 }
@@ -59,6 +87,9 @@ export async function get_device_info() {
 export async function get_device_status() {
   return (await invoke("get_device_status_4k")) as IDeviceStatus;
 }
+export async function get_capabilities() {
+  return (await invoke("get_capabilities_4k")) as ICapabilities;
+}
 export async function get_firmware_version() {
   return (await invoke("get_firmware_4k_version")) as string;
 }
@@ -1,6 +1,10 @@
 import { IRgb } from "@/interface"
 import { IKeyConfig } from ".."
 
+export interface ICapabilities {
+    enable_hs: boolean
+}
+
 export interface IKeyboard {
     keys: IKeyConfig[]
     jitters_elimination_time: number
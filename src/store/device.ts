@@ -1,4 +1,5 @@
-import { IDeviceInfo, IDeviceStatus, IHidDeviceInfo } from "../apis";
+import { IDeviceInfo, IDeviceStatus, IHidDeviceInfo, IRawConfigCheck, IUnsaved } from "../apis";
+import { listen } from "@tauri-apps/api/event";
 import { defineStore, acceptHMRUpdate } from "pinia";
 import { IKeyboard as IKB4K, ILighting as ILT4K } from "../apis/meowpad4k/config";
 import { IKeyboard as IKB3K, ILighting as ILT3K, LightingMode as LM3K } from "../apis/meowpad3k/config";
@@ -16,7 +17,22 @@ export const useDeviceStore = defineStore("device", () => {
   const device_status = ref<IDeviceStatus | undefined>(undefined);
   const device_hid_info = ref<IHidDeviceInfo | undefined>(undefined);
 
+  // 设备描述中的按键数量与灯光模式，旧固件没有描述时为 undefined，按型号的默认值显示
+  const key_count = computed(() => device_info.value?.descriptor?.kc);
+  const lighting_modes = computed(() => device_info.value?.descriptor?.lm);
+  function has_key(index: number | undefined) {
+    return index != undefined && (key_count.value == undefined || index < key_count.value);
+  }
+  function has_lighting_mode(mode: number) {
+    return !lighting_modes.value?.length || lighting_modes.value.includes(mode);
+  }
+
   const raw_config = ref<string | undefined>(undefined);
+  // 已写入设备内存但尚未保存到闪存的更改
+  const unsaved = ref<IUnsaved>({ key: false, light: false });
+  listen<IUnsaved>("unsaved_changed", (event) => {
+    unsaved.value = { key: event.payload.key, light: event.payload.light };
+  });
 
   // configs
   const device_config = ref<IKBB | undefined>(undefined);
@@ -78,6 +94,26 @@ export const useDeviceStore = defineStore("device", () => {
     }
   }
 
+  // 读取设备内存中的配置，例如自动切换预设之后
+  async function load_config() {
+    if (is_4k()) {
+      key_config.value = await api4k.get_key_config()
+      extract_key_config_4k()
+      light_config.value = await api4k.get_light_config()
+      extract_light_config_4k()
+    }
+    if (is_3k()) {
+      key_config.value = await api3k.get_key_config()
+      extract_key_config_3k()
+      light_config.value = await api3k.get_light_config()
+      extract_light_config_3k()
+    }
+    if (is_pure()) {
+      device_config.value = await apib.get_key_config()
+      extract_key_config_pure64()
+    }
+  }
+
   async function get_config_raw() {
     if (is_4k()) {
       raw_config.value = await api4k.get_raw_config()
@@ -102,7 +138,7 @@ export const useDeviceStore = defineStore("device", () => {
     }
   }
 
-  async function check_config_raw() {
+  async function check_config_raw(): Promise<IRawConfigCheck | undefined> {
     if (is_4k()) {
       return await api4k.check_raw_config(raw_config.value!)
     }
@@ -113,7 +149,7 @@ export const useDeviceStore = defineStore("device", () => {
       return await apib.check_raw_config(raw_config.value!)
     }
     // 无设备连接时不检查，永远通过
-    return true
+    return undefined
   }
 
   function store_key_config_pure64() {
@@ -280,9 +316,14 @@ export const useDeviceStore = defineStore("device", () => {
     key_config,
     light_config,
     raw_config,
+    unsaved,
     connected,
     device_info,
     device_status,
+    key_count,
+    lighting_modes,
+    has_key,
+    has_lighting_mode,
     led_colors,
     low_speed_color,
     high_speed_color,
@@ -304,6 +345,7 @@ export const useDeviceStore = defineStore("device", () => {
     is_pure,
     try_connect,
     get_status,
+    load_config,
     get_config_raw,
     check_config_raw,
     save_config_raw,
@@ -1,5 +1,6 @@
 import { defineStore, acceptHMRUpdate } from "pinia";
-import { DeviceName, IDevicePreset, IHidDeviceInfo, IVersion } from "@/apis";
+import { DeviceName, IDevicePreset, IHidDeviceInfo, IPresetFilter, IProfileSettings, IVersion } from "@/apis";
+import * as api from "@/apis/api";
 import { Type } from "naive-ui/es/button/src/interface";
 import { Toggle } from "@/interface";
 import { LOCALES, setI18nLanguage } from "@/locales";
@@ -26,7 +27,9 @@ export const useStore = defineStore("main", () => {
   const device_list = ref<IHidDeviceInfo[]>([]);
   const firmware_versions = ref<Map<DeviceName, string>>(new Map());
   const presets = ref<IDevicePreset[]>([]);
+  const preset_filter = ref<IPresetFilter>({});
   const current_preset = ref<IDevicePreset | null>(null);
+  const profile_settings = ref<IProfileSettings>({ enabled: false, rules: [], hotkeys: { presets: [] } });
 
   if (navigator.language === "zh-CN") {
     setLang("zh");
@@ -47,9 +50,21 @@ export const useStore = defineStore("main", () => {
       bottom_dz_available.value === Toggle.On
     );
     await app_store.save();
+  }
+
+  // 预设保存在应用数据目录的预设库中，托盘菜单同时更新
+  async function load_presets() {
+    presets.value = await api.list_presets(preset_filter.value)
+    await api.refresh_tray_menu()
+  }
 
-    await device_presets_store.set("presets", presets.value);
-    await device_presets_store.save();
+  // 按前台应用自动切换预设的规则与切换预设的全局快捷键
+  async function load_profiles() {
+    profile_settings.value = await api.get_profile_settings()
+  }
+
+  async function save_profiles() {
+    await api.set_profile_settings(profile_settings.value)
   }
 
   async function load() {
@@ -65,12 +80,15 @@ export const useStore = defineStore("main", () => {
       bottom_dz_available.value = Toggle.Off;
     }
 
+    // 旧版本保存在设置中的预设，导入预设库后删除
     const p = await device_presets_store.get<IDevicePreset[]>("presets")
-    if (p) {
-      presets.value = p
-    } else {
-      presets.value = []
+    if (p && p.length > 0) {
+      await api.import_presets(p)
+      await device_presets_store.delete("presets")
+      await device_presets_store.save()
     }
+    await load_presets()
+    await load_profiles()
   }
 
   return {
@@ -93,7 +111,12 @@ export const useStore = defineStore("main", () => {
     lang,
     app_store,
     presets,
+    preset_filter,
     device_presets_store,
+    load_presets,
+    profile_settings,
+    load_profiles,
+    save_profiles,
     setLang,
     save,
     load,
@@ -1,5 +1,5 @@
 import { useI18n } from "vue-i18n";
-import { IError } from "./apis";
+import { IError, IPresetReport } from "./apis";
 import { KeyCode, mapping } from "./keycode";
 import { IRgb } from "./interface";
 import { IMixedKey } from "@/apis";
@@ -43,6 +43,14 @@ export function getErrorMsg(t, e: IError): string {
   return e.toString();
 }
 
+export function getPresetReportMsg(t, report: IPresetReport): string {
+  const issues = report.issues.map((i) => i.path ?? i.device_name ?? i.message ?? t(i.kind));
+  const diagnostics = report.diagnostics
+    .filter((d) => d.severity === "error")
+    .map((d) => `${d.path} = ${d.value} (${d.min}-${d.max})`);
+  return t("invalid_preset", { e: [...issues, ...diagnostics].join(", ") });
+}
+
 export function formatKeys(keycodes: KeyCode[]) {
   const keys = keycodes
     .filter((k) => k != KeyCode.None)